@@ -0,0 +1,514 @@
+use std::backtrace::Backtrace;
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::Instant;
+use log::{debug, info};
+use crate::{BeansError, helper};
+
+/// Header that every connectionless packet starts with.
+pub const A2S_HEADER: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+pub const A2S_INFO_REQUEST: u8 = 0x54;
+pub const A2S_INFO_PAYLOAD: &[u8] = b"Source Engine Query\0";
+pub const A2S_INFO_RESPONSE: u8 = 0x49;
+pub const S2C_CHALLENGE: u8 = 0x41;
+/// How long to wait for the server to respond.
+pub const A2S_TIMEOUT: Duration = Duration::from_secs(3);
+/// Amount of times in a row that the server can fail to respond before it's assumed to not be
+/// running. Responses can be dropped, or the server can be busy changing the map.
+pub const A2S_ATTEMPTS: u32 = 3;
+/// How long to wait before querying the server again when it didn't respond.
+pub const A2S_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Default value for `WaitForEmptyConfig.timeout`, in seconds.
+pub const DEFAULT_WAIT_TIMEOUT: u64 = 3600;
+/// Default value for `WaitForEmptyConfig.interval`, in seconds.
+pub const DEFAULT_WAIT_INTERVAL: u64 = 30;
+
+/// Postpone updates until the dedicated server is empty. Read from the `wait_for_empty`
+/// property in `.adastral`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WaitForEmptyConfig
+{
+    /// Address of the server (e.g; `127.0.0.1:27015`)
+    pub address: String,
+    /// Longest amount of seconds to wait for the server to be empty. The update continues
+    /// after this, even when there are still players.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Seconds between checking the player count.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u64>,
+    /// Count bots as players. Defaults to `false`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count_bots: Option<bool>,
+    /// Stop the update when the server doesn't respond, instead of assuming that it isn't
+    /// running. Defaults to `false`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_when_unreachable: Option<bool>
+}
+impl WaitForEmptyConfig
+{
+    /// Get the config for the install in `sourcemods_location`, when it has been set.
+    pub fn get(sourcemods_location: String) -> Option<Self>
+    {
+        crate::version::get_version_file(Some(sourcemods_location)).and_then(|v| v.wait_for_empty)
+    }
+    /// Amount of players in `info`, with bots removed unless `count_bots` is set.
+    pub fn player_count(&self, info: &A2sInfo) -> u8
+    {
+        match self.count_bots.unwrap_or(false) {
+            true => info.players,
+            false => info.players.saturating_sub(info.bots)
+        }
+    }
+}
+
+/// Response to A2S_INFO. Only the fields that are used are included.
+/// https://developer.valvesoftware.com/wiki/Server_queries#A2S_INFO
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct A2sInfo
+{
+    pub name: String,
+    pub map: String,
+    pub folder: String,
+    pub game: String,
+    pub players: u8,
+    pub max_players: u8,
+    pub bots: u8
+}
+
+/// Send A2S_INFO to the server at `address`, and parse the response.
+pub async fn query_info(address: &str) -> Result<A2sInfo, BeansError>
+{
+    let socket = with_timeout(address, UdpSocket::bind("0.0.0.0:0")).await?;
+    with_timeout(address, socket.connect(address)).await?;
+
+    let mut request: Vec<u8> = A2S_HEADER.to_vec();
+    request.push(A2S_INFO_REQUEST);
+    request.extend_from_slice(A2S_INFO_PAYLOAD);
+    with_timeout(address, socket.send(&request)).await?;
+
+    let mut buffer = [0u8; 1400];
+    let mut length = with_timeout(address, socket.recv(&mut buffer)).await?;
+    // newer servers want the request to be sent again with the challenge that they responded with.
+    if length >= 9 && buffer[..4] == A2S_HEADER && buffer[4] == S2C_CHALLENGE {
+        debug!("[a2s::query_info] Got challenge from {}", address);
+        request.extend_from_slice(&buffer[5..9]);
+        with_timeout(address, socket.send(&request)).await?;
+        length = with_timeout(address, socket.recv(&mut buffer)).await?;
+    }
+    parse_info(&buffer[..length]).map_err(|message| a2s_error(address, std::io::Error::new(std::io::ErrorKind::InvalidData, message)))
+}
+
+/// Wait for `io` (on the socket for `address`) for up to `A2S_TIMEOUT`.
+async fn with_timeout<T, F>(address: &str, io: F) -> Result<T, BeansError>
+    where F: Future<Output = std::io::Result<T>>
+{
+    match tokio::time::timeout(A2S_TIMEOUT, io).await {
+        Ok(Ok(v)) => Ok(v),
+        Ok(Err(e)) => Err(a2s_error(address, e)),
+        Err(_) => Err(a2s_error(address, std::io::Error::new(std::io::ErrorKind::TimedOut, format!("No response after {}s", A2S_TIMEOUT.as_secs()))))
+    }
+}
+
+fn a2s_error(address: &str, error: std::io::Error) -> BeansError
+{
+    BeansError::A2sQueryFailure {
+        address: address.to_string(),
+        error,
+        backtrace: Backtrace::capture()
+    }
+}
+
+/// Parse the A2S_INFO response in `data`.
+pub fn parse_info(data: &[u8]) -> Result<A2sInfo, String>
+{
+    if data.len() < 6 || data[..4] != A2S_HEADER {
+        return Err("Invalid header (split packets aren't supported)".to_string());
+    }
+    if data[4] != A2S_INFO_RESPONSE {
+        return Err(format!("Unexpected response type 0x{:02X}", data[4]));
+    }
+    // skip the protocol version.
+    let mut offset = 6;
+    let mut read_string = || -> Result<String, String> {
+        let end = data[offset..].iter().position(|v| *v == 0)
+            .ok_or("Unterminated string".to_string())?;
+        let value = String::from_utf8_lossy(&data[offset..offset + end]).to_string();
+        offset += end + 1;
+        Ok(value)
+    };
+    let name = read_string()?;
+    let map = read_string()?;
+    let folder = read_string()?;
+    let game = read_string()?;
+    // skip the Steam App ID (u16).
+    let rest = &data[offset..];
+    if rest.len() < 5 {
+        return Err("Response is too short".to_string());
+    }
+    Ok(A2sInfo {
+        name,
+        map,
+        folder,
+        game,
+        players: rest[2],
+        max_players: rest[3],
+        bots: rest[4]
+    })
+}
+
+/// What was decided when waiting for the server to be empty.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum WaitForEmptyDecision
+{
+    /// The server didn't respond `A2S_ATTEMPTS` times in a row, so it's assumed to not be running.
+    Unreachable,
+    /// There are no players on the server.
+    Empty,
+    /// The server still had players after waiting for `waited` seconds.
+    TimedOut {
+        players: u8,
+        waited: u64
+    }
+}
+impl Display for WaitForEmptyDecision {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WaitForEmptyDecision::Unreachable
+            => write!(f, "the server didn't respond, so it's assumed to not be running"),
+            WaitForEmptyDecision::Empty
+            => write!(f, "the server is empty"),
+            WaitForEmptyDecision::TimedOut { players, waited }
+            => write!(f, "there are still {} player(s) after waiting {}s", players, waited)
+        }
+    }
+}
+
+/// Wait until there are no players on the server, or until `config.timeout` has passed.
+pub async fn wait_for_empty(config: &WaitForEmptyConfig) -> WaitForEmptyDecision
+{
+    let timeout = Duration::from_secs(config.timeout.unwrap_or(DEFAULT_WAIT_TIMEOUT));
+    let interval = Duration::from_secs(config.interval.unwrap_or(DEFAULT_WAIT_INTERVAL).max(1));
+    let started = Instant::now();
+    let mut failures: u32 = 0;
+    loop {
+        let info = match query_info(&config.address).await {
+            Ok(v) => {
+                failures = 0;
+                v
+            },
+            Err(e) => {
+                failures += 1;
+                debug!("[a2s::wait_for_empty] {:}", e);
+                if failures >= A2S_ATTEMPTS || started.elapsed() + A2S_RETRY_DELAY > timeout {
+                    return WaitForEmptyDecision::Unreachable;
+                }
+                info!("[a2s::wait_for_empty] {} didn't respond, trying again in {}s ({}/{})",
+                    config.address, A2S_RETRY_DELAY.as_secs(), failures, A2S_ATTEMPTS);
+                tokio::time::sleep(A2S_RETRY_DELAY).await;
+                continue;
+            }
+        };
+        let players = config.player_count(&info);
+        if players == 0 {
+            return WaitForEmptyDecision::Empty;
+        }
+        if started.elapsed() >= timeout {
+            return WaitForEmptyDecision::TimedOut {
+                players,
+                waited: started.elapsed().as_secs()
+            };
+        }
+        info!("[a2s::wait_for_empty] {} player(s) on {}, checking again in {}s ({}s left)",
+            players, info.map, interval.as_secs(), timeout.saturating_sub(started.elapsed()).as_secs());
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Name of the file in the mod folder that has what was decided the last time an update waited
+/// for the server to be empty.
+pub const LAST_WAIT_FILENAME: &str = ".adastral_wait_for_empty";
+/// Content of `LAST_WAIT_FILENAME`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct LastWaitForEmpty
+{
+    pub decision: WaitForEmptyDecision,
+    /// When the decision was made, formatted with RFC 3339.
+    pub time: String
+}
+impl LastWaitForEmpty
+{
+    pub fn new(decision: WaitForEmptyDecision) -> Self
+    {
+        Self {
+            decision,
+            time: chrono::Local::now().to_rfc3339()
+        }
+    }
+    /// Read `LAST_WAIT_FILENAME` from `mod_location`. `None` is returned when an update hasn't
+    /// waited for the server yet, or when the file can't be read.
+    pub fn read(mod_location: String) -> Option<Self>
+    {
+        let location = helper::join_path(mod_location, LAST_WAIT_FILENAME.to_string());
+        let content = std::fs::read_to_string(&location).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                debug!("[LastWaitForEmpty::read] Failed to parse {} ({:})", location, e);
+                None
+            }
+        }
+    }
+    /// Write to `LAST_WAIT_FILENAME` in `mod_location`.
+    pub fn write(&self, mod_location: String) -> Result<(), BeansError>
+    {
+        let location = helper::join_path(mod_location, LAST_WAIT_FILENAME.to_string());
+        let content = serde_json::to_string(self)?;
+        if let Err(e) = std::fs::write(&location, content) {
+            return Err(BeansError::FileWriteFailure {
+                location,
+                error: e
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Player count of the dedicated server, which is shown by the `status` subcommand.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ServerStatus
+{
+    pub address: String,
+    /// `None` when the server didn't respond.
+    pub info: Option<A2sInfo>,
+    /// Amount of players, following `WaitForEmptyConfig.count_bots`.
+    pub players: Option<u8>,
+    /// What was decided the last time an update waited for the server to be empty.
+    pub last_wait: Option<LastWaitForEmpty>
+}
+
+/// Query the server in `config` once, without waiting. The last decision is read from the
+/// install in `mod_location`.
+pub async fn get_server_status(config: &WaitForEmptyConfig, mod_location: String) -> ServerStatus
+{
+    let info = match query_info(&config.address).await {
+        Ok(v) => Some(v),
+        Err(e) => {
+            debug!("[a2s::get_server_status] {:}", e);
+            None
+        }
+    };
+    let players = info.as_ref().map(|v| config.player_count(v));
+    ServerStatus {
+        address: config.address.clone(),
+        info,
+        players,
+        last_wait: LastWaitForEmpty::read(mod_location)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use crate::helper::test_dir::TestDir;
+
+    const CHALLENGE: [u8; 4] = [0x0A, 0x0B, 0x0C, 0x0D];
+
+    /// Response that `StandInServer` sends for a request.
+    #[derive(Clone, Copy)]
+    enum Reply
+    {
+        /// Respond with `S2C_CHALLENGE`, and expect the next request to have the challenge.
+        Challenge,
+        /// Respond with A2S_INFO, with this amount of players and 1 bot.
+        Info(u8),
+        /// Respond with something that isn't an A2S_INFO response.
+        Garbage
+    }
+
+    /// Stand-in for a dedicated server that responds to A2S_INFO with `replies`, in order.
+    ///
+    /// It runs on the same runtime as the test, so it has responded before the clock is advanced
+    /// in the tests that pause time (the runtime is only idle once nothing can make progress).
+    struct StandInServer
+    {
+        address: String,
+        requests: Arc<Mutex<Vec<Vec<u8>>>>
+    }
+    impl StandInServer
+    {
+        async fn start(replies: Vec<Reply>) -> Self
+        {
+            let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let address = socket.local_addr().unwrap().to_string();
+            let requests: Arc<Mutex<Vec<Vec<u8>>>> = Arc::new(Mutex::new(Vec::new()));
+            let recorded = requests.clone();
+            tokio::spawn(async move {
+                let mut buffer = [0u8; 1400];
+                for reply in replies {
+                    let (length, from) = match socket.recv_from(&mut buffer).await {
+                        Ok(v) => v,
+                        Err(_) => return
+                    };
+                    recorded.lock().unwrap().push(buffer[..length].to_vec());
+                    let mut response: Vec<u8> = A2S_HEADER.to_vec();
+                    match reply {
+                        Reply::Challenge => {
+                            response.push(S2C_CHALLENGE);
+                            response.extend_from_slice(&CHALLENGE);
+                        },
+                        Reply::Info(players) => response = info_packet(players, 1),
+                        Reply::Garbage => response.extend_from_slice(b"\x49\x11truncated")
+                    }
+                    socket.send_to(&response, from).await.unwrap();
+                }
+            });
+            Self {
+                address,
+                requests
+            }
+        }
+        fn requests(&self) -> Vec<Vec<u8>>
+        {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+    fn info_packet(players: u8, bots: u8) -> Vec<u8>
+    {
+        let mut data: Vec<u8> = A2S_HEADER.to_vec();
+        data.extend_from_slice(&[A2S_INFO_RESPONSE, 0x11]);
+        data.extend_from_slice(b"Open Fortress Server\0ctf_2fort\0open_fortress\0Open Fortress\0");
+        data.extend_from_slice(&0u16.to_le_bytes());
+        data.extend_from_slice(&[players, 24, bots, b'd', b'l', 0, 1]);
+        data
+    }
+    fn config(address: String, timeout: u64) -> WaitForEmptyConfig
+    {
+        WaitForEmptyConfig {
+            address,
+            timeout: Some(timeout),
+            interval: Some(30),
+            count_bots: None,
+            stop_when_unreachable: None
+        }
+    }
+    /// Address that nothing is listening on.
+    fn unused_address() -> String
+    {
+        std::net::UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string()
+    }
+
+    #[test]
+    fn parse_info_valid()
+    {
+        let info = parse_info(&info_packet(5, 2)).unwrap();
+        assert_eq!(info.name, "Open Fortress Server");
+        assert_eq!(info.map, "ctf_2fort");
+        assert_eq!(info.folder, "open_fortress");
+        assert_eq!(info.game, "Open Fortress");
+        assert_eq!((info.players, info.max_players, info.bots), (5, 24, 2));
+    }
+
+    #[test]
+    fn parse_info_invalid()
+    {
+        let valid = info_packet(5, 2);
+        // too short for the header
+        assert!(parse_info(&[]).is_err());
+        assert!(parse_info(&valid[..5]).is_err());
+        // split packet
+        let mut split = valid.clone();
+        split[..4].copy_from_slice(&[0xFE, 0xFF, 0xFF, 0xFF]);
+        assert!(parse_info(&split).is_err());
+        // challenge instead of A2S_INFO
+        let mut challenge = A2S_HEADER.to_vec();
+        challenge.push(S2C_CHALLENGE);
+        challenge.extend_from_slice(&CHALLENGE);
+        assert!(parse_info(&challenge).is_err());
+        // every length that cuts off the strings or the player counts (the last 4 bytes aren't
+        // read)
+        for length in 6..valid.len() - 4 {
+            assert!(parse_info(&valid[..length]).is_err(), "length {} should fail", length);
+        }
+        // garbage after the header
+        let mut garbage = A2S_HEADER.to_vec();
+        garbage.extend_from_slice(&[A2S_INFO_RESPONSE, 0x11, 0xFF, 0xFE, 0xFD]);
+        assert!(parse_info(&garbage).is_err());
+    }
+
+    #[tokio::test]
+    async fn query_with_challenge()
+    {
+        let server = StandInServer::start(vec![Reply::Challenge, Reply::Info(3)]).await;
+        let info = query_info(&server.address).await.unwrap();
+        assert_eq!(info.players, 3);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].ends_with(A2S_INFO_PAYLOAD));
+        assert!(requests[1].ends_with(&CHALLENGE));
+        assert_eq!(requests[1][..requests[0].len()], requests[0][..]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_until_empty()
+    {
+        let server = StandInServer::start(vec![Reply::Info(4), Reply::Challenge, Reply::Info(2), Reply::Info(1)]).await;
+        let decision = wait_for_empty(&config(server.address.clone(), 3600)).await;
+        // the last response only has a bot, which isn't counted.
+        assert_eq!(decision, WaitForEmptyDecision::Empty);
+        assert_eq!(server.requests().len(), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_times_out()
+    {
+        let server = StandInServer::start(vec![Reply::Info(5); 3]).await;
+        let decision = wait_for_empty(&config(server.address.clone(), 60)).await;
+        assert_eq!(decision, WaitForEmptyDecision::TimedOut {
+            players: 4,
+            waited: 60
+        });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_retries_when_the_response_is_bad()
+    {
+        let server = StandInServer::start(vec![Reply::Info(5), Reply::Garbage, Reply::Garbage, Reply::Info(0)]).await;
+        let decision = wait_for_empty(&config(server.address.clone(), 3600)).await;
+        assert_eq!(decision, WaitForEmptyDecision::Empty);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_when_unreachable()
+    {
+        let decision = wait_for_empty(&config(unused_address(), 3600)).await;
+        assert_eq!(decision, WaitForEmptyDecision::Unreachable);
+
+        let server = StandInServer::start(vec![Reply::Garbage; A2S_ATTEMPTS as usize]).await;
+        let decision = wait_for_empty(&config(server.address.clone(), 3600)).await;
+        assert_eq!(decision, WaitForEmptyDecision::Unreachable);
+        assert_eq!(server.requests().len(), A2S_ATTEMPTS as usize);
+    }
+
+    #[test]
+    fn last_wait_round_trip()
+    {
+        let dir = TestDir::new("a2s-last-wait");
+        let location = dir.text();
+        assert!(LastWaitForEmpty::read(location.clone()).is_none());
+
+        LastWaitForEmpty::new(WaitForEmptyDecision::TimedOut {
+            players: 2,
+            waited: 3600
+        }).write(location.clone()).unwrap();
+        let last = LastWaitForEmpty::read(location.clone()).unwrap();
+        assert_eq!(last.decision, WaitForEmptyDecision::TimedOut {
+            players: 2,
+            waited: 3600
+        });
+    }
+}
\ No newline at end of file
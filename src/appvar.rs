@@ -122,7 +122,7 @@ impl AppVarData {
 
                 Err(BeansError::AppVarDataSerializeFailure {
                     error: e,
-                    data: data.clone()
+                    data: Box::new(data.clone())
                 })
             }
         }
@@ -140,7 +140,132 @@ pub struct AppVarMod
     pub short_name: String,
     /// stylized name of the sourcemod.
     /// e.g; `Open Fortress`
-    pub name_stylized: String
+    pub name_stylized: String,
+    /// symlinks that are created in the mod folder after installing, updating and verifying.
+    /// e.g; `bin/server_srv.so` -> `bin/server.so` for dedicated servers on Linux.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub symlinks: Vec<AppVarSymlink>,
+    /// permissions that are set on everything in the mod folder after installing, updating and
+    /// verifying. Only used on Linux.
+    #[serde(default)]
+    pub permissions: AppVarPermissions
+}
+/// Symlink in the mod folder. Only created on Linux.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AppVarSymlink
+{
+    /// location of the symlink, relative to the mod folder.
+    /// e.g; `bin/server_srv.so`
+    pub link: String,
+    /// file that the symlink points to, relative to the mod folder.
+    /// e.g; `bin/server.so`
+    pub target: String
+}
+/// Permissions that the files in the mod folder need to have. Modes are octal strings (e.g;
+/// `0700`), and the bits in them are added to files that are missing them. Bits that aren't in
+/// them are never removed.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AppVarPermissions
+{
+    /// set to `false` to leave the permissions alone.
+    #[serde(default = "AppVarPermissions::default_enabled")]
+    pub enabled: bool,
+    /// bits that directories need.
+    /// e.g; `0700`
+    #[serde(default = "AppVarPermissions::default_executable_mode")]
+    pub directory: String,
+    /// bits that files which don't match `executable_patterns` need.
+    /// e.g; `0600`
+    #[serde(default = "AppVarPermissions::default_file_mode")]
+    pub file: String,
+    /// bits that files which match `executable_patterns` need.
+    /// e.g; `0700`
+    #[serde(default = "AppVarPermissions::default_executable_mode")]
+    pub executable: String,
+    /// patterns for files that should be executable. `*` matches anything except `/`. Patterns
+    /// with a `/` are matched against the location relative to the mod folder, and the others
+    /// are matched against the file name.
+    /// e.g; `*.so`, `bin/*.so`
+    #[serde(default = "AppVarPermissions::default_executable_patterns")]
+    pub executable_patterns: Vec<String>
+}
+impl Default for AppVarPermissions
+{
+    fn default() -> Self {
+        Self {
+            enabled: Self::default_enabled(),
+            directory: Self::default_executable_mode(),
+            file: Self::default_file_mode(),
+            executable: Self::default_executable_mode(),
+            executable_patterns: Self::default_executable_patterns()
+        }
+    }
+}
+impl AppVarPermissions
+{
+    fn default_enabled() -> bool {
+        true
+    }
+    fn default_file_mode() -> String {
+        "0600".to_string()
+    }
+    fn default_executable_mode() -> String {
+        "0700".to_string()
+    }
+    fn default_executable_patterns() -> Vec<String> {
+        vec!["*.so".to_string(), "*.so.*".to_string()]
+    }
+
+    /// Parse `value` as an octal mode (e.g; `0755`). `None` when it isn't valid.
+    pub fn parse_mode(value: &str) -> Option<u32>
+    {
+        let value = value.trim().trim_start_matches("0o");
+        match u32::from_str_radix(value, 8) {
+            Ok(v) if v <= 0o7777 => Some(v),
+            _ => None
+        }
+    }
+
+    /// Check if the file at `location` (relative to the mod folder) matches any of the
+    /// `executable_patterns`.
+    pub fn is_executable(&self, location: &str) -> bool
+    {
+        let name = location.rsplit('/').next().unwrap_or(location);
+        self.executable_patterns.iter().any(|pattern| {
+            match pattern.contains('/') {
+                true => wildcard_match(pattern, location),
+                false => wildcard_match(pattern, name)
+            }
+        })
+    }
+}
+/// Check if `value` matches `pattern`, where `*` matches anything except `/`.
+fn wildcard_match(pattern: &str, value: &str) -> bool
+{
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    // position in `pattern` after the last `*`, and the position in `value` that it matched up to.
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut v) = (0, 0);
+    while v < value.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p + 1, v));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == value[v] {
+            p += 1;
+            v += 1;
+        } else if let Some((sp, sv)) = star {
+            if value[sv] == '/' {
+                return false;
+            }
+            star = Some((sp, sv + 1));
+            p = sp;
+            v = sv + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
 }
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct AppVarRemote
@@ -151,4 +276,57 @@ pub struct AppVarRemote
     /// url where the version details are stored.
     /// e.g; `https://beans.adastral.net/versions.json`
     pub versions_url: String
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard() {
+        let cases = [
+            ("*.so", "server.so", true),
+            ("*.so", "server.so.1", false),
+            ("*.so.*", "libtier0.so.1", true),
+            ("*.so", ".so", true),
+            ("*.so", "bin/server.so", false),
+            ("bin/*.so", "bin/server.so", true),
+            ("bin/*.so", "bin/linux64/server.so", false),
+            ("bin/*/*.so", "bin/linux64/server.so", true),
+            ("*", "", true),
+            ("*", "a/b", false),
+            ("**", "anything", true),
+            ("", "", true),
+            ("", "a", false),
+            ("a*b*c", "aXbYc", true),
+            ("a*b*c", "aXcYb", false),
+            ("srcds_*", "srcds_linux", true),
+            ("srcds_linux", "srcds_linux", true),
+            ("srcds_linux", "srcds_linux64", false)
+        ];
+        for (pattern, value, expected) in cases.iter() {
+            assert_eq!(wildcard_match(pattern, value), *expected, "{:?} {:?}", pattern, value);
+        }
+    }
+
+    #[test]
+    fn modes() {
+        let cases = [
+            ("0755", Some(0o755)),
+            ("755", Some(0o755)),
+            ("0o644", Some(0o644)),
+            (" 0600 ", Some(0o600)),
+            ("4755", Some(0o4755)),
+            ("7777", Some(0o7777)),
+            ("17777", None),
+            ("0", Some(0)),
+            ("0800", None),
+            ("rwxr-xr-x", None),
+            ("-755", None),
+            ("", None)
+        ];
+        for (value, expected) in cases.iter() {
+            assert_eq!(AppVarPermissions::parse_mode(value), *expected, "{:?}", value);
+        }
+    }
 }
\ No newline at end of file
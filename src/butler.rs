@@ -1,42 +1,139 @@
 use std::backtrace::Backtrace;
-use std::process::ExitStatus;
-use log::{debug, error, info};
-use crate::{BeansError, depends, DownloadFailureReason, helper};
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, ExitStatus, Stdio};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, error, info, trace, warn};
+use crate::{BeansError, ButlerFailureReason, depends, DownloadFailureReason, helper};
+
+/// Message that is written to stdout by butler when the `--json` flag is provided.
+///
+/// Every line that butler writes is a JSON object with a `type` property, which is what we use
+/// to figure out what variant it is.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ButlerMessage
+{
+    /// Regular log message.
+    Log {
+        #[serde(default)]
+        level: String,
+        #[serde(default)]
+        message: String
+    },
+    /// Progress of the current operation. `progress` is between `0.0` and `1.0`.
+    Progress {
+        #[serde(default)]
+        progress: f64,
+        #[serde(default)]
+        eta: Option<f64>,
+        #[serde(default)]
+        bps: Option<f64>
+    },
+    /// Final result of the command (only sent by some commands)
+    Result {
+        #[serde(default)]
+        value: serde_json::Value
+    },
+    /// Fatal error. butler exits with a non-zero code after this is sent.
+    Error {
+        #[serde(default)]
+        message: String
+    },
+    /// Any message type that we don't care about (e.g; `start`, `end`)
+    #[serde(other)]
+    Unknown
+}
+
+/// Output of a butler command that exited successfully.
+#[derive(Debug, Clone)]
+pub struct ButlerOutput
+{
+    pub status: ExitStatus,
+    /// Value of the last `result` message that butler sent.
+    pub result: Option<serde_json::Value>,
+    /// All of the log messages that butler sent (excluding `debug`), in order.
+    pub messages: Vec<String>
+}
+impl ButlerOutput
+{
+    /// Parse `result` as the result of `butler verify`. `None` when butler didn't send one, or
+    /// it isn't valid.
+    pub fn verify_result(&self) -> Option<VerifyResult>
+    {
+        let value = self.result.clone()?;
+        match serde_json::from_value::<VerifyResult>(value) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                warn!("[ButlerOutput::verify_result] Failed to parse result {:}", e);
+                None
+            }
+        }
+    }
+}
+
+/// `value` of the `result` message that `butler verify` sends once it's finished.
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyResult
+{
+    /// Amount of bytes that didn't match the signature.
+    #[serde(default)]
+    pub total_corrupted: u64,
+    /// Amount of bytes that were healed (only when `--heal` was used).
+    #[serde(default)]
+    pub total_healed: u64,
+    /// Files that didn't match the signature, relative to the game folder.
+    #[serde(default)]
+    pub wounded_files: Vec<String>
+}
+impl VerifyResult
+{
+    /// Anything was healed.
+    pub fn repaired(&self) -> bool
+    {
+        self.total_healed > 0
+    }
+    /// Lines that describe what was repaired, for `WebhookPayload.details`.
+    pub fn details(&self) -> Vec<String>
+    {
+        let mut result = vec![format!("Repaired {} of {} corrupted data",
+            helper::format_size(self.total_healed as usize),
+            helper::format_size(self.total_corrupted as usize))];
+        result.extend(self.wounded_files.iter().cloned());
+        result
+    }
+}
 
 pub fn verify(
     signature_url: String,
     gamedir: String,
     remote: String
-) -> Result<ExitStatus, BeansError> {
-    let mut cmd = std::process::Command::new(&depends::get_butler_location());
+) -> Result<ButlerOutput, BeansError> {
+    let mut cmd = Command::new(&depends::get_butler_location());
     cmd.args([
+        "--json",
         "verify",
         &signature_url,
         &gamedir,
         format!("--heal=archive,{}", remote).as_str()
     ]);
     debug!("[butler::verify] {:#?}", cmd);
-    match cmd
-        .spawn() {
-        Err(e) => {
-            Err(BeansError::ButlerVerifyFailure {
+    match run(cmd, "Verifying") {
+        Err(reason) => {
+            let xe = BeansError::ButlerVerifyFailure {
                 signature_url,
                 gamedir,
-                remote,
-                error: e,
+                remote: Box::new(remote),
+                reason: Box::new(reason),
                 backtrace: Backtrace::capture()
-            })
+            };
+            error!("[butler::verify] {:}", xe);
+            sentry::capture_error(&xe);
+            Err(xe)
         },
-        Ok(mut v) => {
-            let w = v.wait()?;
-            debug!("[butler::verify] Exited with {:#?}", w);
-            if let Some(c) = w.code() {
-                if c != 0 {
-                    error!("[butler::verify] exited with code {c}, which isn't good!");
-                    panic!("[butler::verify] exited with code {c}");
-                }
-            }
-            Ok(w)
+        Ok(v) => {
+            debug!("[butler::verify] Exited with {:#?}", v.status);
+            Ok(v)
         }
     }
 }
@@ -45,7 +142,7 @@ pub async fn patch_dl(
     staging_dir: String,
     patch_filename: String,
     gamedir: String
-) -> Result<ExitStatus, BeansError> {
+) -> Result<ButlerOutput, BeansError> {
     if helper::file_exists(staging_dir.clone()) {
         std::fs::remove_dir_all(&staging_dir)?;
     }
@@ -68,38 +165,173 @@ pub fn patch(
     patchfile_location: String,
     staging_dir: String,
     gamedir: String
-) -> Result<ExitStatus, BeansError> {
-    let mut cmd = std::process::Command::new(&depends::get_butler_location());
+) -> Result<ButlerOutput, BeansError> {
+    let mut cmd = Command::new(&depends::get_butler_location());
     cmd.args([
+        "--json",
         "apply",
         &format!("--staging-dir={}", &staging_dir),
         &patchfile_location,
         &gamedir
     ]);
     debug!("[butler::patch] {:#?}", &cmd);
-    match cmd
-        .spawn() {
-        Err(e) => {
+    match run(cmd, "Patching") {
+        Err(reason) => {
             let xe = BeansError::ButlerPatchFailure {
                 patchfile_location,
                 gamedir,
-                error: e,
+                reason: Box::new(reason),
                 backtrace: Backtrace::capture()
             };
-            error!("[butler::patch] {:#?}", xe);
+            error!("[butler::patch] {:}", xe);
             sentry::capture_error(&xe);
             Err(xe)
         },
-        Ok(mut v) => {
-            let w = v.wait()?;
-            debug!("Exited with {:#?}", w);
-            if let Some(c) = w.code() {
-                if c != 0 {
-                    error!("[butler::patch] exited with code {c}, which isn't good!");
-                    panic!("[butler::patch] exited with code {c}");
+        Ok(v) => {
+            debug!("[butler::patch] Exited with {:#?}", v.status);
+            Ok(v)
+        }
+    }
+}
+
+/// Run the butler command provided, and parse everything that it writes to stdout as a
+/// `ButlerMessage`. Progress messages are shown with a progress bar, and log messages are
+/// forwarded to our logger.
+///
+/// `cmd` must have the `--json` flag provided, otherwise nothing will be parsed.
+fn run(mut cmd: Command, label: &str) -> Result<ButlerOutput, ButlerFailureReason>
+{
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+    let mut child = cmd.spawn().map_err(ButlerFailureReason::Spawn)?;
+
+    // butler only writes to stderr when something goes really wrong (like a go panic), so
+    // we collect it in a separate thread so it can be used when there's no error message.
+    let stderr_thread = child.stderr.take().map(|mut stderr| {
+        std::thread::spawn(move || {
+            let mut content = String::new();
+            let _ = stderr.read_to_string(&mut content);
+            content
+        })
+    });
+
+    let pb = ProgressBar::new(PROGRESS_LENGTH);
+    pb.set_style(ProgressStyle::with_template("{spinner:.green} {msg} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {percent}% ({eta})")
+        .unwrap()
+        .progress_chars("#>-"));
+    pb.set_message(label.to_string());
+
+    let mut output_result: Option<serde_json::Value> = None;
+    let mut messages: Vec<String> = Vec::new();
+    let mut last_error: Option<String> = None;
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines() {
+            let line = match line {
+                Ok(v) => v,
+                Err(e) => {
+                    debug!("[butler::run] Failed to read line from stdout {:#?}", e);
+                    break;
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<ButlerMessage>(&line) {
+                Ok(ButlerMessage::Progress { progress, eta, bps }) => {
+                    trace!("[butler::run] progress={progress} eta={:?} bps={:?}", eta, bps);
+                    let position = (progress.clamp(0.0, 1.0) * PROGRESS_LENGTH as f64) as u64;
+                    pb.set_position(position);
+                },
+                Ok(ButlerMessage::Log { level, message }) => {
+                    pb.suspend(|| match level.as_str() {
+                        "error" => error!("[butler] {}", message),
+                        "warning" => warn!("[butler] {}", message),
+                        "debug" => debug!("[butler] {}", message),
+                        _ => info!("[butler] {}", message)
+                    });
+                    if level != "debug" {
+                        messages.push(message);
+                    }
+                },
+                Ok(ButlerMessage::Result { value }) => {
+                    debug!("[butler::run] result: {:#?}", value);
+                    output_result = Some(value);
+                },
+                Ok(ButlerMessage::Error { message }) => {
+                    pb.suspend(|| error!("[butler] {}", message));
+                    last_error = Some(message);
+                },
+                Ok(ButlerMessage::Unknown) => {
+                    trace!("[butler::run] ignoring message {}", line);
+                },
+                Err(e) => {
+                    debug!("[butler::run] Failed to parse line as ButlerMessage ({:}) {}", e, line);
                 }
             }
-            Ok(w)
         }
     }
+
+    let status = child.wait().map_err(ButlerFailureReason::Spawn)?;
+    let stderr_content = stderr_thread
+        .and_then(|t| t.join().ok())
+        .unwrap_or_default();
+    if !stderr_content.trim().is_empty() {
+        debug!("[butler::run] stderr: {}", stderr_content);
+    }
+
+    if status.success() {
+        pb.finish();
+        return Ok(ButlerOutput {
+            status,
+            result: output_result,
+            messages
+        });
+    }
+    pb.abandon();
+
+    if last_error.is_none() && !stderr_content.trim().is_empty() {
+        last_error = stderr_content.trim().lines().last().map(|v| v.to_string());
+    }
+    Err(ButlerFailureReason::ExitCode {
+        code: status.code(),
+        message: last_error
+    })
+}
+
+/// Length of the progress bar that is used for butler.
+const PROGRESS_LENGTH: u64 = 1000;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(result: Option<serde_json::Value>) -> ButlerOutput
+    {
+        ButlerOutput {
+            status: ExitStatus::default(),
+            result,
+            // only the result should be used to tell if anything was repaired.
+            messages: vec!["0 B corrupted data found".to_string()]
+        }
+    }
+
+    #[test]
+    fn verify_result_healed() {
+        let result = output(Some(serde_json::json!({
+            "totalCorrupted": 2048,
+            "totalHealed": 2048,
+            "woundedFiles": ["bin/server.so"]
+        }))).verify_result().unwrap();
+        assert!(result.repaired());
+        assert_eq!(result.wounded_files, vec!["bin/server.so".to_string()]);
+        assert_eq!(result.details().len(), 2);
+    }
+
+    #[test]
+    fn verify_result_nothing_healed() {
+        let result = output(Some(serde_json::json!({ "totalCorrupted": 0 }))).verify_result().unwrap();
+        assert!(!result.repaired());
+        assert_eq!(output(None).verify_result(), None);
+        assert_eq!(output(Some(serde_json::json!("done"))).verify_result(), None);
+    }
 }
\ No newline at end of file
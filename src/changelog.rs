@@ -0,0 +1,218 @@
+use colored::Colorize;
+use crate::version::RemoteVersionResponse;
+
+/// Release notes for a single version from `versions.json`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ChangelogEntry
+{
+    pub version: usize,
+    /// Release notes, formatted with Markdown.
+    pub changelog: Option<String>,
+    /// Where the full release notes can be read.
+    pub changelog_url: Option<String>
+}
+
+/// Get the release notes for every version after `current` up to (and including) `target`, in
+/// order. Only versions in `channel` are included, and versions that don't have a changelog or
+/// changelog URL are skipped.
+pub fn between(list: &RemoteVersionResponse, current: usize, target: usize, channel: &str) -> Vec<ChangelogEntry>
+{
+    let mut result: Vec<ChangelogEntry> = list.versions.iter()
+        .filter(|(id, v)| **id > current && **id <= target && v.is_in_channel(channel))
+        .filter(|(_, v)| v.changelog.is_some() || v.changelog_url.is_some())
+        .map(|(id, v)| ChangelogEntry {
+            version: *id,
+            changelog: v.changelog.clone(),
+            changelog_url: v.changelog_url.clone()
+        })
+        .collect();
+    result.sort_by_key(|v| v.version);
+    result
+}
+
+/// Print `entries` to the console, with the Markdown in them rendered. Nothing is printed when
+/// `entries` is empty.
+pub fn print(entries: &[ChangelogEntry])
+{
+    if entries.is_empty() {
+        return;
+    }
+    println!("{}", "======== What's new ========".bold());
+    for entry in entries.iter() {
+        println!();
+        println!("{}", format!("v{}", entry.version).bold().underline());
+        if let Some(text) = &entry.changelog {
+            println!("{}", render_markdown(text));
+        }
+        if let Some(url) = &entry.changelog_url {
+            println!("Full release notes: {}", url.blue().underline());
+        }
+    }
+    println!();
+}
+
+/// Render basic Markdown for the terminal. Supports headings, bullet points, quotes, bold,
+/// italics, inline code and links. Anything else is left as-is.
+pub fn render_markdown(text: &str) -> String
+{
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+        let rendered = if let Some(heading) = trimmed.strip_prefix("# ") {
+            render_inline(heading).bold().underline().to_string()
+        } else if trimmed.starts_with("## ") || trimmed.starts_with("### ") {
+            render_inline(trimmed.trim_start_matches('#').trim_start()).bold().to_string()
+        } else if let Some(item) = trimmed.strip_prefix("- ").or(trimmed.strip_prefix("* ")) {
+            format!("{}  • {}", indent, render_inline(item))
+        } else if let Some(quote) = trimmed.strip_prefix("> ") {
+            format!("{}│ {}", indent, render_inline(quote).dimmed())
+        } else {
+            format!("{}{}", indent, render_inline(trimmed))
+        };
+        lines.push(rendered);
+    }
+    lines.join("\n")
+}
+
+/// Render the inline formatting in `text` (bold, italics, inline code and links).
+fn render_inline(text: &str) -> String
+{
+    let mut result = String::new();
+    // byte offset to continue from, once a span of formatting has been rendered.
+    let mut next = 0;
+    for (i, c) in text.char_indices() {
+        if i < next {
+            continue;
+        }
+        let rest = &text[i..];
+        let span = match c {
+            '*' if rest.starts_with("**") => rest[2..].find("**")
+                .map(|end| (render_inline(&rest[2..2 + end]).bold().to_string(), end + 4)),
+            '`' => rest[1..].find('`')
+                .map(|end| (rest[1..1 + end].cyan().to_string(), end + 2)),
+            '*' | '_' => render_italics(rest, text[..i].chars().next_back()),
+            '[' => parse_link(rest)
+                .map(|(label, url, length)| (format!("{} ({})", label, url.blue().underline()), length)),
+            _ => None
+        };
+        match span {
+            Some((rendered, length)) => {
+                result.push_str(&rendered);
+                next = i + length;
+            },
+            None => result.push(c)
+        }
+    }
+    result
+}
+
+/// Render the italics at the start of `text`, which starts with `*` or `_`. `previous` is the
+/// character before it. Returns the rendered text and the length of the italics in bytes.
+///
+/// Like Markdown, the text has to start and end right next to the markers, so `2 * 3 * 4` isn't
+/// italics.
+fn render_italics(text: &str, previous: Option<char>) -> Option<(String, usize)>
+{
+    let marker = text.chars().next()?;
+    if text[1..].chars().next().map_or(true, char::is_whitespace) {
+        return None;
+    }
+    let end = text[1..].find(marker)?;
+    if text[1..1 + end].chars().next_back().map_or(true, char::is_whitespace) {
+        return None;
+    }
+    // underscores in the middle of words (like snake_case) aren't for italics.
+    let at_word_start = previous.map_or(true, |c| !c.is_alphanumeric());
+    let at_word_end = text[end + 2..].chars().next().map_or(true, |c| !c.is_alphanumeric());
+    if end == 0 || (marker == '_' && !(at_word_start && at_word_end)) {
+        return None;
+    }
+    Some((text[1..1 + end].italic().to_string(), end + 2))
+}
+
+/// Parse a Markdown link (`[label](url)`) at the start of `text`. Returns the label, URL and
+/// the length of the link in bytes. The label ends at the first `]`, which has to be followed
+/// by the URL.
+fn parse_link(text: &str) -> Option<(String, String, usize)>
+{
+    let label_end = text.find(']')?;
+    if !text[label_end + 1..].starts_with('(') {
+        return None;
+    }
+    let url_end = text[label_end + 2..].find(')')? + label_end + 2;
+    let label = text[1..label_end].to_string();
+    let url = text[label_end + 2..url_end].to_string();
+    Some((label, url, url_end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Make sure that `colored` adds the escape codes, even when the tests aren't run in a
+    /// terminal.
+    fn colorize()
+    {
+        colored::control::set_override(true);
+    }
+
+    #[test]
+    fn bold_and_code() {
+        colorize();
+        assert_eq!(render_inline("a **bold** b"), format!("a {} b", "bold".bold()));
+        assert_eq!(render_inline("**a `b`**"), format!("{}", format!("a {}", "b".cyan()).bold()));
+        assert_eq!(render_inline("run `beans-rs verify`"), format!("run {}", "beans-rs verify".cyan()));
+        assert_eq!(render_inline("**not closed"), "**not closed");
+    }
+
+    #[test]
+    fn italics() {
+        colorize();
+        assert_eq!(render_inline("an *italic* word"), format!("an {} word", "italic".italic()));
+        assert_eq!(render_inline("an _italic_ word"), format!("an {} word", "italic".italic()));
+        assert_eq!(render_inline("2 * 3 * 4"), "2 * 3 * 4");
+        assert_eq!(render_inline("a *b * c"), "a *b * c");
+        assert_eq!(render_inline("_ a _"), "_ a _");
+        assert_eq!(render_inline("**"), "**");
+    }
+
+    #[test]
+    fn snake_case_is_not_italics() {
+        colorize();
+        assert_eq!(render_inline("set sv_cheats and mp_timelimit"), "set sv_cheats and mp_timelimit");
+        assert_eq!(render_inline("_leading and trailing_"), format!("{}", "leading and trailing".italic()));
+        assert_eq!(render_inline("a_b_c"), "a_b_c");
+    }
+
+    #[test]
+    fn links() {
+        colorize();
+        assert_eq!(render_inline("see [the wiki](https://example.com/wiki) for more"),
+            format!("see the wiki ({}) for more", "https://example.com/wiki".blue().underline()));
+        assert_eq!(render_inline("[not a link"), "[not a link");
+        assert_eq!(render_inline("[a] x [b](u)"), format!("[a] x b ({})", "u".blue().underline()));
+    }
+
+    #[test]
+    fn non_ascii() {
+        colorize();
+        assert_eq!(render_inline("Café **über** 日本語"), format!("Café {} 日本語", "über".bold()));
+        assert_eq!(render_inline("ñ_é_ñ"), "ñ_é_ñ");
+        assert_eq!(render_inline("— _ünïcödé_ —"), format!("— {} —", "ünïcödé".italic()));
+        assert_eq!(render_inline("[日本](https://例え.jp) `ß`"),
+            format!("日本 ({}) {}", "https://例え.jp".blue().underline(), "ß".cyan()));
+        assert_eq!(render_inline("🎉 **🎉**"), format!("🎉 {}", "🎉".bold()));
+    }
+
+    #[test]
+    fn markdown_lines() {
+        colorize();
+        let rendered = render_markdown("# Title\n- item *one*\n  * nested\n> quote");
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[0], "Title".bold().underline().to_string());
+        assert_eq!(lines[1], format!("  • item {}", "one".italic()));
+        assert_eq!(lines[2], "    • nested");
+        assert_eq!(lines[3], format!("│ {}", "quote".dimmed()));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use log::{debug, error, warn};
+use crate::hooks::HookConfig;
+use crate::rcon::RconConfig;
+use crate::webhook::WebhookConfig;
+
+/// Environment variable for the location of the config file, which is used instead of
+/// `BeansConfig::location`.
+pub const CONFIG_ENV: &str = "BEANS_CONFIG";
+/// Name of the folder that the config file is in.
+pub const CONFIG_DIRNAME: &str = "beans-rs";
+/// Name of the config file.
+pub const CONFIG_FILENAME: &str = "config.json";
+/// Properties that used to be read from `.adastral`, and are now only read from the config file.
+pub const MOVED_PROPERTIES: [&str; 3] = ["hooks", "rcon", "webhooks"];
+/// Set once `warn_moved_properties` has logged the warning.
+static WARNED_MOVED_PROPERTIES: AtomicBool = AtomicBool::new(false);
+
+/// Settings for running beans-rs on a server (hooks, RCON and webhooks).
+///
+/// These run commands and have passwords in them, so they're read from a file outside of the
+/// mod folder (see `BeansConfig::location`) instead of `.adastral`, since packages, butler and
+/// the user that runs the game can all write to the mod folder.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct BeansConfig
+{
+    /// Settings for every install.
+    #[serde(flatten)]
+    pub defaults: InstallConfig,
+    /// Settings for a specific install, by the location of its sourcemods folder. The properties
+    /// that are set replace the ones in `defaults`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub installs: HashMap<String, InstallConfig>
+}
+/// Settings for an install in `BeansConfig`.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct InstallConfig
+{
+    /// Commands to run before and after workflows, see `HookConfig`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<HookConfig>,
+    /// Used to stop the dedicated server before updating, see `RconConfig`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rcon: Option<RconConfig>,
+    /// Webhooks that are notified about updates and failures, see `WebhookConfig`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhooks: Option<Vec<WebhookConfig>>
+}
+impl BeansConfig
+{
+    /// Location of the config file. `CONFIG_ENV` when it's set, otherwise
+    /// `/etc/beans-rs/config.json` when running as root,
+    /// `$XDG_CONFIG_HOME/beans-rs/config.json` (or `~/.config/beans-rs/config.json`) on Linux,
+    /// and `%APPDATA%\beans-rs\config.json` on Windows.
+    pub fn location() -> Option<PathBuf>
+    {
+        if let Ok(v) = std::env::var(CONFIG_ENV) {
+            if !v.trim().is_empty() {
+                return Some(PathBuf::from(v));
+            }
+        }
+        // SAFETY: geteuid can't fail, and doesn't touch any memory.
+        #[cfg(target_os = "linux")]
+        if unsafe { libc::geteuid() } == 0 {
+            return Some(PathBuf::from("/etc").join(CONFIG_DIRNAME).join(CONFIG_FILENAME));
+        }
+        let base = match cfg!(target_os = "windows") {
+            true => std::env::var("APPDATA").ok().map(PathBuf::from),
+            false => std::env::var("XDG_CONFIG_HOME").ok()
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| simple_home_dir::home_dir().map(|v| v.join(".config")))
+        };
+        base.map(|v| v.join(CONFIG_DIRNAME).join(CONFIG_FILENAME))
+    }
+
+    /// Read the config file. The default config is used when it doesn't exist, or it can't be
+    /// used (which is logged).
+    pub fn read() -> Self
+    {
+        let location = match Self::location() {
+            Some(v) => v,
+            None => {
+                debug!("[BeansConfig::read] Couldn't find where the config file would be");
+                return Self::default();
+            }
+        };
+        if !location.exists() {
+            return Self::default();
+        }
+        if let Err(reason) = check_permissions(&location) {
+            error!("[BeansConfig::read] Ignoring {}, since {}", location.display(), reason);
+            return Self::default();
+        }
+        let content = match std::fs::read_to_string(&location) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("[BeansConfig::read] Failed to read {} ({:})", location.display(), e);
+                return Self::default();
+            }
+        };
+        match serde_json::from_str::<Self>(&content) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("[BeansConfig::read] Failed to parse {} ({:})", location.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Get the settings for the install in `sourcemods_location`.
+    pub fn for_install(sourcemods_location: &str) -> InstallConfig
+    {
+        Self::read().get(sourcemods_location)
+    }
+    /// Get the settings for the install in `sourcemods_location`, with the properties from
+    /// `installs` on top of `defaults`.
+    pub fn get(&self, sourcemods_location: &str) -> InstallConfig
+    {
+        let mut result = self.defaults.clone();
+        let specific = self.installs.iter()
+            .find(|(k, _)| same_location(k, sourcemods_location))
+            .map(|(_, v)| v.clone());
+        if let Some(v) = specific {
+            if v.hooks.is_some() {
+                result.hooks = v.hooks;
+            }
+            if v.rcon.is_some() {
+                result.rcon = v.rcon;
+            }
+            if v.webhooks.is_some() {
+                result.webhooks = v.webhooks;
+            }
+        }
+        result
+    }
+}
+
+/// Check if the sourcemods folders `a` and `b` are the same folder.
+fn same_location(a: &str, b: &str) -> bool
+{
+    let normalize = |v: &str| -> PathBuf {
+        std::fs::canonicalize(v).unwrap_or_else(|_| PathBuf::from(v.trim_end_matches(['/', '\\'])))
+    };
+    normalize(a) == normalize(b)
+}
+
+/// When running as root, the config file has to be owned by root and not writable by anyone
+/// else, since the hooks in it are run as root. The RCON password is in it as well, so a warning
+/// is logged when anyone else can read it.
+#[cfg(target_os = "linux")]
+fn check_permissions(location: &Path) -> Result<(), String>
+{
+    use std::os::unix::fs::MetadataExt;
+    let metadata = std::fs::metadata(location).map_err(|e| e.to_string())?;
+    // SAFETY: geteuid can't fail, and doesn't touch any memory.
+    if unsafe { libc::geteuid() } == 0 {
+        if metadata.uid() != 0 {
+            return Err(format!("it's owned by {} instead of root", metadata.uid()));
+        }
+        if metadata.mode() & 0o022 != 0 {
+            return Err(format!("it can be written to by other users (mode {:o})", metadata.mode() & 0o777));
+        }
+    }
+    if metadata.mode() & 0o044 != 0 {
+        warn!("[config::check_permissions] {} can be read by other users (mode {:o}), run \"chmod 600 {0}\" to fix it", location.display(), metadata.mode() & 0o777);
+    }
+    Ok(())
+}
+#[cfg(not(target_os = "linux"))]
+fn check_permissions(_location: &Path) -> Result<(), String>
+{
+    Ok(())
+}
+
+/// Log a warning for every property in `MOVED_PROPERTIES` that is still in the `.adastral` at
+/// `location` (with the content `content`), since they're ignored now. Only warns once.
+pub fn warn_moved_properties(location: &str, content: &str)
+{
+    if !MOVED_PROPERTIES.iter().any(|v| content.contains(&format!("\"{}\"", v))) {
+        return;
+    }
+    if WARNED_MOVED_PROPERTIES.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    let value = match serde_json::from_str::<serde_json::Value>(content) {
+        Ok(v) => v,
+        Err(_) => return
+    };
+    for name in MOVED_PROPERTIES.iter() {
+        if value.get(name).is_some() {
+            warn!("[config::warn_moved_properties] \"{}\" in {} is ignored, and will be removed. Move it to {}", name, location,
+                BeansConfig::location().map(|v| v.to_string_lossy().to_string()).unwrap_or(CONFIG_FILENAME.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn install_overrides_defaults() {
+        let config: BeansConfig = serde_json::from_str(r#"{
+            "hooks": { "pre_update": "systemctl stop of" },
+            "webhooks": [{ "url": "http://127.0.0.1/a" }],
+            "installs": {
+                "/srv/of1/sourcemods/": {
+                    "rcon": { "address": "127.0.0.1:27015" },
+                    "webhooks": []
+                }
+            }
+        }"#).unwrap();
+
+        let specific = config.get("/srv/of1/sourcemods");
+        assert_eq!(specific.hooks.and_then(|v| v.pre_update).as_deref(), Some("systemctl stop of"));
+        assert_eq!(specific.rcon.map(|v| v.address).as_deref(), Some("127.0.0.1:27015"));
+        assert_eq!(specific.webhooks.map(|v| v.len()), Some(0));
+
+        let other = config.get("/srv/of2/sourcemods");
+        assert!(other.rcon.is_none());
+        assert_eq!(other.webhooks.map(|v| v.len()), Some(1));
+    }
+
+    #[test]
+    fn empty_config() {
+        let config: BeansConfig = serde_json::from_str("{}").unwrap();
+        let result = config.get("/srv/of1/sourcemods");
+        assert!(result.hooks.is_none() && result.rcon.is_none() && result.webhooks.is_none());
+    }
+}
\ No newline at end of file
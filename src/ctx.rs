@@ -1,10 +1,10 @@
 use std::backtrace::Backtrace;
-use crate::{BeansError, depends, helper, version};
+use crate::{BeansError, depends, flags, helper, version};
 use crate::helper::{find_sourcemod_path, InstallType, parse_location};
 use crate::version::{RemotePatch, RemoteVersion, RemoteVersionResponse};
 #[cfg(target_os = "linux")]
 use std::os::unix::fs::PermissionsExt;
-use log::{debug, error, info, trace};
+use log::{debug, info, trace};
 
 #[derive(Debug, Clone)]
 pub struct RunnerContext
@@ -12,17 +12,22 @@ pub struct RunnerContext
     pub sourcemod_path: String,
     pub remote_version_list: RemoteVersionResponse,
     pub current_version: Option<usize>,
-    pub appvar: crate::appvar::AppVarData
+    /// Release channel for this install. Read from `.adastral`, and defaults to
+    /// `version::DEFAULT_CHANNEL`.
+    pub channel: String,
+    pub appvar: crate::appvar::AppVarData,
+    /// Directory with packages and patches that have already been downloaded (see
+    /// `FleetWorkflow`). Files in it are used instead of downloading them again, and are never
+    /// deleted by workflows.
+    pub download_cache: Option<String>
 }
 impl RunnerContext
 {
     pub async fn create_auto(sml_via: SourceModDirectoryParam) -> Result<Self, BeansError>
     {
-        depends::try_write_deps();
-        if let Err(e) = depends::try_install_vcredist().await {
-            sentry::capture_error(&e);
-            println!("Failed to install vcredist! {:}", e);
-            debug!("[RunnerContext::create_auto] Failed to install vcredist! {:#?}", e);
+        // nothing is written when doing a dry run, and butler isn't needed for planning.
+        if !flags::dry_run() {
+            Self::prepare_dependencies().await;
         }
         let sourcemod_path = parse_location(match sml_via
         {
@@ -40,18 +45,91 @@ impl RunnerContext
             }
         });
         let version_list = version::get_version_list().await?;
-
+        let ctx = Self::create_with_version_list(sourcemod_path, version_list)?;
+        ctx.remote_version_list.show_notice(ctx.current_version);
+        Ok(ctx)
+    }
+    /// Write butler (and install vcredist on Windows), which are needed by the workflows.
+    pub async fn prepare_dependencies()
+    {
+        depends::try_write_deps();
+        if let Err(e) = depends::try_install_vcredist().await {
+            sentry::capture_error(&e);
+            println!("Failed to install vcredist! {:}", e);
+            debug!("[RunnerContext::prepare_dependencies] Failed to install vcredist! {:#?}", e);
+        }
+    }
+    /// Create a context for the sourcemods folder at `sourcemod_path`, with a version list that
+    /// has already been fetched. Used when there are multiple installs (see `FleetWorkflow`).
+    pub fn create_with_version_list(sourcemod_path: String, version_list: RemoteVersionResponse) -> Result<Self, BeansError>
+    {
+        let sourcemod_path = parse_location(sourcemod_path);
         if helper::install_state(Some(sourcemod_path.clone())) == InstallType::OtherSource {
-            version::update_version_file(Some(sourcemod_path.clone()))?;
+            match flags::dry_run() {
+                true => info!("[RunnerContext::create_with_version_list] Not migrating .revision to .adastral since this is a dry run"),
+                false => version::update_version_file(Some(sourcemod_path.clone()))?
+            }
         }
 
-        return Ok(Self
+        let current_version = crate::version::get_current_version(Some(sourcemod_path.clone()));
+        Ok(Self
         {
-            sourcemod_path: parse_location(sourcemod_path.clone()),
+            sourcemod_path: sourcemod_path.clone(),
             remote_version_list: version_list,
-            current_version: crate::version::get_current_version(Some(sourcemod_path.clone())),
-            appvar: crate::appvar::parse()
-        });
+            current_version,
+            channel: Self::read_channel(sourcemod_path),
+            appvar: crate::appvar::parse(),
+            download_cache: None
+        })
+    }
+    /// Get the channel that is set in `.adastral` for the sourcemods location provided. When it
+    /// isn't set, `version::DEFAULT_CHANNEL` is returned.
+    pub fn read_channel(sourcemod_path: String) -> String
+    {
+        version::get_version_file(Some(sourcemod_path))
+            .and_then(|v| v.channel)
+            .unwrap_or(version::DEFAULT_CHANNEL.to_string())
+    }
+    /// Set the release channel for this install. When the mod is installed, it will also be
+    /// written to `.adastral` (unless this is a dry run).
+    ///
+    /// Returns `BeansError::InvalidArgument` when there are no versions in `channel`.
+    pub fn set_channel(&mut self, channel: String) -> Result<(), BeansError>
+    {
+        let available = self.remote_version_list.channels();
+        if !available.contains(&channel) {
+            return Err(BeansError::InvalidArgument {
+                name: "channel".to_string(),
+                value: channel,
+                reason: format!("Available channels are {}", available.join(", "))
+            });
+        }
+        if self.channel != channel {
+            info!("[RunnerContext::set_channel] Changing channel from {} to {}", self.channel, channel);
+        }
+        self.channel = channel;
+        if self.current_version.is_some() && !flags::dry_run() {
+            self.write_version_file()?;
+        }
+        Ok(())
+    }
+    /// Write `current_version` and `channel` to `.adastral`. Other values in the file are kept.
+    pub fn write_version_file(&mut self) -> Result<(), BeansError>
+    {
+        let current_version = match self.current_version {
+            Some(v) => v,
+            None => {
+                debug!("[RunnerContext::write_version_file] current_version is None, ignoring.");
+                return Ok(());
+            }
+        };
+        let mut data = version::get_version_file(Some(self.sourcemod_path.clone())).unwrap_or_default();
+        data.version = current_version.to_string();
+        data.channel = match self.channel.as_str() {
+            version::DEFAULT_CHANNEL => None,
+            v => Some(v.to_string())
+        };
+        data.write(Some(self.sourcemod_path.clone()))
     }
     /// Sets `remote_version_list` from `version::get_version_list()`
     pub async fn set_remote_version_list(&mut self) -> Result<(), BeansError>
@@ -77,12 +155,12 @@ impl RunnerContext
         helper::join_path(self.sourcemod_path.clone(), crate::STAGING_DIR.to_string())
     }
 
-    /// Get the latest item in `remote_version_list`
+    /// Get the latest item in `remote_version_list` that is in `self.channel`
     pub fn latest_remote_version(&mut self) -> (usize, RemoteVersion)
     {
         let mut highest = usize::MIN;
-        for (key, _) in self.remote_version_list.clone().versions.into_iter() {
-            if key > highest {
+        for (key, value) in self.remote_version_list.clone().versions.into_iter() {
+            if key > highest && value.is_in_channel(&self.channel) {
                 highest = key;
             }
         }
@@ -186,13 +264,25 @@ impl RunnerContext
     /// Download package with Progress Bar.
     /// Ok is the location to where it was downloaded to.
     pub async fn download_package(version: RemoteVersion) -> Result<String, BeansError>
+    {
+        Self::download_remote_file(version.file.expect("No URL for latest package!"), version.pre_sz).await
+    }
+
+    /// Download `file` (relative to `base_url` in the AppVar) to a temporary file, and return
+    /// the location of it. `size` is used to check if there is enough free space.
+    pub async fn download_remote_file(file: String, size: Option<usize>) -> Result<String, BeansError>
     {
         let av = crate::appvar::parse();
         let mut out_loc = helper::get_tmp_dir();
 
-        if let Some(size) = version.pre_sz {
-            if helper::has_free_space(out_loc.clone(), size)? == false {
-                panic!("Not enough free space to install latest version!");
+        if let Some(size) = size {
+            let available = helper::get_free_space(out_loc.clone())?;
+            if (size as u64) >= available {
+                return Err(BeansError::NotEnoughFreeSpace {
+                    location: out_loc,
+                    required: size,
+                    available
+                });
             }
         }
 
@@ -201,55 +291,46 @@ impl RunnerContext
 
         info!("[RunnerContext::download_package] writing to {}", out_loc);
         helper::download_with_progress(
-            format!("{}{}", &av.remote_info.base_url, version.file.expect("No URL for latest package!")),
+            format!("{}{}", &av.remote_info.base_url, file),
             out_loc.clone()).await?;
 
         Ok(out_loc)
     }
 
-    /// Extract zstd_location to the detected sourcemods directory.
-    /// TODO replace unwrap/expect with match error handling
-    pub fn extract_package(zstd_location: String, out_dir: String) -> Result<(), BeansError>
+    /// Get the location of `file` in `download_cache`, when it has already been downloaded.
+    pub fn get_cached_file(&self, file: &str) -> Option<String>
     {
-        let tar_tmp_location = helper::get_tmp_file("data.tar".to_string());
-
-        let zstd_file = std::fs::File::open(&zstd_location)?;
-        let mut tar_tmp_file = std::fs::File::create_new(&tar_tmp_location)?;
-        zstd::stream::copy_decode(zstd_file, &tar_tmp_file)?;
-        tar_tmp_file = std::fs::File::open(&tar_tmp_location)?; // we do this again to make sure that the tar is properly opened.
-
-        let mut archive = tar::Archive::new(&tar_tmp_file);
-        let x = archive.unpack(&out_dir);
-        if helper::file_exists(tar_tmp_location.clone()) {
-            if let Err(e) = std::fs::remove_file(tar_tmp_location.clone()) {
-                sentry::capture_error(&e);
-                error!("[RunnerContext::extract_package] Failed to delete temporary file: {:}", e);
-                debug!("[RunnerContext::extract_package] Failed to delete {}\n{:#?}", tar_tmp_location, e);
-            }
+        let location = helper::join_path(self.download_cache.clone()?, cache_filename(file));
+        match helper::file_exists(location.clone()) {
+            true => Some(location),
+            false => None
         }
-        match x {
-            Err(e) => {
-                let xe = BeansError::TarExtractFailure {
-                    src_file: tar_tmp_location,
-                    target_dir: out_dir,
-                    error: e,
-                    backtrace: Backtrace::capture()
-                };
-                trace!("[RunnerContext::extract_package] {:}\n{:#?}", xe, xe);
-                sentry::capture_error(&xe);
-                return Err(xe);
-            },
-            Ok(_) => Ok(())
+    }
+
+    /// Extract the package at `location` to the detected sourcemods directory. The format is
+    /// detected with `extract::PackageFormat::detect`, and can be a directory.
+    /// Returns the location of every file in the package, relative to `out_dir`.
+    ///
+    /// Every entry is checked before anything is extracted (see `extract::unpack_checked`), and
+    /// `max_size` is the `postsz` of the package, when it's known.
+    pub fn extract_package(location: String, out_dir: String, max_size: Option<usize>) -> Result<Vec<String>, BeansError>
+    {
+        let x = crate::extract::extract(&location, &out_dir, max_size);
+        if let Err(xe) = &x {
+            trace!("[RunnerContext::extract_package] {:}\n{:#?}", xe, xe);
+            sentry::capture_error(xe);
         }
+        x
     }
 
+    /// Remove anything at the locations of `AppVarMod.symlinks` that isn't a symlink, so
+    /// patching doesn't write through them. They're created again with `repair_symlinks`.
     #[cfg(target_os = "linux")]
     pub fn prepare_symlink(&mut self) -> Result<(), BeansError>
     {
-        for pair in SYMLINK_FILES.into_iter() {
-            let target: &str = pair[1];
-            let mod_location = self.get_mod_location();
-            let ln_location = format!("{}{}", mod_location, target);
+        let mod_location = self.get_mod_location();
+        for symlink in self.appvar.mod_info.symlinks.iter() {
+            let ln_location = helper::join_path(mod_location.clone(), symlink.link.clone());
             if helper::file_exists(ln_location.clone())
             && helper::is_symlink(ln_location.clone()) == false {
                 if let Err(e) = std::fs::remove_file(&ln_location) {
@@ -267,12 +348,276 @@ impl RunnerContext
         // ignored since this symlink stuff is for linux only
         Ok(())
     }
+
+    /// Get the state of every symlink in `AppVarMod.symlinks` for the mod at `mod_location`,
+    /// without changing anything.
+    #[cfg(target_os = "linux")]
+    pub fn check_symlinks(mod_location: String) -> Vec<SymlinkReport>
+    {
+        let root = std::path::PathBuf::from(&mod_location);
+        let mut result: Vec<SymlinkReport> = Vec::new();
+        for symlink in crate::appvar::parse().mod_info.symlinks.into_iter() {
+            if !is_inside_mod(&symlink.link) || !is_inside_mod(&symlink.target) {
+                log::warn!("[RunnerContext::check_symlinks] Ignoring {} -> {}, since it's outside of the mod folder", symlink.link, symlink.target);
+                continue;
+            }
+            let link_location = root.join(&symlink.link);
+            let target_location = root.join(&symlink.target);
+            let state = if !target_location.is_file() {
+                SymlinkState::MissingTarget
+            } else {
+                match std::fs::symlink_metadata(&link_location) {
+                    Err(_) => SymlinkState::Missing,
+                    Ok(m) if m.file_type().is_symlink() => {
+                        match (std::fs::canonicalize(&link_location), std::fs::canonicalize(&target_location)) {
+                            (Ok(a), Ok(b)) if a == b => SymlinkState::Ok,
+                            _ => SymlinkState::Broken
+                        }
+                    },
+                    Ok(_) => SymlinkState::Broken
+                }
+            };
+            result.push(SymlinkReport {
+                link: symlink.link,
+                target: symlink.target,
+                state
+            });
+        }
+        result
+    }
+    #[cfg(not(target_os = "linux"))]
+    pub fn check_symlinks(_mod_location: String) -> Vec<SymlinkReport>
+    {
+        // ignored since this symlink stuff is for linux only
+        Vec::new()
+    }
+
+    /// Create the symlinks in `AppVarMod.symlinks` for the mod at `mod_location`, and replace
+    /// any that don't point to their target. Links with a target that doesn't exist are
+    /// reported, and left alone.
+    #[cfg(target_os = "linux")]
+    pub fn repair_symlinks(mod_location: String) -> Result<Vec<SymlinkReport>, BeansError>
+    {
+        let root = std::path::PathBuf::from(&mod_location);
+        let mut result = Self::check_symlinks(mod_location);
+        for report in result.iter_mut() {
+            let link_location = root.join(&report.link);
+            match report.state {
+                SymlinkState::Ok => continue,
+                SymlinkState::MissingTarget => {
+                    log::warn!("[RunnerContext::repair_symlinks] Not creating {}, since {} doesn't exist", report.link, report.target);
+                    continue;
+                },
+                SymlinkState::Broken => {
+                    if let Err(e) = std::fs::remove_file(&link_location) {
+                        return Err(BeansError::FileWriteFailure {
+                            location: link_location.to_string_lossy().to_string(),
+                            error: e
+                        });
+                    }
+                    report.state = SymlinkState::Repaired;
+                },
+                _ => report.state = SymlinkState::Created
+            }
+            // relative, so the mod folder can be moved without breaking the link.
+            let target = relative_link_target(&report.link, &report.target);
+            if let Err(e) = std::os::unix::fs::symlink(&target, &link_location) {
+                return Err(BeansError::FileWriteFailure {
+                    location: link_location.to_string_lossy().to_string(),
+                    error: e
+                });
+            }
+            info!("[RunnerContext::repair_symlinks] {}", report);
+        }
+        Ok(result)
+    }
+    #[cfg(not(target_os = "linux"))]
+    pub fn repair_symlinks(_mod_location: String) -> Result<Vec<SymlinkReport>, BeansError>
+    {
+        // ignored since this symlink stuff is for linux only
+        Ok(Vec::new())
+    }
+
+    /// Add the permissions from `AppVarMod.permissions` to everything in the mod at
+    /// `mod_location` that is missing them, since files from the package or from butler can be
+    /// read-only, or be missing the executable bit. Permissions are only ever added, so modes from
+    /// the package or the user are kept. Symlinks are left alone, and are never followed (see
+    /// `helper::nofollow::walk`). Every change that was made is logged and returned.
+    #[cfg(target_os = "linux")]
+    pub fn normalize_permissions(mod_location: String) -> Result<Vec<PermissionChange>, BeansError>
+    {
+        let policy = crate::appvar::parse().mod_info.permissions;
+        let mut result: Vec<PermissionChange> = Vec::new();
+        if !policy.enabled || !std::path::Path::new(&mod_location).is_dir() {
+            return Ok(result);
+        }
+        let mode = |value: &str, default: u32| match crate::appvar::AppVarPermissions::parse_mode(value) {
+            Some(v) => v,
+            None => {
+                log::warn!("[RunnerContext::normalize_permissions] Invalid mode \"{}\", using {:04o} instead", value, default);
+                default
+            }
+        };
+        let directory_mode = mode(&policy.directory, 0o700);
+        let file_mode = mode(&policy.file, 0o600);
+        let executable_mode = mode(&policy.executable, 0o700);
+
+        // mode that was being set when the walk failed.
+        let mut failed_mode: Option<u32> = None;
+        let walked = helper::nofollow::walk(std::path::Path::new(&mod_location), |entry| {
+            // only what is in the mod folder, not the mod folder itself.
+            if entry.relative.as_os_str().is_empty() {
+                return Ok(());
+            }
+            let relative = entry.relative.to_string_lossy().to_string();
+            let required = if entry.is_dir() {
+                directory_mode
+            } else if entry.is_file() {
+                match policy.is_executable(&relative) {
+                    true => executable_mode,
+                    false => file_mode
+                }
+            } else {
+                // symlinks (and anything else that isn't a file or directory)
+                return Ok(());
+            };
+            let current = entry.stat.st_mode & 0o7777;
+            let target = current | required;
+            if current == target {
+                return Ok(());
+            }
+            // SAFETY: name is a valid C string.
+            if unsafe { libc::fchmodat(entry.dir, entry.name.as_ptr(), target, libc::AT_SYMLINK_NOFOLLOW) } != 0 {
+                failed_mode = Some(target);
+                return Err(std::io::Error::last_os_error());
+            }
+            let change = PermissionChange {
+                location: relative,
+                old_mode: current,
+                new_mode: target
+            };
+            info!("[RunnerContext::normalize_permissions] {}", change);
+            result.push(change);
+            Ok(())
+        });
+        if let Err((location, error)) = walked {
+            let xe = match failed_mode {
+                Some(mode) => BeansError::PermissionSetFailure {
+                    location: location.to_string_lossy().to_string(),
+                    mode,
+                    error
+                },
+                None => BeansError::IO {
+                    error,
+                    backtrace: Backtrace::capture()
+                }
+            };
+            sentry::capture_error(&xe);
+            return Err(xe);
+        }
+        if !result.is_empty() {
+            println!("[RunnerContext::normalize_permissions] Fixed the permissions on {} file(s) in {}", result.len(), mod_location);
+        }
+        Ok(result)
+    }
+    #[cfg(not(target_os = "linux"))]
+    pub fn normalize_permissions(_mod_location: String) -> Result<Vec<PermissionChange>, BeansError>
+    {
+        // permissions are only set on linux
+        Ok(Vec::new())
+    }
 }
 
-pub const SYMLINK_FILES: &'static [&'static [&'static str; 2]] = &[
-    &["bin/server.so", "bin/server_srv.so"]
-];
+/// Permissions that were changed by `RunnerContext::normalize_permissions`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PermissionChange
+{
+    /// Location of the file or directory, relative to the mod folder.
+    pub location: String,
+    pub old_mode: u32,
+    pub new_mode: u32
+}
+impl std::fmt::Display for PermissionChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({:04o} -> {:04o})", self.location, self.old_mode, self.new_mode)
+    }
+}
 
+/// State of a symlink from `AppVarMod.symlinks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum SymlinkState
+{
+    /// The symlink points to the target.
+    Ok,
+    /// The symlink didn't exist, and was created.
+    Created,
+    /// The symlink didn't point to the target (or wasn't a symlink), and was replaced.
+    Repaired,
+    /// The symlink doesn't exist.
+    Missing,
+    /// The symlink doesn't point to the target, or isn't a symlink.
+    Broken,
+    /// The file that the symlink should point to doesn't exist.
+    MissingTarget
+}
+impl std::fmt::Display for SymlinkState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SymlinkState::Ok => write!(f, "ok"),
+            SymlinkState::Created => write!(f, "created"),
+            SymlinkState::Repaired => write!(f, "repaired"),
+            SymlinkState::Missing => write!(f, "missing"),
+            SymlinkState::Broken => write!(f, "doesn't point to the target"),
+            SymlinkState::MissingTarget => write!(f, "target doesn't exist")
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SymlinkReport
+{
+    /// Location of the symlink, relative to the mod folder.
+    pub link: String,
+    /// File that the symlink points to, relative to the mod folder.
+    pub target: String,
+    pub state: SymlinkState
+}
+impl std::fmt::Display for SymlinkReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> {} ({})", self.link, self.target, self.state)
+    }
+}
+
+/// Check that `location` is relative, and doesn't go outside of the folder it's relative to.
+#[cfg(target_os = "linux")]
+fn is_inside_mod(location: &str) -> bool
+{
+    use std::path::Component;
+    std::path::Path::new(location).components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+/// Get the path to `target` from the folder that `link` is in. Both are relative to the mod
+/// folder.
+#[cfg(target_os = "linux")]
+fn relative_link_target(link: &str, target: &str) -> std::path::PathBuf
+{
+    use std::path::{Component, Path, PathBuf};
+    let link_dir: Vec<Component> = Path::new(link).parent()
+        .map(|v| v.components().collect())
+        .unwrap_or_default();
+    let target: Vec<Component> = Path::new(target).components().collect();
+    let common = link_dir.iter().zip(target.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut result = PathBuf::new();
+    for _ in common..link_dir.len() {
+        result.push("..");
+    }
+    for component in target[common..].iter() {
+        result.push(component);
+    }
+    result
+}
 
 #[derive(Clone, Debug)]
 pub enum SourceModDirectoryParam
@@ -287,4 +632,11 @@ impl Default for SourceModDirectoryParam
     fn default() -> Self {
         SourceModDirectoryParam::AutoDetect
     }
+}
+
+/// Name of the file in `RunnerContext.download_cache` for `file` (relative to `base_url` in the
+/// AppVar).
+pub fn cache_filename(file: &str) -> String
+{
+    file.replace(['/', '\\', ':'], "_")
 }
\ No newline at end of file
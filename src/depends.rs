@@ -79,8 +79,13 @@ pub async fn try_install_vcredist() -> Result<(), BeansError>
         });
     }
 
+    // nobody is there to click through the installer when we're automated.
+    let ui_arg = match crate::flags::automated() {
+        true => "/quiet",
+        false => "/passive"
+    };
     std::process::Command::new(&out_loc)
-        .args(["/install","/passive","/norestart"])
+        .args(["/install",ui_arg,"/norestart"])
         .spawn()
         .expect("Failed to install vsredist!")
         .wait()?;
@@ -3,6 +3,7 @@ use std::fmt::{Display, Formatter};
 use std::num::ParseIntError;
 use thiserror::Error;
 use crate::appvar::AppVarData;
+use crate::process::GameProcess;
 use crate::version::AdastralVersionFile;
 
 #[derive(Debug, Error)]
@@ -13,6 +14,12 @@ pub enum BeansError
     FreeSpaceCheckFailure {
         location: String
     },
+    #[error("Not enough free space in {location} (requires {required} bytes, {available} bytes available)")]
+    NotEnoughFreeSpace {
+        location: String,
+        required: usize,
+        available: u64
+    },
     /// Failed to find the sourcemod mod folder.
     #[error("Failed to detect sourcemod folder. Please provide it via the --location argument.")]
     SourceModLocationNotFound,
@@ -33,6 +40,36 @@ pub enum BeansError
         error: std::io::Error,
         backtrace: Backtrace
     },
+    #[error("Refusing to extract {src_file} to {target_dir}, since {} entries in it aren't safe to extract ({})",
+        rejected.len(), rejected.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(", "))]
+    UnsafePackage {
+        src_file: String,
+        target_dir: String,
+        rejected: Vec<crate::extract::RejectedEntry>
+    },
+    #[error("Refusing to extract {src_file}, since it contains {size} bytes but it should only have {limit} bytes")]
+    PackageSizeExceeded {
+        src_file: String,
+        limit: usize,
+        size: u64
+    },
+    #[error("SHA256 of {location} is {actual}, but it should be {expected}")]
+    PackageHashMismatch {
+        location: String,
+        expected: String,
+        actual: String
+    },
+    #[error("Can't install from {location}, since {reason}")]
+    PackageSourceInvalid {
+        location: String,
+        reason: String
+    },
+    #[error("Extracted {written} bytes to {target_dir}, but the package has {expected} bytes")]
+    ExtractSizeMismatch {
+        target_dir: String,
+        expected: u64,
+        written: u64
+    },
     #[error("Failed to send request ({error:})")]
     Reqwest {
         error: reqwest::Error,
@@ -66,20 +103,21 @@ pub enum BeansError
         backtrace: Backtrace
     },
 
-    #[error("Failed to run the verify command with butler. ({error:})")]
+    /// `remote` and `reason` are boxed to keep `BeansError` (and every `Result` with it) small.
+    #[error("Failed to run the verify command with butler. ({reason:})")]
     ButlerVerifyFailure {
         signature_url: String,
         gamedir: String,
-        remote: String,
-        error: std::io::Error,
+        remote: Box<String>,
+        reason: Box<ButlerFailureReason>,
         backtrace: Backtrace
     },
 
-    #[error("Failed to run the apply command with butler. {error:}")]
+    #[error("Failed to run the apply command with butler. ({reason:})")]
     ButlerPatchFailure {
         patchfile_location: String,
         gamedir: String,
-        error: std::io::Error,
+        reason: Box<ButlerFailureReason>,
         backtrace: Backtrace
     },
 
@@ -134,7 +172,7 @@ pub enum BeansError
     #[error("Failed to serialize provided AppVarData to JSON. ({error:})")]
     AppVarDataSerializeFailure {
         error: serde_json::Error,
-        data: AppVarData
+        data: Box<AppVarData>
     },
 
     #[error("Failed to read gameinfo.txt at {location} ({error:})")]
@@ -151,9 +189,458 @@ pub enum BeansError
         location: String
     },
 
+    #[error("Failed to set permissions on {location} to {mode:04o} ({error:})")]
+    PermissionSetFailure {
+        location: String,
+        mode: u32,
+        error: std::io::Error
+    },
+
+    #[error("Failed to change the owner of {location} to {uid}:{gid} ({error:})")]
+    OwnershipSetFailure {
+        location: String,
+        uid: u32,
+        gid: u32,
+        error: std::io::Error
+    },
+    #[error("beans-rs is running as root, but {location} belongs to {uid}:{gid}. Run beans-rs as that user, or use --allow-root to give the files back to them afterwards.")]
+    RunningAsRoot {
+        location: String,
+        uid: u32,
+        gid: u32
+    },
+
+    #[error("Unable to ask \"{prompt}\" since beans-rs is running in automated mode. Provide it with the {env_key} environment variable.")]
+    AutomatedInputRequired {
+        prompt: String,
+        env_key: String
+    },
+    #[error("Invalid value \"{value}\" for {env_key} ({reason})")]
+    AutomatedInputInvalid {
+        env_key: String,
+        value: String,
+        reason: String
+    },
+
     #[error("Failed to backup gameinfo.txt, {reason:}")]
     GameinfoBackupFailure {
         reason: GameinfoBackupFailureReason
+    },
+
+    #[error("Refusing to launch since the installed version is out of date (current: {current}, latest: {latest}). Update the game, or use --force to launch anyway.")]
+    LaunchOutOfDate {
+        current: usize,
+        latest: usize
+    },
+    #[error("Failed to launch the game with {program} ({error:})")]
+    LaunchFailure {
+        program: String,
+        error: std::io::Error,
+        backtrace: Backtrace
+    },
+
+    #[error("Could not find Source SDK Base 2013 Multiplayer, which is required for Steam shortcuts. Please install it from Steam.")]
+    SourceSdkNotFound,
+    #[error("Could not find any Steam users in {location}. Please log in to Steam at least once.")]
+    SteamUserdataNotFound {
+        location: String
+    },
+    #[error("Failed to parse Steam shortcuts at {location} (offset {offset}: {reason})")]
+    SteamShortcutsParseFailure {
+        location: String,
+        offset: usize,
+        reason: String
+    },
+    #[error("Could not find the home directory for the current user.")]
+    HomeDirectoryNotFound,
+
+    #[error("This version of beans-rs (v{current}) is too old, v{minimum} or later is required. Please run \"beans-rs self-update\" or download the latest version.")]
+    BeansVersionTooOld {
+        current: String,
+        minimum: String
+    },
+    #[error("Failed to update beans-rs, {reason:}")]
+    SelfUpdateFailure {
+        reason: SelfUpdateFailureReason,
+        backtrace: Backtrace
+    },
+
+    #[error("The game or server is using {location}, please close it first (or use --wait-for-exit). Found: {}", processes.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(", "))]
+    GameRunning {
+        location: String,
+        processes: Vec<GameProcess>
+    },
+
+    #[error("Failed to stop the server at {address} with RCON, {reason:}")]
+    RconFailure {
+        address: String,
+        reason: RconFailureReason,
+        backtrace: Backtrace
+    },
+    #[error("Invalid fleet manifest at {location}, {reason}")]
+    FleetManifestInvalid {
+        location: String,
+        reason: String
+    },
+    #[error("{} of {total} fleet target(s) failed ({})", failed.len(), failed.join(", "))]
+    FleetTargetsFailed {
+        failed: Vec<String>,
+        total: usize
+    },
+
+    #[error("Failed to query the server at {address} with A2S_INFO, {error:}")]
+    A2sQueryFailure {
+        address: String,
+        error: std::io::Error,
+        backtrace: Backtrace
+    },
+    #[error("The server at {address} didn't respond, so the update was stopped (since stop_when_unreachable is set in wait_for_empty)")]
+    ServerUnreachable {
+        address: String
+    },
+
+    #[error("Could not find the add-on \"{id}\" in versions.json")]
+    AddonNotFound {
+        id: String
+    },
+    #[error("The add-on \"{id}\" isn't installed")]
+    AddonNotInstalled {
+        id: String
+    },
+    #[error("\"{id}\" isn't a valid add-on ID, since they can only have letters, numbers, \"_\", \".\" and \"-\"")]
+    AddonIdInvalid {
+        id: String
+    },
+    #[error("The add-on \"{id}\" requires v{required} of the game or later (installed: {current:?})")]
+    AddonGameVersionTooOld {
+        id: String,
+        required: usize,
+        current: Option<usize>
+    },
+
+    #[error("The {hook} hook failed, {reason:} (command: {command})")]
+    HookFailure {
+        hook: String,
+        command: String,
+        reason: HookFailureReason,
+        backtrace: Backtrace
+    },
+
+    #[error("Invalid value \"{value}\" for argument --{name} ({reason})")]
+    InvalidArgument {
+        name: String,
+        value: String,
+        reason: String
+    }
+}
+/// Process exit codes that the beans-rs CLI uses, grouped by the category of failure.
+///
+/// These are documented in `README.md` and are relied on by scripts, so existing values must
+/// never be changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum BeansExitCode
+{
+    Success = 0,
+    /// Failure that doesn't fit in any of the other categories.
+    General = 1,
+    /// Invalid command-line argument.
+    InvalidArgument = 2,
+    /// Failed to send a request, or failed to download something.
+    Network = 10,
+    /// Data from the server is invalid, or the requested version doesn't exist.
+    RemoteData = 11,
+    /// Not enough free space, or the free space couldn't be checked.
+    DiskSpace = 20,
+    /// Failed to read, write or find a file.
+    FileSystem = 21,
+    /// Failed to extract a package.
+    Extraction = 22,
+    /// butler failed to verify or patch the game.
+    Butler = 30,
+    /// Couldn't find Steam or the sourcemods folder.
+    SourceModsNotFound = 40,
+    /// The mod isn't installed.
+    NotInstalled = 41,
+    /// The latest version is already installed.
+    AlreadyLatest = 42,
+    /// The installed version is out of date.
+    OutOfDate = 43,
+    /// The game or dedicated server is using the mod.
+    GameRunning = 44,
+    /// Failed to read, write or migrate the `.adastral` version file.
+    VersionFile = 50,
+    /// Failed to read, backup or set permissions on `gameinfo.txt`.
+    GameInfo = 51,
+    /// Input is required, but beans-rs is running in automated mode.
+    AutomatedInput = 60,
+    /// Failed to launch the game.
+    Launch = 70,
+    /// Failed to create a desktop entry or Steam shortcut.
+    Shortcut = 71,
+    /// Failed to update beans-rs.
+    SelfUpdate = 80,
+    /// beans-rs is older than the minimum version that the server allows.
+    BeansVersionTooOld = 81,
+    /// A hook that runs before a workflow failed.
+    Hook = 90,
+    /// Failed to stop or query the dedicated server.
+    Rcon = 91,
+    /// One or more targets in a fleet manifest failed.
+    Fleet = 92
+}
+impl BeansError
+{
+    /// Get the category of this error as an exit code for the process.
+    pub fn exit_code(&self) -> BeansExitCode
+    {
+        match self {
+            BeansError::InvalidArgument { .. }
+            | BeansError::FleetManifestInvalid { .. }
+            | BeansError::AddonIdInvalid { .. }
+            | BeansError::RunningAsRoot { .. } => BeansExitCode::InvalidArgument,
+            BeansError::Reqwest { .. }
+            | BeansError::DownloadFailure { .. } => BeansExitCode::Network,
+            BeansError::SerdeJson { .. }
+            | BeansError::RemoteVersionNotFound { .. }
+            | BeansError::AddonNotFound { .. }
+            | BeansError::PackageHashMismatch { .. } => BeansExitCode::RemoteData,
+            BeansError::FreeSpaceCheckFailure { .. }
+            | BeansError::NotEnoughFreeSpace { .. } => BeansExitCode::DiskSpace,
+            BeansError::FileOpenFailure { .. }
+            | BeansError::FileWriteFailure { .. }
+            | BeansError::FileNotFound { .. }
+            | BeansError::PermissionSetFailure { .. }
+            | BeansError::OwnershipSetFailure { .. }
+            | BeansError::HomeDirectoryNotFound
+            | BeansError::IO { .. } => BeansExitCode::FileSystem,
+            BeansError::TarExtractFailure { .. }
+            | BeansError::UnsafePackage { .. }
+            | BeansError::PackageSizeExceeded { .. }
+            | BeansError::ExtractSizeMismatch { .. }
+            | BeansError::PackageSourceInvalid { .. } => BeansExitCode::Extraction,
+            BeansError::ButlerVerifyFailure { .. }
+            | BeansError::ButlerPatchFailure { .. } => BeansExitCode::Butler,
+            BeansError::SourceModLocationNotFound
+            | BeansError::SteamNotFound
+            | BeansError::RegistryKeyFailure { .. } => BeansExitCode::SourceModsNotFound,
+            BeansError::TargetSourcemodNotInstalled { .. }
+            | BeansError::AddonNotInstalled { .. } => BeansExitCode::NotInstalled,
+            BeansError::LatestVersionAlreadyInstalled { .. } => BeansExitCode::AlreadyLatest,
+            BeansError::LaunchOutOfDate { .. }
+            | BeansError::AddonGameVersionTooOld { .. } => BeansExitCode::OutOfDate,
+            BeansError::GameRunning { .. } => BeansExitCode::GameRunning,
+            BeansError::LaunchFailure { .. } => BeansExitCode::Launch,
+            BeansError::SourceSdkNotFound
+            | BeansError::SteamUserdataNotFound { .. }
+            | BeansError::SteamShortcutsParseFailure { .. } => BeansExitCode::Shortcut,
+            BeansError::SelfUpdateFailure { .. } => BeansExitCode::SelfUpdate,
+            BeansError::BeansVersionTooOld { .. } => BeansExitCode::BeansVersionTooOld,
+            BeansError::HookFailure { .. } => BeansExitCode::Hook,
+            BeansError::RconFailure { .. }
+            | BeansError::A2sQueryFailure { .. }
+            | BeansError::ServerUnreachable { .. } => BeansExitCode::Rcon,
+            BeansError::FleetTargetsFailed { .. } => BeansExitCode::Fleet,
+            BeansError::VersionFileMigrationFailure { .. }
+            | BeansError::VersionFileMigrationDeleteFailure { .. }
+            | BeansError::VersionFileSerialize { .. }
+            | BeansError::VersionFileParseFailure { .. }
+            | BeansError::VersionFileReadFailure { .. } => BeansExitCode::VersionFile,
+            BeansError::GameInfoFileReadFail { .. }
+            | BeansError::GameInfoPermissionSetFail { .. }
+            | BeansError::GameinfoBackupFailure { .. } => BeansExitCode::GameInfo,
+            BeansError::AutomatedInputRequired { .. }
+            | BeansError::AutomatedInputInvalid { .. } => BeansExitCode::AutomatedInput,
+            BeansError::AppVarDataSerializeFailure { .. } => BeansExitCode::General
+        }
+    }
+
+    /// Get the name of the variant and its fields, for machine-readable output.
+    ///
+    /// Backtraces are not included, and errors from other crates are converted to strings.
+    pub fn details(&self) -> (&'static str, serde_json::Value)
+    {
+        use serde_json::json;
+        match self {
+            BeansError::FreeSpaceCheckFailure { location }
+            => ("FreeSpaceCheckFailure", json!({ "location": location })),
+            BeansError::NotEnoughFreeSpace { location, required, available }
+            => ("NotEnoughFreeSpace", json!({ "location": location, "required": required, "available": available })),
+            BeansError::SourceModLocationNotFound
+            => ("SourceModLocationNotFound", json!({})),
+            BeansError::FileOpenFailure { location, error }
+            => ("FileOpenFailure", json!({ "location": location, "error": io_error_json(error) })),
+            BeansError::FileWriteFailure { location, error }
+            => ("FileWriteFailure", json!({ "location": location, "error": io_error_json(error) })),
+            BeansError::TarExtractFailure { src_file, target_dir, error, .. }
+            => ("TarExtractFailure", json!({ "src_file": src_file, "target_dir": target_dir, "error": io_error_json(error) })),
+            BeansError::UnsafePackage { src_file, target_dir, rejected }
+            => ("UnsafePackage", json!({ "src_file": src_file, "target_dir": target_dir, "rejected": rejected })),
+            BeansError::PackageSizeExceeded { src_file, limit, size }
+            => ("PackageSizeExceeded", json!({ "src_file": src_file, "limit": limit, "size": size })),
+            BeansError::PackageHashMismatch { location, expected, actual }
+            => ("PackageHashMismatch", json!({ "location": location, "expected": expected, "actual": actual })),
+            BeansError::PackageSourceInvalid { location, reason }
+            => ("PackageSourceInvalid", json!({ "location": location, "reason": reason })),
+            BeansError::ExtractSizeMismatch { target_dir, expected, written }
+            => ("ExtractSizeMismatch", json!({ "target_dir": target_dir, "expected": expected, "written": written })),
+            BeansError::Reqwest { error, .. }
+            => ("Reqwest", json!({ "error": format!("{:}", error), "url": error.url().map(|v| v.to_string()) })),
+            BeansError::SerdeJson { error, .. }
+            => ("SerdeJson", json!({ "error": format!("{:}", error) })),
+            BeansError::LatestVersionAlreadyInstalled { current, latest }
+            => ("LatestVersionAlreadyInstalled", json!({ "current": current, "latest": latest })),
+            BeansError::DownloadFailure { reason }
+            => ("DownloadFailure", json!({ "reason": match reason {
+                DownloadFailureReason::Reqwest { url, error }
+                => json!({ "type": "Reqwest", "url": url, "error": format!("{:}", error) }),
+                DownloadFailureReason::FileNotFound { location }
+                => json!({ "type": "FileNotFound", "location": location })
+            }})),
+            BeansError::IO { error, .. }
+            => ("IO", json!({ "error": io_error_json(error) })),
+            BeansError::TargetSourcemodNotInstalled { missing_file, .. }
+            => ("TargetSourcemodNotInstalled", json!({ "missing_file": missing_file })),
+            BeansError::ButlerVerifyFailure { signature_url, gamedir, remote, reason, .. }
+            => ("ButlerVerifyFailure", json!({ "signature_url": signature_url, "gamedir": gamedir, "remote": remote, "reason": butler_reason_json(reason) })),
+            BeansError::ButlerPatchFailure { patchfile_location, gamedir, reason, .. }
+            => ("ButlerPatchFailure", json!({ "patchfile_location": patchfile_location, "gamedir": gamedir, "reason": butler_reason_json(reason) })),
+            BeansError::FileNotFound { location, .. }
+            => ("FileNotFound", json!({ "location": location })),
+            BeansError::RemoteVersionNotFound { version }
+            => ("RemoteVersionNotFound", json!({ "version": version })),
+            BeansError::SteamNotFound
+            => ("SteamNotFound", json!({})),
+            BeansError::RegistryKeyFailure { msg, error, .. }
+            => ("RegistryKeyFailure", json!({ "msg": msg, "error": io_error_json(error) })),
+            BeansError::VersionFileMigrationFailure { error, location }
+            => ("VersionFileMigrationFailure", json!({ "location": location, "error": io_error_json(error) })),
+            BeansError::VersionFileMigrationDeleteFailure { error, location }
+            => ("VersionFileMigrationDeleteFailure", json!({ "location": location, "error": io_error_json(error) })),
+            BeansError::VersionFileSerialize { error, instance }
+            => ("VersionFileSerialize", json!({ "error": format!("{:}", error), "instance": instance })),
+            BeansError::VersionFileParseFailure { error, old_location, old_content }
+            => ("VersionFileParseFailure", json!({ "error": format!("{:}", error), "old_location": old_location, "old_content": old_content })),
+            BeansError::VersionFileReadFailure { error, location }
+            => ("VersionFileReadFailure", json!({ "location": location, "error": io_error_json(error) })),
+            BeansError::AppVarDataSerializeFailure { error, data }
+            => ("AppVarDataSerializeFailure", json!({ "error": format!("{:}", error), "data": data })),
+            BeansError::GameInfoFileReadFail { error, location, .. }
+            => ("GameInfoFileReadFail", json!({ "location": location, "error": io_error_json(error) })),
+            BeansError::GameInfoPermissionSetFail { error, permissions, location }
+            => ("GameInfoPermissionSetFail", json!({ "location": location, "permissions": format!("{:?}", permissions), "error": io_error_json(error) })),
+            BeansError::PermissionSetFailure { location, mode, error }
+            => ("PermissionSetFailure", json!({ "location": location, "mode": format!("{:04o}", mode), "error": io_error_json(error) })),
+            BeansError::OwnershipSetFailure { location, uid, gid, error }
+            => ("OwnershipSetFailure", json!({ "location": location, "uid": uid, "gid": gid, "error": io_error_json(error) })),
+            BeansError::RunningAsRoot { location, uid, gid }
+            => ("RunningAsRoot", json!({ "location": location, "uid": uid, "gid": gid })),
+            BeansError::AutomatedInputRequired { prompt, env_key }
+            => ("AutomatedInputRequired", json!({ "prompt": prompt, "env_key": env_key })),
+            BeansError::AutomatedInputInvalid { env_key, value, reason }
+            => ("AutomatedInputInvalid", json!({ "env_key": env_key, "value": value, "reason": reason })),
+            BeansError::GameinfoBackupFailure { reason }
+            => ("GameinfoBackupFailure", json!({ "reason": format!("{:}", reason) })),
+            BeansError::LaunchOutOfDate { current, latest }
+            => ("LaunchOutOfDate", json!({ "current": current, "latest": latest })),
+            BeansError::LaunchFailure { program, error, .. }
+            => ("LaunchFailure", json!({ "program": program, "error": io_error_json(error) })),
+            BeansError::SourceSdkNotFound
+            => ("SourceSdkNotFound", json!({})),
+            BeansError::SteamUserdataNotFound { location }
+            => ("SteamUserdataNotFound", json!({ "location": location })),
+            BeansError::SteamShortcutsParseFailure { location, offset, reason }
+            => ("SteamShortcutsParseFailure", json!({ "location": location, "offset": offset, "reason": reason })),
+            BeansError::HomeDirectoryNotFound
+            => ("HomeDirectoryNotFound", json!({})),
+            BeansError::BeansVersionTooOld { current, minimum }
+            => ("BeansVersionTooOld", json!({ "current": current, "minimum": minimum })),
+            BeansError::SelfUpdateFailure { reason, .. }
+            => ("SelfUpdateFailure", json!({ "reason": match reason {
+                SelfUpdateFailureReason::AssetNotFound { tag, asset_name }
+                => json!({ "type": "AssetNotFound", "tag": tag, "asset_name": asset_name }),
+                SelfUpdateFailureReason::HashMissing { asset_name }
+                => json!({ "type": "HashMissing", "asset_name": asset_name }),
+                SelfUpdateFailureReason::HashMismatch { expected, actual }
+                => json!({ "type": "HashMismatch", "expected": expected, "actual": actual }),
+                SelfUpdateFailureReason::Replace { location, error }
+                => json!({ "type": "Replace", "location": location, "error": io_error_json(error) }),
+                SelfUpdateFailureReason::SmokeTest { message }
+                => json!({ "type": "SmokeTest", "message": message }),
+                SelfUpdateFailureReason::Restore { location, error }
+                => json!({ "type": "Restore", "location": location, "error": io_error_json(error) })
+            }})),
+            BeansError::GameRunning { location, processes }
+            => ("GameRunning", json!({ "location": location, "processes": processes })),
+            BeansError::RconFailure { address, reason, .. }
+            => ("RconFailure", json!({ "address": address, "reason": match reason {
+                RconFailureReason::Connect { error }
+                => json!({ "type": "Connect", "error": io_error_json(error) }),
+                RconFailureReason::Io { error }
+                => json!({ "type": "Io", "error": io_error_json(error) }),
+                RconFailureReason::AuthFailed
+                => json!({ "type": "AuthFailed" }),
+                RconFailureReason::InvalidPacket { message }
+                => json!({ "type": "InvalidPacket", "message": message }),
+                RconFailureReason::ServerDidNotExit { timeout }
+                => json!({ "type": "ServerDidNotExit", "timeout": timeout })
+            }})),
+            BeansError::A2sQueryFailure { address, error, .. }
+            => ("A2sQueryFailure", json!({ "address": address, "error": io_error_json(error) })),
+            BeansError::ServerUnreachable { address }
+            => ("ServerUnreachable", json!({ "address": address })),
+            BeansError::FleetManifestInvalid { location, reason }
+            => ("FleetManifestInvalid", json!({ "location": location, "reason": reason })),
+            BeansError::FleetTargetsFailed { failed, total }
+            => ("FleetTargetsFailed", json!({ "failed": failed, "total": total })),
+            BeansError::AddonNotFound { id }
+            => ("AddonNotFound", json!({ "id": id })),
+            BeansError::AddonNotInstalled { id }
+            => ("AddonNotInstalled", json!({ "id": id })),
+            BeansError::AddonIdInvalid { id }
+            => ("AddonIdInvalid", json!({ "id": id })),
+            BeansError::AddonGameVersionTooOld { id, required, current }
+            => ("AddonGameVersionTooOld", json!({ "id": id, "required": required, "current": current })),
+            BeansError::HookFailure { hook, command, reason, .. }
+            => ("HookFailure", json!({ "hook": hook, "command": command, "reason": match reason {
+                HookFailureReason::Spawn { error }
+                => json!({ "type": "Spawn", "error": io_error_json(error) }),
+                HookFailureReason::ExitCode { code }
+                => json!({ "type": "ExitCode", "code": code })
+            }})),
+            BeansError::InvalidArgument { name, value, reason }
+            => ("InvalidArgument", json!({ "name": name, "value": value, "reason": reason }))
+        }
+    }
+
+    /// Create the JSON object that is printed when the `--json` argument is used.
+    pub fn to_json(&self) -> serde_json::Value
+    {
+        let (variant, fields) = self.details();
+        serde_json::json!({
+            "error": {
+                "variant": variant,
+                "message": format!("{:}", self),
+                "exit_code": self.exit_code() as i32,
+                "fields": fields
+            }
+        })
+    }
+}
+fn io_error_json(error: &std::io::Error) -> serde_json::Value
+{
+    serde_json::json!({
+        "kind": format!("{:?}", error.kind()),
+        "message": format!("{:}", error)
+    })
+}
+fn butler_reason_json(reason: &ButlerFailureReason) -> serde_json::Value
+{
+    match reason {
+        ButlerFailureReason::Spawn(e)
+        => serde_json::json!({ "type": "Spawn", "error": io_error_json(e) }),
+        ButlerFailureReason::ExitCode { code, message }
+        => serde_json::json!({ "type": "ExitCode", "code": code, "message": message })
     }
 }
 #[derive(Debug)]
@@ -169,6 +656,148 @@ pub enum DownloadFailureReason
     }
 }
 #[derive(Debug)]
+pub enum ButlerFailureReason
+{
+    /// Failed to start butler, or failed to wait for it to exit.
+    Spawn(std::io::Error),
+    /// butler exited with a non-zero exit code.
+    ExitCode {
+        /// Will be `None` when butler was terminated by a signal.
+        code: Option<i32>,
+        /// Last error message that butler sent before exiting.
+        message: Option<String>
+    }
+}
+impl Display for ButlerFailureReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ButlerFailureReason::Spawn(e)
+            => write!(f, "Failed to run butler ({:})", e),
+            ButlerFailureReason::ExitCode { code, message } => {
+                match code {
+                    Some(c) => write!(f, "butler exited with code {}", c)?,
+                    None => write!(f, "butler was terminated")?
+                };
+                match message {
+                    Some(m) => write!(f, ": {}", m),
+                    None => Ok(())
+                }
+            }
+        }
+    }
+}
+#[derive(Debug)]
+pub enum SelfUpdateFailureReason
+{
+    /// The release doesn't have an asset for the current platform.
+    AssetNotFound {
+        tag: String,
+        asset_name: String
+    },
+    /// Couldn't find the SHA256 hash for the asset, so it can't be trusted.
+    HashMissing {
+        asset_name: String
+    },
+    /// The downloaded asset doesn't match the expected hash.
+    HashMismatch {
+        expected: String,
+        actual: String
+    },
+    /// Failed to move the current executable, or failed to copy the new one in its place.
+    Replace {
+        location: String,
+        error: std::io::Error
+    },
+    /// The new executable failed to run with `--version`. The backup has been restored.
+    SmokeTest {
+        message: String
+    },
+    /// Failed to restore the backup after the smoke test failed.
+    Restore {
+        location: String,
+        error: std::io::Error
+    }
+}
+impl Display for SelfUpdateFailureReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SelfUpdateFailureReason::AssetNotFound { tag, asset_name }
+            => write!(f, "release {} doesn't have the asset {}", tag, asset_name),
+            SelfUpdateFailureReason::HashMissing { asset_name }
+            => write!(f, "couldn't find the SHA256 hash for {}", asset_name),
+            SelfUpdateFailureReason::HashMismatch { expected, actual }
+            => write!(f, "hash mismatch (expected {}, got {})", expected, actual),
+            SelfUpdateFailureReason::Replace { location, error }
+            => write!(f, "couldn't replace {} ({:})", location, error),
+            SelfUpdateFailureReason::SmokeTest { message }
+            => write!(f, "the new version failed to run, so the previous version was restored ({})", message),
+            SelfUpdateFailureReason::Restore { location, error }
+            => write!(f, "the new version failed to run, and the previous version couldn't be restored to {} ({:})", location, error)
+        }
+    }
+}
+#[derive(Debug)]
+pub enum RconFailureReason
+{
+    /// Couldn't connect to the server. Usually means that the server isn't running.
+    Connect {
+        error: std::io::Error
+    },
+    /// Failed to read or write a packet.
+    Io {
+        error: std::io::Error
+    },
+    /// The server rejected the password.
+    AuthFailed,
+    InvalidPacket {
+        message: String
+    },
+    /// The server was still running `timeout` seconds after `quit` was sent.
+    ServerDidNotExit {
+        timeout: u64
+    }
+}
+impl Display for RconFailureReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RconFailureReason::Connect { error }
+            => write!(f, "couldn't connect ({:})", error),
+            RconFailureReason::Io { error }
+            => write!(f, "connection failed ({:})", error),
+            RconFailureReason::AuthFailed
+            => write!(f, "the password was rejected"),
+            RconFailureReason::InvalidPacket { message }
+            => write!(f, "invalid packet ({})", message),
+            RconFailureReason::ServerDidNotExit { timeout }
+            => write!(f, "the server was still running {}s after quit was sent", timeout)
+        }
+    }
+}
+#[derive(Debug)]
+pub enum HookFailureReason
+{
+    /// Failed to start the shell that runs the hook.
+    Spawn {
+        error: std::io::Error
+    },
+    /// The hook exited with a non-zero exit code. `code` is `None` when it was killed by a signal.
+    ExitCode {
+        code: Option<i32>
+    }
+}
+impl Display for HookFailureReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HookFailureReason::Spawn { error }
+            => write!(f, "couldn't run it ({:})", error),
+            HookFailureReason::ExitCode { code: Some(code) }
+            => write!(f, "it exited with {}", code),
+            HookFailureReason::ExitCode { code: None }
+            => write!(f, "it was killed by a signal")
+        }
+    }
+}
+#[derive(Debug)]
 pub enum GameinfoBackupFailureReason
 {
     ReadContentFail(GameinfoBackupReadContentFail),
@@ -0,0 +1,1262 @@
+use std::backtrace::Backtrace;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Display, Formatter};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, info, warn};
+use crate::{BeansError, helper};
+
+/// Headers and padding in a tar file are about 1KB for every entry, so this much more than the
+/// size of the files in it is allowed when decompressing a package.
+pub const DECOMPRESS_OVERHEAD: u64 = 256 * 1024 * 1024;
+/// Start of the name of every file that beans-rs keeps its state in, see `is_state_file`.
+pub const STATE_FILE_PREFIX: &str = ".adastral";
+/// Most symlinks that are followed when checking where an entry ends up, so loops are rejected.
+const MAX_LINK_HOPS: usize = 40;
+
+/// Format of a package, which is detected with `PackageFormat::detect`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum PackageFormat
+{
+    /// `.tar.zst`, which is what packages from `versions.json` use.
+    TarZstd,
+    /// `.tar.gz`
+    TarGzip,
+    /// `.tar.xz`
+    TarXz,
+    /// `.tar` that isn't compressed.
+    Tar,
+    Zip,
+    /// Package that has already been extracted.
+    Directory
+}
+impl Display for PackageFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackageFormat::TarZstd => write!(f, ".tar.zst"),
+            PackageFormat::TarGzip => write!(f, ".tar.gz"),
+            PackageFormat::TarXz => write!(f, ".tar.xz"),
+            PackageFormat::Tar => write!(f, ".tar"),
+            PackageFormat::Zip => write!(f, ".zip"),
+            PackageFormat::Directory => write!(f, "directory")
+        }
+    }
+}
+impl PackageFormat
+{
+    /// Detect the format of the package at `location` with the first bytes of it (instead of
+    /// the extension, since that can be wrong).
+    pub fn detect(location: &str) -> Result<Self, BeansError>
+    {
+        let path = Path::new(location);
+        if path.is_dir() {
+            return Ok(PackageFormat::Directory);
+        }
+        let mut file = std::fs::File::open(path)?;
+        let mut header: Vec<u8> = Vec::new();
+        file.by_ref().take(512).read_to_end(&mut header)?;
+        let format = if header.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+            PackageFormat::TarZstd
+        } else if header.starts_with(&[0x1F, 0x8B]) {
+            PackageFormat::TarGzip
+        } else if header.starts_with(&[0xFD, b'7', b'z', b'X', b'Z', 0x00]) {
+            PackageFormat::TarXz
+        } else if header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06") {
+            PackageFormat::Zip
+        } else if header.len() >= 262 && &header[257..262] == b"ustar" {
+            PackageFormat::Tar
+        } else if location.to_lowercase().ends_with(".tar") {
+            // tar files from before POSIX.1-1988 don't have "ustar" in the header.
+            PackageFormat::Tar
+        } else {
+            return Err(BeansError::PackageSourceInvalid {
+                location: location.to_string(),
+                reason: "it isn't a .tar.zst, .tar.gz, .tar.xz, .tar, .zip or a directory".to_string()
+            });
+        };
+        debug!("[PackageFormat::detect] {} is a {}", location, format);
+        Ok(format)
+    }
+}
+
+/// Extract the package at `location` to `out_dir` (the sourcemods folder) with the extractor
+/// for its format. Every format is checked the same way as `unpack_checked` does.
+///
+/// Returns the location of every file in the package, relative to `out_dir`.
+pub fn extract(location: &str, out_dir: &str, max_size: Option<usize>) -> Result<Vec<String>, BeansError>
+{
+    match PackageFormat::detect(location)? {
+        PackageFormat::Tar => unpack_checked(location, out_dir, max_size),
+        PackageFormat::Zip => unpack_zip(location, out_dir, max_size),
+        PackageFormat::Directory => copy_directory(location, out_dir, max_size),
+        format => {
+            let tar_location = decompress(location, format, max_size)?;
+            let result = unpack_checked(&tar_location, out_dir, max_size);
+            if let Err(e) = std::fs::remove_file(&tar_location) {
+                sentry::capture_error(&e);
+                log::error!("[extract::extract] Failed to delete temporary file: {:}", e);
+                debug!("[extract::extract] Failed to delete {}\n{:#?}", tar_location, e);
+            }
+            result
+        }
+    }
+}
+
+/// Check every entry in the package at `location` like `extract` does, without writing
+/// anything, and fail the same way when it isn't safe to extract. Returns `None` for `.tar.xz`
+/// packages, since they can only be checked once they have been decompressed to a temporary
+/// file.
+pub fn list_package(location: &str, out_dir: &str, max_size: Option<usize>) -> Result<Option<ArchiveSummary>, BeansError>
+{
+    let format = PackageFormat::detect(location)?;
+    let open = || std::fs::File::open(location).map_err(|e| BeansError::TarExtractFailure {
+        src_file: location.to_string(),
+        target_dir: out_dir.to_string(),
+        error: e,
+        backtrace: Backtrace::capture()
+    });
+    let summary = match format {
+        PackageFormat::Tar => check_archive(location, out_dir)?,
+        PackageFormat::TarZstd => {
+            let decoder = zstd::stream::Decoder::new(open()?).map_err(|e| BeansError::TarExtractFailure {
+                src_file: location.to_string(),
+                target_dir: out_dir.to_string(),
+                error: e,
+                backtrace: Backtrace::capture()
+            })?;
+            check_tar(decoder, location, out_dir)?
+        },
+        PackageFormat::TarGzip => check_tar(flate2::read::MultiGzDecoder::new(open()?), location, out_dir)?,
+        PackageFormat::Zip => {
+            let mut archive = zip::ZipArchive::new(open()?).map_err(|e| BeansError::TarExtractFailure {
+                src_file: location.to_string(),
+                target_dir: out_dir.to_string(),
+                error: e.into(),
+                backtrace: Backtrace::capture()
+            })?;
+            check_zip(&mut archive, location, out_dir)?.0
+        },
+        PackageFormat::Directory => check_directory(location, out_dir)?.0,
+        PackageFormat::TarXz => return Ok(None)
+    };
+    Ok(Some(summary.ensure_safe(location, out_dir, max_size)?))
+}
+
+/// Decompress the `.tar.zst`, `.tar.gz` or `.tar.xz` file at `location` to a temporary file,
+/// and return the location of it. When `max_size` is set, decompressing stops once the tar file
+/// is larger than it (with `DECOMPRESS_OVERHEAD` added), so a package can't fill up the disk.
+fn decompress(location: &str, format: PackageFormat, max_size: Option<usize>) -> Result<String, BeansError>
+{
+    let tar_location = helper::get_tmp_file("data.tar".to_string());
+    let limit = max_size.map(|v| v as u64 + DECOMPRESS_OVERHEAD);
+    decompress_to(location, format, &tar_location, limit)?;
+    Ok(tar_location)
+}
+/// Decompress the package at `location` to `tar_location`, failing once more than `limit` bytes
+/// have been written. `tar_location` is deleted when it fails.
+fn decompress_to(location: &str, format: PackageFormat, tar_location: &str, limit: Option<u64>) -> Result<(), BeansError>
+{
+    let map_err = |e: std::io::Error| BeansError::TarExtractFailure {
+        src_file: location.to_string(),
+        target_dir: tar_location.to_string(),
+        error: e,
+        backtrace: Backtrace::capture()
+    };
+    let file = std::fs::File::open(location).map_err(map_err)?;
+    let mut out = LimitedWriter {
+        inner: std::fs::File::create_new(tar_location).map_err(map_err)?,
+        written: 0,
+        limit: limit.unwrap_or(u64::MAX)
+    };
+    let pb = progress_bar(file.metadata().map_err(map_err)?.len());
+    pb.set_message(format!("Decompressing {}", format));
+    let reader = pb.wrap_read(file);
+    let result = match format {
+        PackageFormat::TarZstd => zstd::stream::copy_decode(reader, &mut out),
+        PackageFormat::TarGzip => std::io::copy(&mut flate2::read::MultiGzDecoder::new(reader), &mut out).map(|_| ()),
+        PackageFormat::TarXz => lzma_rs::xz_decompress(&mut std::io::BufReader::new(reader), &mut out)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{:?}", e))),
+        _ => Ok(())
+    };
+    pb.finish();
+    if let Err(e) = result {
+        let written = out.written;
+        drop(out);
+        let _ = std::fs::remove_file(tar_location);
+        if let Some(limit) = limit.filter(|v| written > *v) {
+            return Err(BeansError::PackageSizeExceeded {
+                src_file: location.to_string(),
+                limit: limit as usize,
+                size: written
+            });
+        }
+        return Err(map_err(e));
+    }
+    Ok(())
+}
+
+/// Writer that fails once more than `limit` bytes would be written to it.
+struct LimitedWriter<W: std::io::Write>
+{
+    inner: W,
+    written: u64,
+    limit: u64
+}
+impl<W: std::io::Write> std::io::Write for LimitedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.written + buf.len() as u64 > self.limit {
+            self.written += buf.len() as u64;
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("larger than {} bytes", self.limit)));
+        }
+        let length = self.inner.write(buf)?;
+        self.written += length as u64;
+        Ok(length)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Why an entry in a package wasn't allowed to be extracted.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub enum RejectedEntryReason
+{
+    /// The location is absolute (e.g; `/etc/passwd`).
+    AbsolutePath,
+    /// The location has a `..` in it.
+    ParentDirectory,
+    /// Symlink that points to somewhere outside of the directory it's extracted to.
+    OutwardSymlink(String),
+    /// Hard link to a file outside of the directory it's extracted to.
+    OutwardHardLink(String),
+    /// Character or block device.
+    DeviceNode,
+    /// Anything else that isn't a file, directory or link (e.g; a FIFO).
+    UnsupportedType(String),
+    /// Would be written outside of the target directory through a symlink (that already exists,
+    /// or that is earlier in the package).
+    OutsideTarget,
+    /// Link to a file that beans-rs keeps its state in (see `is_state_file`).
+    StateFileLink(String)
+}
+impl Display for RejectedEntryReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RejectedEntryReason::AbsolutePath => write!(f, "absolute path"),
+            RejectedEntryReason::ParentDirectory => write!(f, "path contains \"..\""),
+            RejectedEntryReason::OutwardSymlink(target) => write!(f, "symlink to {} is outside of the target directory", target),
+            RejectedEntryReason::OutwardHardLink(target) => write!(f, "hard link to {} is outside of the target directory", target),
+            RejectedEntryReason::DeviceNode => write!(f, "device node"),
+            RejectedEntryReason::UnsupportedType(v) => write!(f, "unsupported entry type {}", v),
+            RejectedEntryReason::OutsideTarget => write!(f, "would be written outside of the target directory"),
+            RejectedEntryReason::StateFileLink(target) => write!(f, "link to {}, which only beans-rs can write to", target)
+        }
+    }
+}
+
+/// Entry in a package that was rejected by `check_archive`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RejectedEntry
+{
+    pub location: String,
+    pub reason: RejectedEntryReason
+}
+impl Display for RejectedEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.location, self.reason)
+    }
+}
+
+/// Result of `check_archive`.
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveSummary
+{
+    /// Location of every file (not directories), relative to the directory it's extracted to.
+    pub files: Vec<String>,
+    /// Amount of entries, including directories and links.
+    pub entry_count: usize,
+    /// Size of every entry added together.
+    pub total_size: u64,
+    pub rejected: Vec<RejectedEntry>,
+    /// Entries that aren't extracted, since they're files that beans-rs keeps its state in (see
+    /// `is_state_file`).
+    pub skipped: Vec<String>
+}
+
+impl ArchiveSummary
+{
+    /// Add an entry that was rejected.
+    fn reject(&mut self, location: String, reason: RejectedEntryReason)
+    {
+        let rejected = RejectedEntry {
+            location,
+            reason
+        };
+        warn!("[ArchiveSummary::reject] Rejected {}", rejected);
+        self.rejected.push(rejected);
+    }
+    /// Add an entry that isn't going to be extracted, since it's a state file.
+    fn skip(&mut self, location: String)
+    {
+        warn!("[ArchiveSummary::skip] Not extracting {}, since beans-rs keeps its state in it", location);
+        self.skipped.push(location);
+    }
+    /// Add an entry that is going to be extracted.
+    fn add(&mut self, location: String, size: u64, is_dir: bool)
+    {
+        self.entry_count += 1;
+        self.total_size += size;
+        if !is_dir {
+            self.files.push(location);
+        }
+    }
+
+    /// Fail when any entry was rejected, or when the entries are larger than `max_size`.
+    fn ensure_safe(self, src_file: &str, out_dir: &str, max_size: Option<usize>) -> Result<Self, BeansError>
+    {
+        if !self.rejected.is_empty() {
+            return Err(BeansError::UnsafePackage {
+                src_file: src_file.to_string(),
+                target_dir: out_dir.to_string(),
+                rejected: self.rejected
+            });
+        }
+        if let Some(limit) = max_size {
+            if self.total_size > limit as u64 {
+                return Err(BeansError::PackageSizeExceeded {
+                    src_file: src_file.to_string(),
+                    limit,
+                    size: self.total_size
+                });
+            }
+        }
+        debug!("[ArchiveSummary::ensure_safe] Extracting {} files ({} bytes) to {}", self.files.len(), self.total_size, out_dir);
+        Ok(self)
+    }
+
+    /// Set the mtime of `directories` (which change while extracting), then compare `written`
+    /// with the size of the package.
+    fn finish(&self, out_dir: &str, directories: &[(PathBuf, i64)], written: u64, max_size: Option<usize>) -> Result<(), BeansError>
+    {
+        for (location, mtime) in directories.iter().rev() {
+            let time = filetime::FileTime::from_unix_time(*mtime, 0);
+            if let Err(e) = filetime::set_file_mtime(location, time) {
+                warn!("[ArchiveSummary::finish] Failed to set mtime on {} ({:})", location.to_string_lossy(), e);
+            }
+        }
+        if written != self.total_size {
+            return Err(BeansError::ExtractSizeMismatch {
+                target_dir: out_dir.to_string(),
+                expected: self.total_size,
+                written
+            });
+        }
+        match max_size {
+            Some(v) if v as u64 != written
+            => warn!("[ArchiveSummary::finish] Extracted {} bytes, but the package should have {} bytes", written, v),
+            _ => info!("[ArchiveSummary::finish] Extracted {} bytes to {}", written, out_dir)
+        }
+        Ok(())
+    }
+}
+
+/// Check every entry in the tar file at `tar_location` without extracting anything. Entries
+/// that could write outside of the directory they're extracted to are added to `rejected`.
+pub fn check_archive(tar_location: &str, out_dir: &str) -> Result<ArchiveSummary, BeansError>
+{
+    let file = std::fs::File::open(tar_location).map_err(|e| BeansError::TarExtractFailure {
+        src_file: tar_location.to_string(),
+        target_dir: out_dir.to_string(),
+        error: e,
+        backtrace: Backtrace::capture()
+    })?;
+    check_tar(file, tar_location, out_dir)
+}
+/// Check every entry in the tar that is read from `reader`, like `check_archive` does.
+/// `src_file` is only used for errors.
+fn check_tar<R: Read>(reader: R, src_file: &str, out_dir: &str) -> Result<ArchiveSummary, BeansError>
+{
+    let map_err = |e: std::io::Error| BeansError::TarExtractFailure {
+        src_file: src_file.to_string(),
+        target_dir: out_dir.to_string(),
+        error: e,
+        backtrace: Backtrace::capture()
+    };
+    let mut archive = tar::Archive::new(reader);
+    let mut result = ArchiveSummary::default();
+    let mut links = ArchiveLinks::default();
+    for entry in archive.entries().map_err(map_err)? {
+        let entry = entry.map_err(map_err)?;
+        let path = entry.path().map_err(map_err)?.to_path_buf();
+        let location = path.to_string_lossy().trim_start_matches("./").to_string();
+        let entry_type = entry.header().entry_type();
+        let size = entry.header().size().map_err(map_err)?;
+        if is_state_file(&path) {
+            result.skip(location);
+            continue;
+        }
+
+        let reason = match check_path(&path) {
+            Some(v) => Some(v),
+            None => match entry_type {
+                tar::EntryType::Regular | tar::EntryType::Continuous | tar::EntryType::Directory => links.check(&path, None),
+                tar::EntryType::Symlink | tar::EntryType::Link => {
+                    let target = entry.link_name().map_err(map_err)?
+                        .map(|v| v.to_path_buf())
+                        .unwrap_or_default();
+                    links.check(&path, Some((&target, entry_type.is_symlink())))
+                },
+                tar::EntryType::Char | tar::EntryType::Block => Some(RejectedEntryReason::DeviceNode),
+                // handled by the tar crate, and never returned as an entry.
+                tar::EntryType::GNULongName | tar::EntryType::GNULongLink
+                | tar::EntryType::XHeader | tar::EntryType::XGlobalHeader => None,
+                v => Some(RejectedEntryReason::UnsupportedType(format!("{:?}", v)))
+            }
+        };
+        match reason {
+            Some(v) => result.reject(location, v),
+            None => result.add(location, size, entry_type.is_dir())
+        }
+    }
+    Ok(result)
+}
+
+/// Check the tar file at `tar_location` with `check_archive`, then extract it to `out_dir`.
+/// Nothing is extracted when any entry was rejected, or when the size of every file added
+/// together is larger than `max_size` (the `postsz` of the package, when it's known).
+///
+/// The modes and mtimes from the archive are kept. Once everything is extracted, the size of
+/// the files that were written is compared to the size in the archive (which fails when they're
+/// different), and to `max_size` (which only warns).
+///
+/// Returns the location of every file in the package, relative to `out_dir`.
+pub fn unpack_checked(tar_location: &str, out_dir: &str, max_size: Option<usize>) -> Result<Vec<String>, BeansError>
+{
+    let summary = check_archive(tar_location, out_dir)?
+        .ensure_safe(tar_location, out_dir, max_size)?;
+
+    let map_err = |e: std::io::Error| BeansError::TarExtractFailure {
+        src_file: tar_location.to_string(),
+        target_dir: out_dir.to_string(),
+        error: e,
+        backtrace: Backtrace::capture()
+    };
+    std::fs::create_dir_all(out_dir).map_err(map_err)?;
+    let file = std::fs::File::open(tar_location).map_err(map_err)?;
+    let mut archive = tar::Archive::new(file);
+    // so the result is the same no matter what the umask is, or when it was extracted.
+    archive.set_preserve_permissions(true);
+    archive.set_preserve_mtime(true);
+
+    let pb = progress_bar(summary.total_size);
+    let root = PathBuf::from(out_dir);
+    let mut directories: Vec<(PathBuf, i64)> = Vec::new();
+    let mut written: u64 = 0;
+    for (index, entry) in archive.entries().map_err(map_err)?.enumerate() {
+        let mut entry = entry.map_err(map_err)?;
+        let path = entry.path().map_err(map_err)?.to_path_buf();
+        if is_state_file(&path) {
+            continue;
+        }
+        pb.set_message(format!("Extracting {}/{}", index + 1, summary.entry_count));
+        debug!("[extract::unpack_checked] {}", path.to_string_lossy());
+        // `unpack_in` also refuses to write outside of `out_dir` (even through symlinks that
+        // already exist), and returns false when it skipped the entry because of that.
+        if !entry.unpack_in(out_dir).map_err(map_err)? {
+            pb.abandon();
+            return Err(BeansError::UnsafePackage {
+                src_file: tar_location.to_string(),
+                target_dir: out_dir.to_string(),
+                rejected: vec![RejectedEntry {
+                    location: path.to_string_lossy().to_string(),
+                    reason: RejectedEntryReason::OutsideTarget
+                }]
+            });
+        }
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_dir() {
+            directories.push((root.join(&path), entry.header().mtime().unwrap_or(0) as i64));
+        } else if entry_type.is_file() || entry_type == tar::EntryType::Continuous {
+            written += std::fs::symlink_metadata(root.join(&path)).map_err(map_err)?.len();
+        }
+        pb.inc(entry.header().size().unwrap_or(0));
+    }
+    pb.finish_with_message(format!("Extracted {} files", summary.files.len()));
+    summary.finish(out_dir, &directories, written, max_size)?;
+    Ok(summary.files)
+}
+
+/// Check every entry in the `.zip` file at `location` like `check_archive` does, then extract it
+/// to `out_dir`. The same checks as `unpack_checked` are done once it's extracted.
+pub fn unpack_zip(location: &str, out_dir: &str, max_size: Option<usize>) -> Result<Vec<String>, BeansError>
+{
+    let map_err = |e: std::io::Error| BeansError::TarExtractFailure {
+        src_file: location.to_string(),
+        target_dir: out_dir.to_string(),
+        error: e,
+        backtrace: Backtrace::capture()
+    };
+    let zip_err = |e: zip::result::ZipError| map_err(e.into());
+    let file = std::fs::File::open(location).map_err(map_err)?;
+    let mut archive = zip::ZipArchive::new(file).map_err(zip_err)?;
+    let (summary, link_targets) = check_zip(&mut archive, location, out_dir)?;
+    let summary = summary.ensure_safe(location, out_dir, max_size)?;
+
+    let root = PathBuf::from(out_dir);
+    std::fs::create_dir_all(&root).map_err(map_err)?;
+    let canonical_root = root.canonicalize().map_err(map_err)?;
+    let pb = progress_bar(summary.total_size);
+    let mut directories: Vec<(PathBuf, i64)> = Vec::new();
+    let mut written: u64 = 0;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(zip_err)?;
+        if is_state_file(Path::new(entry.name())) {
+            continue;
+        }
+        let target = root.join(entry.name());
+        pb.set_message(format!("Extracting {}/{}", index + 1, summary.entry_count));
+        debug!("[extract::unpack_zip] {}", entry.name());
+        let mtime = entry.last_modified().and_then(zip_mtime);
+        if entry.is_dir() {
+            create_dir_inside_root(&canonical_root, &target, location, out_dir)?;
+            set_mode(&target, entry.unix_mode()).map_err(map_err)?;
+            directories.push((target, mtime.unwrap_or(0)));
+            continue;
+        }
+        create_dir_inside_root(&canonical_root, target.parent().unwrap_or(&root), location, out_dir)?;
+        remove_existing(&target).map_err(map_err)?;
+        if let Some(link) = link_targets.get(&index) {
+            create_symlink(link, &target).map_err(map_err)?;
+            continue;
+        }
+        let mut out = std::fs::File::create(&target).map_err(map_err)?;
+        written += std::io::copy(&mut entry, &mut out).map_err(map_err)?;
+        drop(out);
+        set_mode(&target, entry.unix_mode()).map_err(map_err)?;
+        if let Some(v) = mtime {
+            filetime::set_file_mtime(&target, filetime::FileTime::from_unix_time(v, 0)).map_err(map_err)?;
+        }
+        pb.inc(entry.size());
+    }
+    pb.finish_with_message(format!("Extracted {} files", summary.files.len()));
+    summary.finish(out_dir, &directories, written, max_size)?;
+    Ok(summary.files)
+}
+
+/// Check every entry in a `.zip` file like `check_archive` does. Also returns the targets of
+/// the symlinks in it, by the index of the entry.
+fn check_zip(archive: &mut zip::ZipArchive<std::fs::File>, location: &str, out_dir: &str)
+    -> Result<(ArchiveSummary, HashMap<usize, PathBuf>), BeansError>
+{
+    let map_err = |e: std::io::Error| BeansError::TarExtractFailure {
+        src_file: location.to_string(),
+        target_dir: out_dir.to_string(),
+        error: e,
+        backtrace: Backtrace::capture()
+    };
+    let zip_err = |e: zip::result::ZipError| map_err(e.into());
+    let mut summary = ArchiveSummary::default();
+    let mut links = ArchiveLinks::default();
+    let mut link_targets: HashMap<usize, PathBuf> = HashMap::new();
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(zip_err)?;
+        let path = PathBuf::from(entry.name());
+        let entry_location = entry.name().trim_start_matches("./").to_string();
+        if is_state_file(&path) {
+            summary.skip(entry_location);
+            continue;
+        }
+        let reason = match check_path(&path) {
+            Some(v) => Some(v),
+            None if entry.is_symlink() => {
+                let mut target = String::new();
+                entry.read_to_string(&mut target).map_err(map_err)?;
+                let target = PathBuf::from(target);
+                let reason = links.check(&path, Some((&target, true)));
+                link_targets.insert(index, target);
+                reason
+            },
+            None => match entry.unix_mode().map(|v| v & 0o170000) {
+                Some(0o020000) | Some(0o060000) => Some(RejectedEntryReason::DeviceNode),
+                Some(v) if v != 0 && v != 0o040000 && v != 0o100000
+                => Some(RejectedEntryReason::UnsupportedType(format!("mode {:06o}", v))),
+                _ => links.check(&path, None)
+            }
+        };
+        let size = match entry.is_symlink() || entry.is_dir() {
+            true => 0,
+            false => entry.size()
+        };
+        match reason {
+            Some(v) => summary.reject(entry_location, v),
+            None => summary.add(entry_location, size, entry.is_dir())
+        }
+    }
+    Ok((summary, link_targets))
+}
+
+/// Copy a package that has already been extracted at `location` to `out_dir`. `location` can
+/// have the mod folder in it (like packages do), or be the mod folder. The same checks as
+/// `unpack_checked` are done before and after copying.
+pub fn copy_directory(location: &str, out_dir: &str, max_size: Option<usize>) -> Result<Vec<String>, BeansError>
+{
+    let map_err = |e: std::io::Error| BeansError::TarExtractFailure {
+        src_file: location.to_string(),
+        target_dir: out_dir.to_string(),
+        error: e,
+        backtrace: Backtrace::capture()
+    };
+    let (summary, prefix, entries) = check_directory(location, out_dir)?;
+    let root = PathBuf::from(out_dir);
+    let summary = summary.ensure_safe(location, out_dir, max_size)?;
+
+    std::fs::create_dir_all(&root).map_err(map_err)?;
+    let canonical_root = root.canonicalize().map_err(map_err)?;
+    create_dir_inside_root(&canonical_root, &root.join(&prefix), location, out_dir)?;
+    let pb = progress_bar(summary.total_size);
+    let mut directories: Vec<(PathBuf, i64)> = Vec::new();
+    let mut written: u64 = 0;
+    for (index, (path, relative, kind)) in entries.iter().enumerate() {
+        let target = root.join(relative);
+        pb.set_message(format!("Copying {}/{}", index + 1, summary.entry_count));
+        debug!("[extract::copy_directory] {}", relative.to_string_lossy());
+        let metadata = std::fs::symlink_metadata(path).map_err(map_err)?;
+        let mtime = filetime::FileTime::from_last_modification_time(&metadata);
+        if let CopyEntry::Directory = kind {
+            create_dir_inside_root(&canonical_root, &target, location, out_dir)?;
+            std::fs::set_permissions(&target, metadata.permissions()).map_err(map_err)?;
+            directories.push((target, mtime.unix_seconds()));
+            continue;
+        }
+        create_dir_inside_root(&canonical_root, target.parent().unwrap_or(&root), location, out_dir)?;
+        remove_existing(&target).map_err(map_err)?;
+        match kind {
+            CopyEntry::Symlink(link) => create_symlink(link, &target).map_err(map_err)?,
+            _ => {
+                // also copies the permissions.
+                let size = std::fs::copy(path, &target).map_err(map_err)?;
+                filetime::set_file_mtime(&target, mtime).map_err(map_err)?;
+                written += size;
+                pb.inc(size);
+            }
+        }
+    }
+    pb.finish_with_message(format!("Copied {} files", summary.files.len()));
+    summary.finish(out_dir, &directories, written, max_size)?;
+    Ok(summary.files)
+}
+
+/// Entry in a directory that is being copied by `copy_directory`.
+enum CopyEntry {
+    Directory,
+    File,
+    Symlink(PathBuf)
+}
+/// Entries to copy with `copy_directory`; (location, location relative to `out_dir`, type)
+type CopyEntries = Vec<(PathBuf, PathBuf, CopyEntry)>;
+/// Check every entry in a package that has already been extracted at `location` like
+/// `check_archive` does. Also returns where `location` is in `out_dir`, and the entries to copy.
+fn check_directory(location: &str, out_dir: &str)
+    -> Result<(ArchiveSummary, PathBuf, CopyEntries), BeansError>
+{
+    let map_err = |e: std::io::Error| BeansError::TarExtractFailure {
+        src_file: location.to_string(),
+        target_dir: out_dir.to_string(),
+        error: e,
+        backtrace: Backtrace::capture()
+    };
+    let sourcemod_name = crate::appvar::parse().mod_info.sourcemod_name;
+    let source = PathBuf::from(location);
+    // where `source` is in `out_dir`.
+    let prefix = if source.join(&sourcemod_name).is_dir() {
+        PathBuf::new()
+    } else if source.join("gameinfo.txt").is_file() {
+        PathBuf::from(&sourcemod_name)
+    } else {
+        return Err(BeansError::PackageSourceInvalid {
+            location: location.to_string(),
+            reason: format!("it doesn't have a {} folder or a gameinfo.txt in it", sourcemod_name)
+        });
+    };
+    let root = PathBuf::from(out_dir);
+    if let (Ok(a), Ok(b)) = (source.canonicalize(), root.join(&prefix).canonicalize()) {
+        if a == b {
+            return Err(BeansError::PackageSourceInvalid {
+                location: location.to_string(),
+                reason: "it's the folder that it would be installed to".to_string()
+            });
+        }
+    }
+    let mut summary = ArchiveSummary::default();
+    let mut entries: CopyEntries = Vec::new();
+    let mut pending: Vec<PathBuf> = vec![source.clone()];
+    while let Some(directory) = pending.pop() {
+        let mut children: Vec<PathBuf> = std::fs::read_dir(&directory).map_err(map_err)?
+            .map(|v| v.map(|e| e.path()))
+            .collect::<Result<Vec<PathBuf>, std::io::Error>>()
+            .map_err(map_err)?;
+        // so the files are always copied in the same order.
+        children.sort();
+        for path in children.into_iter() {
+            let relative = prefix.join(path.strip_prefix(&source).unwrap_or(&path));
+            let entry_location = relative.to_string_lossy().to_string();
+            if is_state_file(&relative) {
+                summary.skip(entry_location);
+                continue;
+            }
+            let metadata = std::fs::symlink_metadata(&path).map_err(map_err)?;
+            let file_type = metadata.file_type();
+            if file_type.is_symlink() {
+                let target = std::fs::read_link(&path).map_err(map_err)?;
+                match check_link(&relative, &target, true) {
+                    Some(reason) => summary.reject(entry_location, reason),
+                    None => {
+                        summary.add(entry_location, 0, false);
+                        entries.push((path, relative, CopyEntry::Symlink(target)));
+                    }
+                }
+            } else if file_type.is_dir() {
+                summary.add(entry_location, 0, true);
+                entries.push((path.clone(), relative, CopyEntry::Directory));
+                pending.push(path);
+            } else if file_type.is_file() {
+                summary.add(entry_location, metadata.len(), false);
+                entries.push((path, relative, CopyEntry::File));
+            } else {
+                summary.reject(entry_location, special_file_reason(&file_type));
+            }
+        }
+    }
+    Ok((summary, prefix, entries))
+}
+
+/// Check if `path` is (or is in) a file that beans-rs keeps its state in, like `.adastral` or
+/// `.adastral_addons`. They're never extracted from packages, so a package can't change the
+/// installed version, add-ons or backups.
+pub fn is_state_file(path: &Path) -> bool
+{
+    path.components().any(|c| match c {
+        Component::Normal(v) => v.to_string_lossy().to_lowercase().starts_with(STATE_FILE_PREFIX),
+        _ => false
+    })
+}
+
+/// Check that `path` is relative and doesn't have `..` in it.
+fn check_path(path: &Path) -> Option<RejectedEntryReason>
+{
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => return Some(RejectedEntryReason::AbsolutePath),
+            Component::ParentDir => return Some(RejectedEntryReason::ParentDirectory),
+            Component::CurDir | Component::Normal(_) => {}
+        }
+    }
+    None
+}
+
+/// Check that the relative `path` stays inside of the directory it's relative to, after `..` is
+/// applied.
+fn is_inside(path: &Path) -> bool
+{
+    let mut depth: usize = 0;
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => return false,
+            Component::ParentDir => {
+                if depth == 0 {
+                    return false;
+                }
+                depth -= 1;
+            },
+            Component::CurDir => {},
+            Component::Normal(_) => depth += 1
+        }
+    }
+    true
+}
+
+/// Check that the link at `path` to `target` doesn't point outside of the directory it's
+/// extracted to. Hard links are relative to that directory, and symlinks are relative to the
+/// directory they're in.
+fn check_link(path: &Path, target: &Path, is_symlink: bool) -> Option<RejectedEntryReason>
+{
+    let relative_to = match is_symlink {
+        true => path.parent().map(|v| v.to_path_buf()).unwrap_or_default(),
+        false => PathBuf::new()
+    };
+    let target_text = target.to_string_lossy().to_string();
+    match is_inside(&relative_to.join(target)) {
+        true => None,
+        false if is_symlink => Some(RejectedEntryReason::OutwardSymlink(target_text)),
+        false => Some(RejectedEntryReason::OutwardHardLink(target_text))
+    }
+}
+
+/// Create the folder at `directory` (and any parents that are missing). The closest parent that
+/// exists is checked first, so nothing is created when it's outside of `canonical_root` once
+/// symlinks that already exist are followed.
+fn create_dir_inside_root(canonical_root: &Path, directory: &Path, src_file: &str, out_dir: &str) -> Result<(), BeansError>
+{
+    let mut missing: Vec<&Path> = Vec::new();
+    let mut existing = directory;
+    while existing.symlink_metadata().is_err() {
+        missing.push(existing);
+        match existing.parent() {
+            Some(v) => existing = v,
+            None => break
+        }
+    }
+    match existing.canonicalize() {
+        Ok(v) if v.starts_with(canonical_root) => {},
+        _ => return Err(BeansError::UnsafePackage {
+            src_file: src_file.to_string(),
+            target_dir: out_dir.to_string(),
+            rejected: vec![RejectedEntry {
+                location: directory.to_string_lossy().to_string(),
+                reason: RejectedEntryReason::OutsideTarget
+            }]
+        })
+    }
+    for location in missing.into_iter().rev() {
+        if let Err(e) = std::fs::create_dir(location) {
+            return Err(BeansError::TarExtractFailure {
+                src_file: src_file.to_string(),
+                target_dir: out_dir.to_string(),
+                error: e,
+                backtrace: Backtrace::capture()
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Symlinks that were earlier in a package. Entries after them can be extracted through them
+/// (like `a -> ..` and then `a/b`), so they're followed when checking where an entry ends up.
+#[derive(Debug, Default)]
+struct ArchiveLinks
+{
+    /// Target of every symlink, by where the symlink ends up (relative to the root).
+    links: HashMap<PathBuf, PathBuf>
+}
+impl ArchiveLinks
+{
+    /// Check the entry at `path` (which has already been checked with `check_path`) after
+    /// following the symlinks in its parents. `link` is the target of the entry, and `true` when
+    /// it's a symlink. Symlinks that are allowed are added, so the entries after it are checked
+    /// with it.
+    fn check(&mut self, path: &Path, link: Option<(&Path, bool)>) -> Option<RejectedEntryReason>
+    {
+        let location = match self.resolve(path, false) {
+            Some(v) => v,
+            None => return Some(RejectedEntryReason::OutsideTarget)
+        };
+        let (target, is_symlink) = match link {
+            Some(v) => v,
+            None => return None
+        };
+        if let Some(reason) = check_link(&location, target, is_symlink) {
+            return Some(reason);
+        }
+        let relative_to = match is_symlink {
+            true => location.parent().map(|v| v.to_path_buf()).unwrap_or_default(),
+            false => PathBuf::new()
+        };
+        let target_text = target.to_string_lossy().to_string();
+        match self.resolve(&relative_to.join(target), true) {
+            None => return Some(match is_symlink {
+                true => RejectedEntryReason::OutwardSymlink(target_text),
+                false => RejectedEntryReason::OutwardHardLink(target_text)
+            }),
+            Some(v) if is_state_file(&v) => return Some(RejectedEntryReason::StateFileLink(target_text)),
+            Some(_) => {}
+        }
+        if is_symlink {
+            self.links.insert(location, target.to_path_buf());
+        }
+        None
+    }
+
+    /// Where the relative `path` ends up once the symlinks in it are followed (including the last
+    /// component when `follow_last` is `true`). `None` when it's outside of the root, or when
+    /// more than `MAX_LINK_HOPS` symlinks were followed.
+    fn resolve(&self, path: &Path, follow_last: bool) -> Option<PathBuf>
+    {
+        let mut pending: VecDeque<Component> = path.components().collect();
+        let mut result = PathBuf::new();
+        let mut hops: usize = 0;
+        while let Some(component) = pending.pop_front() {
+            match component {
+                Component::Prefix(_) | Component::RootDir => return None,
+                Component::CurDir => {},
+                Component::ParentDir => {
+                    if !result.pop() {
+                        return None;
+                    }
+                },
+                Component::Normal(v) => {
+                    result.push(v);
+                    if pending.is_empty() && !follow_last {
+                        break;
+                    }
+                    if let Some(target) = self.links.get(&result) {
+                        hops += 1;
+                        if hops > MAX_LINK_HOPS {
+                            return None;
+                        }
+                        // symlinks are relative to the directory they're in.
+                        result.pop();
+                        for v in target.components().rev() {
+                            pending.push_front(v);
+                        }
+                    }
+                }
+            }
+        }
+        Some(result)
+    }
+}
+
+/// Remove the file or symlink at `target` when there is one, so it isn't written through.
+fn remove_existing(target: &Path) -> Result<(), std::io::Error>
+{
+    match std::fs::symlink_metadata(target) {
+        Ok(m) if !m.is_dir() => std::fs::remove_file(target),
+        _ => Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn create_symlink(link: &Path, target: &Path) -> Result<(), std::io::Error>
+{
+    std::os::unix::fs::symlink(link, target)
+}
+#[cfg(not(unix))]
+fn create_symlink(link: &Path, target: &Path) -> Result<(), std::io::Error>
+{
+    warn!("[extract::create_symlink] Not creating {} -> {}, since symlinks are only created on Linux", target.to_string_lossy(), link.to_string_lossy());
+    Ok(())
+}
+
+/// Set the permissions of `target` to `mode` (from a `.zip`), when it's set.
+#[cfg(unix)]
+fn set_mode(target: &Path, mode: Option<u32>) -> Result<(), std::io::Error>
+{
+    use std::os::unix::fs::PermissionsExt;
+    match mode.map(|v| v & 0o7777) {
+        Some(v) if v != 0 => std::fs::set_permissions(target, std::fs::Permissions::from_mode(v)),
+        _ => Ok(())
+    }
+}
+#[cfg(not(unix))]
+fn set_mode(_target: &Path, _mode: Option<u32>) -> Result<(), std::io::Error>
+{
+    Ok(())
+}
+
+#[cfg(unix)]
+fn special_file_reason(file_type: &std::fs::FileType) -> RejectedEntryReason
+{
+    use std::os::unix::fs::FileTypeExt;
+    match file_type.is_block_device() || file_type.is_char_device() {
+        true => RejectedEntryReason::DeviceNode,
+        false => RejectedEntryReason::UnsupportedType(format!("{:?}", file_type))
+    }
+}
+#[cfg(not(unix))]
+fn special_file_reason(file_type: &std::fs::FileType) -> RejectedEntryReason
+{
+    RejectedEntryReason::UnsupportedType(format!("{:?}", file_type))
+}
+
+/// Convert the mtime of an entry in a `.zip` to a unix timestamp. `.zip` files don't have a
+/// timezone, so it's treated as UTC.
+fn zip_mtime(time: zip::DateTime) -> Option<i64>
+{
+    chrono::NaiveDate::from_ymd_opt(time.year() as i32, time.month() as u32, time.day() as u32)?
+        .and_hms_opt(time.hour() as u32, time.minute() as u32, time.second() as u32)
+        .map(|v| v.and_utc().timestamp())
+}
+
+fn progress_bar(length: u64) -> ProgressBar
+{
+    let pb = ProgressBar::new(length);
+    pb.set_style(ProgressStyle::with_template("{spinner:.green} {msg} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
+        .unwrap()
+        .progress_chars("#>-"));
+    pb
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use crate::helper::test_dir::TestDir;
+
+    fn text(location: &Path) -> String
+    {
+        location.to_string_lossy().to_string()
+    }
+
+    /// Create a tar file with `entries` (location, type, content or link target). The names are
+    /// written to the header directly, since `tar::Builder` refuses to write the unsafe ones.
+    fn tar_with(entries: &[(&str, tar::EntryType, &str)]) -> Vec<u8>
+    {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, kind, content) in entries.iter() {
+            let mut header = tar::Header::new_old();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_entry_type(*kind);
+            header.set_mode(0o644);
+            header.set_mtime(1700000000);
+            let data: &[u8] = match kind {
+                tar::EntryType::Symlink | tar::EntryType::Link => {
+                    header.as_old_mut().linkname[..content.len()].copy_from_slice(content.as_bytes());
+                    &[]
+                },
+                tar::EntryType::Regular => content.as_bytes(),
+                _ => &[]
+            };
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            builder.append(&header, data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+    fn check(entries: &[(&str, tar::EntryType, &str)]) -> ArchiveSummary
+    {
+        check_tar(Cursor::new(tar_with(entries)), "test.tar", "out").unwrap()
+    }
+    fn reasons(summary: &ArchiveSummary) -> Vec<(String, RejectedEntryReason)>
+    {
+        summary.rejected.iter().map(|v| (v.location.clone(), v.reason.clone())).collect()
+    }
+
+    #[test]
+    fn accepts_normal_package()
+    {
+        let dir = TestDir::new("extract-normal");
+        let tar_location = dir.join("p.tar");
+        std::fs::write(&tar_location, tar_with(&[
+            ("open_fortress/", tar::EntryType::Directory, ""),
+            ("open_fortress/gameinfo.txt", tar::EntryType::Regular, "hello"),
+            ("open_fortress/bin/server.so", tar::EntryType::Regular, "world!"),
+            ("open_fortress/bin/server_srv.so", tar::EntryType::Symlink, "server.so"),
+            ("open_fortress/bin/hard.so", tar::EntryType::Link, "open_fortress/bin/server.so")
+        ])).unwrap();
+        let out = dir.join("out");
+        let files = unpack_checked(&text(&tar_location), &text(&out), Some(11)).unwrap();
+        assert_eq!(files.len(), 4);
+        assert_eq!(std::fs::read_to_string(out.join("open_fortress/bin/server_srv.so")).unwrap(), "world!");
+    }
+
+    #[test]
+    fn skips_state_files()
+    {
+        let dir = TestDir::new("extract-state_files");
+        let tar_location = dir.join("p.tar");
+        std::fs::write(&tar_location, tar_with(&[
+            ("open_fortress/gameinfo.txt", tar::EntryType::Regular, "hello"),
+            ("open_fortress/.adastral", tar::EntryType::Regular, "{\"version\":\"1\"}"),
+            ("open_fortress/.ADASTRAL_addons", tar::EntryType::Regular, "{}"),
+            ("open_fortress/.adastral_addons_backup/", tar::EntryType::Directory, ""),
+            ("open_fortress/.adastral_addons_backup/hd/a.vtf", tar::EntryType::Regular, "x")
+        ])).unwrap();
+        let out = dir.join("out");
+        std::fs::create_dir_all(out.join("open_fortress")).unwrap();
+        std::fs::write(out.join("open_fortress/.adastral"), "{\"version\":\"2\"}").unwrap();
+        let files = unpack_checked(&text(&tar_location), &text(&out), Some(5)).unwrap();
+        assert_eq!(files, vec!["open_fortress/gameinfo.txt"]);
+        assert_eq!(std::fs::read_to_string(out.join("open_fortress/.adastral")).unwrap(), "{\"version\":\"2\"}");
+        assert!(!out.join("open_fortress/.ADASTRAL_addons").exists());
+        assert!(!out.join("open_fortress/.adastral_addons_backup").exists());
+    }
+
+    #[test]
+    fn rejects_link_to_state_file()
+    {
+        let summary = check(&[
+            ("open_fortress/cfg", tar::EntryType::Symlink, "."),
+            ("open_fortress/version", tar::EntryType::Symlink, "cfg/.adastral"),
+            ("open_fortress/hard", tar::EntryType::Link, "open_fortress/.adastral")
+        ]);
+        assert_eq!(reasons(&summary), vec![
+            ("open_fortress/version".to_string(), RejectedEntryReason::StateFileLink("cfg/.adastral".to_string())),
+            ("open_fortress/hard".to_string(), RejectedEntryReason::StateFileLink("open_fortress/.adastral".to_string()))
+        ]);
+    }
+
+    #[test]
+    fn rejects_absolute_path()
+    {
+        let summary = check(&[("/etc/passwd", tar::EntryType::Regular, "x")]);
+        assert_eq!(reasons(&summary), vec![("/etc/passwd".to_string(), RejectedEntryReason::AbsolutePath)]);
+    }
+
+    #[test]
+    fn rejects_parent_directory()
+    {
+        let summary = check(&[("open_fortress/../../x", tar::EntryType::Regular, "x")]);
+        assert_eq!(reasons(&summary), vec![("open_fortress/../../x".to_string(), RejectedEntryReason::ParentDirectory)]);
+    }
+
+    #[test]
+    fn rejects_outward_links()
+    {
+        let summary = check(&[
+            ("open_fortress/l", tar::EntryType::Symlink, "../../etc"),
+            ("open_fortress/abs", tar::EntryType::Symlink, "/etc"),
+            ("open_fortress/h", tar::EntryType::Link, "../etc/passwd"),
+            ("open_fortress/ok", tar::EntryType::Symlink, "../open_fortress")
+        ]);
+        assert_eq!(reasons(&summary), vec![
+            ("open_fortress/l".to_string(), RejectedEntryReason::OutwardSymlink("../../etc".to_string())),
+            ("open_fortress/abs".to_string(), RejectedEntryReason::OutwardSymlink("/etc".to_string())),
+            ("open_fortress/h".to_string(), RejectedEntryReason::OutwardHardLink("../etc/passwd".to_string()))
+        ]);
+    }
+
+    #[test]
+    fn rejects_symlink_chain()
+    {
+        // each link is fine on its own, but `d/l/l2` is `l2` in the root, which points above it.
+        let summary = check(&[
+            ("d/l", tar::EntryType::Symlink, ".."),
+            ("d/l/l2", tar::EntryType::Symlink, ".."),
+            ("d/l/l2/escaped", tar::EntryType::Regular, "x"),
+            ("d/l/file", tar::EntryType::Regular, "x"),
+            ("h", tar::EntryType::Link, "d/l/../etc/passwd")
+        ]);
+        assert_eq!(reasons(&summary), vec![
+            ("d/l/l2".to_string(), RejectedEntryReason::OutwardSymlink("..".to_string())),
+            // `d/l/l2` was rejected, so it isn't followed.
+            ("h".to_string(), RejectedEntryReason::OutwardHardLink("d/l/../etc/passwd".to_string()))
+        ]);
+        assert_eq!(summary.files, vec!["d/l", "d/l/l2/escaped", "d/l/file"]);
+    }
+
+    #[test]
+    fn rejects_symlink_loop()
+    {
+        let summary = check(&[
+            ("a", tar::EntryType::Symlink, "b"),
+            ("b", tar::EntryType::Symlink, "a"),
+            ("a/x", tar::EntryType::Regular, "x")
+        ]);
+        assert_eq!(reasons(&summary), vec![("a/x".to_string(), RejectedEntryReason::OutsideTarget)]);
+    }
+
+    #[test]
+    fn symlink_chain_is_not_extracted()
+    {
+        let dir = TestDir::new("extract-chain");
+        let tar_location = dir.join("p.tar");
+        std::fs::write(&tar_location, tar_with(&[
+            ("d/l", tar::EntryType::Symlink, ".."),
+            ("d/l/l2", tar::EntryType::Symlink, "..")
+        ])).unwrap();
+        let out = dir.join("sourcemods").join("out");
+        std::fs::create_dir_all(&out).unwrap();
+        let err = unpack_checked(&text(&tar_location), &text(&out), None).unwrap_err();
+        assert!(matches!(err, BeansError::UnsafePackage { .. }));
+        // nothing is extracted when any entry is rejected.
+        assert_eq!(std::fs::read_dir(&out).unwrap().count(), 0);
+        assert!(std::fs::symlink_metadata(dir.join("sourcemods").join("l2")).is_err());
+    }
+
+    #[test]
+    fn rejects_device_node()
+    {
+        let summary = check(&[
+            ("open_fortress/tty", tar::EntryType::Char, ""),
+            ("open_fortress/sda", tar::EntryType::Block, ""),
+            ("open_fortress/fifo", tar::EntryType::Fifo, "")
+        ]);
+        assert_eq!(reasons(&summary), vec![
+            ("open_fortress/tty".to_string(), RejectedEntryReason::DeviceNode),
+            ("open_fortress/sda".to_string(), RejectedEntryReason::DeviceNode),
+            ("open_fortress/fifo".to_string(), RejectedEntryReason::UnsupportedType("Fifo".to_string()))
+        ]);
+    }
+
+    #[test]
+    fn rejects_oversize()
+    {
+        let dir = TestDir::new("extract-oversize");
+        let tar_location = dir.join("p.tar");
+        std::fs::write(&tar_location, tar_with(&[("open_fortress/big", tar::EntryType::Regular, "0123456789")])).unwrap();
+        let out = dir.join("out");
+        let err = unpack_checked(&text(&tar_location), &text(&out), Some(9)).unwrap_err();
+        assert!(matches!(err, BeansError::PackageSizeExceeded { limit: 9, size: 10, .. }));
+        assert!(!out.join("open_fortress/big").exists());
+    }
+
+    #[test]
+    fn decompress_stops_at_limit()
+    {
+        let dir = TestDir::new("extract-bomb");
+        let zeros = "\0".repeat(1024 * 1024);
+        let compressed = zstd::stream::encode_all(Cursor::new(tar_with(&[("open_fortress/zeros", tar::EntryType::Regular, &zeros)])), 3).unwrap();
+        let location = dir.join("p.tar.zst");
+        std::fs::write(&location, compressed).unwrap();
+
+        let tar_location = dir.join("limited.tar");
+        let err = decompress_to(&text(&location), PackageFormat::TarZstd, &text(&tar_location), Some(64 * 1024)).unwrap_err();
+        assert!(matches!(err, BeansError::PackageSizeExceeded { limit: 65536, .. }));
+        assert!(!tar_location.exists());
+
+        let tar_location = dir.join("unlimited.tar");
+        decompress_to(&text(&location), PackageFormat::TarZstd, &text(&tar_location), None).unwrap();
+        assert!(std::fs::metadata(&tar_location).unwrap().len() > 1024 * 1024);
+    }
+
+    fn zip_with(location: &Path, entries: &[(&str, Option<&str>)])
+    {
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(location).unwrap());
+        for (name, link) in entries.iter() {
+            match link {
+                Some(target) => writer.add_symlink(*name, *target, options).unwrap(),
+                None => {
+                    writer.start_file(*name, options).unwrap();
+                    writer.write_all(b"x").unwrap();
+                }
+            }
+        }
+        writer.finish().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn zip_rejects_symlink_chain()
+    {
+        let dir = TestDir::new("extract-zip-chain");
+        let location = dir.join("p.zip");
+        zip_with(&location, &[("d/l", Some("..")), ("d/l/l2", Some("..")), ("d/l/l2/x/file", None)]);
+        let out = dir.join("sourcemods").join("out");
+        std::fs::create_dir_all(&out).unwrap();
+        let err = unpack_zip(&text(&location), &text(&out), None).unwrap_err();
+        assert!(matches!(err, BeansError::UnsafePackage { .. }));
+        assert_eq!(std::fs::read_dir(&out).unwrap().count(), 0);
+        assert!(std::fs::symlink_metadata(dir.join("sourcemods").join("l2")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn zip_does_not_create_folders_through_existing_symlink()
+    {
+        let dir = TestDir::new("extract-zip-existing");
+        let outside = dir.join("outside");
+        let out = dir.join("out");
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::create_dir_all(&out).unwrap();
+        std::os::unix::fs::symlink(&outside, out.join("esc")).unwrap();
+
+        let location = dir.join("p.zip");
+        zip_with(&location, &[("esc/new/deeper/file", None)]);
+        let err = unpack_zip(&text(&location), &text(&out), None).unwrap_err();
+        assert!(matches!(err, BeansError::UnsafePackage { .. }));
+        assert_eq!(std::fs::read_dir(&outside).unwrap().count(), 0);
+    }
+}
\ No newline at end of file
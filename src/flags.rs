@@ -6,14 +6,25 @@ bitflags!
     {
         // debug mode, print full errors and other debug messages to console.
         const DEBUG_MODE = 0x01;
-        // run from beans script
+        // run from beans script, or non-interactively (e.g; CI or cron). no prompts or dialogs
+        // will be shown, and the process will exit instead of pausing.
         const AUTOMATED = 0x02;
         // use the standard CLI wizard
         const WIZARD = 0x04;
         // enable experimental GUI for the wizard
         const WIZARD_GUI = 0x08;
         // please enable this flag when this is being used by a standalone application
-        const STANDALONE_APP = 0x16;
+        const STANDALONE_APP = 0x10;
+        // write machine-readable JSON to stdout instead of text, and write log messages to stderr.
+        const JSON_OUTPUT = 0x20;
+        // wait for the game or dedicated server to exit instead of failing when it's using the mod.
+        const WAIT_FOR_EXIT = 0x40;
+        // print what workflows would do instead of doing it. nothing is downloaded (except for
+        // versions.json) and nothing is written to disk.
+        const DRY_RUN = 0x80;
+        // allow running as root when the install is owned by someone else. the files that are
+        // written are given back to the owner afterwards (see `InstallOwner`).
+        const ALLOW_ROOT = 0x100;
     }
 }
 pub static mut LAUNCH_FLAGS: u32 = 0x00;
@@ -60,4 +71,38 @@ pub fn remove_flag(flag: LaunchFlag)
 pub fn debug_mode() -> bool
 {
     has_flag(LaunchFlag::DEBUG_MODE)
+}
+
+/// check if `LaunchFlag::AUTOMATED` is set. When this is true, nothing should wait for user input.
+pub fn automated() -> bool
+{
+    has_flag(LaunchFlag::AUTOMATED)
+}
+
+/// check if `LaunchFlag::JSON_OUTPUT` is set. When this is true, only JSON should be written to
+/// stdout.
+pub fn json_output() -> bool
+{
+    has_flag(LaunchFlag::JSON_OUTPUT)
+}
+
+/// check if `LaunchFlag::WAIT_FOR_EXIT` is set. When this is true, workflows will wait for the
+/// game to exit instead of failing.
+pub fn wait_for_exit() -> bool
+{
+    has_flag(LaunchFlag::WAIT_FOR_EXIT)
+}
+
+/// check if `LaunchFlag::DRY_RUN` is set. When this is true, workflows should print an
+/// `ActionPlan` instead of downloading or writing anything.
+pub fn dry_run() -> bool
+{
+    has_flag(LaunchFlag::DRY_RUN)
+}
+
+/// check if `LaunchFlag::ALLOW_ROOT` is set. When this is false, workflows that write to an install
+/// that is owned by someone else will fail when running as root.
+pub fn allow_root() -> bool
+{
+    has_flag(LaunchFlag::ALLOW_ROOT)
 }
\ No newline at end of file
@@ -10,6 +10,42 @@ pub const STEAM_POSSIBLE_DIR:  &'static [&'static str] = &[
     "~/.var/app/com.valvesoftware.Steam/.steam/registry.vdf"
 ];
 
+/// all possible known locations for the steam installation directory (where `steamapps` is)
+/// only is used on linux, since windows will use the registry.
+pub const STEAM_POSSIBLE_INSTALL_DIR: &'static [&'static str] = &[
+    "~/.steam/steam",
+    "~/.local/share/Steam",
+    "~/.var/app/com.valvesoftware.Steam/.local/share/Steam",
+    "~/.var/app/com.valvesoftware.Steam/.steam/steam"
+];
+
+/// find the steam installation directory on linux.
+/// returns the first item in STEAM_POSSIBLE_INSTALL_DIR that has a `steamapps` folder in it.
+pub fn find_steam_path() -> Result<String, BeansError>
+{
+    let home = match simple_home_dir::home_dir() {
+        Some(v) => match v.to_str() {
+            Some(k) => format_directory_path(k.to_string()),
+            None => {
+                debug!("[helper::find_steam_path] simple_home_dir::home_dir().to_str() returned None!");
+                return Err(BeansError::SteamNotFound);
+            }
+        },
+        None => {
+            debug!("[helper::find_steam_path] simple_home_dir::home_dir() returned None!");
+            return Err(BeansError::SteamNotFound);
+        }
+    };
+    for x in STEAM_POSSIBLE_INSTALL_DIR.iter() {
+        let location = format_directory_path(x.replace("~/", home.as_str()));
+        if crate::helper::is_directory(format!("{}steamapps", location)) {
+            return Ok(location);
+        }
+    }
+    debug!("[helper::find_steam_path] Couldn't find any of the locations in STEAM_POSSIBLE_INSTALL_DIR");
+    Err(BeansError::SteamNotFound)
+}
+
 /// find sourcemod path on linux.
 /// fetches the fake registry that steam uses from find_steam_reg_path
 /// and gets the value of Registry/HKCU/Software/Valve/Steam/SourceModInstallPath
@@ -9,6 +9,10 @@ pub use linux::*;
 mod windows;
 #[cfg(target_os = "windows")]
 pub use windows::*;
+#[cfg(target_os = "linux")]
+pub mod nofollow;
+#[cfg(test)]
+pub mod test_dir;
 
 
 use std::io::Write;
@@ -121,6 +125,25 @@ pub fn get_input(prompt: &str) -> String{
     input.trim().to_string()
 }
 
+/// get user input, but check the environment variable `env_key` first.
+///
+/// when the environment variable isn't set and `LaunchFlag::AUTOMATED` is set, then
+/// `BeansError::AutomatedInputRequired` is returned instead of waiting for user input.
+pub fn try_get_input(prompt: &str, env_key: &str) -> Result<String, BeansError>
+{
+    if let Ok(v) = std::env::var(env_key) {
+        debug!("[helper::try_get_input] using value from {} for \"{}\"", env_key, prompt);
+        return Ok(v.trim().to_string());
+    }
+    if crate::flags::automated() {
+        return Err(BeansError::AutomatedInputRequired {
+            prompt: prompt.to_string(),
+            env_key: env_key.to_string()
+        });
+    }
+    Ok(get_input(prompt))
+}
+
 
 /// check if a file exists
 pub fn file_exists(location: String) -> bool
@@ -141,6 +164,56 @@ pub fn is_symlink(location: String) -> bool {
     }
 }
 
+/// Get the steam installation directory (where `steamapps` is) for the sourcemods folder provided.
+///
+/// When `sourcemods_location` is inside of `steamapps`, then the parent of `steamapps` is used.
+/// Otherwise, `find_steam_path()` is used.
+pub fn get_steam_location(sourcemods_location: String) -> Result<String, BeansError>
+{
+    let smp = PathBuf::from(parse_location(sourcemods_location));
+    if let Some(steamapps) = smp.parent() {
+        let is_steamapps = steamapps.file_name()
+            .and_then(|v| v.to_str())
+            .is_some_and(|v| v.eq_ignore_ascii_case("steamapps"));
+        if is_steamapps {
+            if let Some(steam) = steamapps.parent().and_then(|v| v.to_str()) {
+                return Ok(format_directory_path(steam.to_string()));
+            }
+        }
+    }
+    find_steam_path()
+}
+
+/// Get all of the steam library folders that are in `steamapps/libraryfolders.vdf`. The steam
+/// installation directory provided is always the first item.
+pub fn find_steam_library_folders(steam_location: String) -> Vec<String>
+{
+    let mut result: Vec<String> = vec![format_directory_path(steam_location.clone())];
+    let vdf_location = join_path(steam_location, String::from("steamapps/libraryfolders.vdf"));
+    let content = match std::fs::read_to_string(&vdf_location) {
+        Ok(v) => v,
+        Err(e) => {
+            debug!("[helper::find_steam_library_folders] Failed to read {} ({:})", vdf_location, e);
+            return result;
+        }
+    };
+    for line in content.lines() {
+        let line = line.trim();
+        if !line.starts_with("\"path\"") {
+            continue;
+        }
+        let value = line.trim_start_matches("\"path\"")
+            .trim()
+            .trim_matches('"')
+            .replace("\\\\", "\\");
+        let location = format_directory_path(value);
+        if !result.contains(&location) {
+            result.push(location);
+        }
+    }
+    result
+}
+
 pub fn generate_rand_str(length: usize) -> String
 {
     let s: String = rand::thread_rng()
@@ -253,7 +326,8 @@ pub async fn download_with_progress(url: String, out_location: String) -> Result
     let res = match reqwest::Client::new()
         .get(&url)
         .send()
-        .await {
+        .await
+        .and_then(|v| v.error_for_status()) {
         Ok(v) => v,
         Err(e) => {
             sentry::capture_error(&e);
@@ -266,15 +340,25 @@ pub async fn download_with_progress(url: String, out_location: String) -> Result
         }
     };
 
-    let total_size = res
-        .content_length()
-        .expect("Failed to get length of data to download");
+    // servers that aren't ours don't always send the length, so a spinner is shown instead.
+    let total_size = res.content_length();
 
-    let pb = ProgressBar::new(total_size);
-    pb.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
-        .unwrap()
-        .with_key("eta", |state: &indicatif::ProgressState, w: &mut dyn std::fmt::Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
-        .progress_chars("#>-"));
+    let pb = match total_size {
+        Some(v) => {
+            let pb = ProgressBar::new(v);
+            pb.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
+                .unwrap()
+                .with_key("eta", |state: &indicatif::ProgressState, w: &mut dyn std::fmt::Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
+                .progress_chars("#>-"));
+            pb
+        },
+        None => {
+            let pb = ProgressBar::new_spinner();
+            pb.set_style(ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] {bytes} ({bytes_per_sec})")
+                .unwrap());
+            pb
+        }
+    };
     pb.set_message(format!("Downloading {}", &url));
 
     // download chunks
@@ -292,10 +376,29 @@ pub async fn download_with_progress(url: String, out_location: String) -> Result
     let mut stream = res.bytes_stream();
 
     while let Some(item) = stream.next().await {
-        let chunk = item.expect("Failed to write content to file");
-        file.write_all(&chunk)
-            .expect("Failed to write content to file");
-        let new = std::cmp::min(downloaded + (chunk.len() as u64), total_size);
+        let chunk = match item {
+            Ok(v) => v,
+            Err(e) => {
+                pb.abandon();
+                return Err(BeansError::DownloadFailure {
+                    reason: DownloadFailureReason::Reqwest {
+                        url,
+                        error: e
+                    }
+                });
+            }
+        };
+        if let Err(e) = file.write_all(&chunk) {
+            pb.abandon();
+            return Err(BeansError::FileWriteFailure {
+                location: out_location,
+                error: e
+            });
+        }
+        let new = match total_size {
+            Some(v) => std::cmp::min(downloaded + (chunk.len() as u64), v),
+            None => downloaded + (chunk.len() as u64)
+        };
         downloaded = new;
         pb.set_position(new);
     }
@@ -355,16 +458,7 @@ pub fn format_size(i: usize) -> String {
 /// Create directory in temp directory with name of "beans-rs"
 pub fn get_tmp_dir() -> String
 {
-    let mut dir = std::env::temp_dir().to_str().unwrap_or("").to_string();
-    if cfg!(target_os = "android") {
-        dir = String::from("/data/var/tmp");
-    } else if cfg!(not(target_os = "windows")) {
-        dir = String::from("/var/tmp");
-    }
-    dir = format_directory_path(dir);
-    dir = join_path(dir, String::from("beans-rs"));
-    dir = format_directory_path(dir);
-
+    let dir = tmp_dir_location();
     if !file_exists(dir.clone()) {
         if let Err(e) = std::fs::create_dir(&dir) {
             warn!("[helper::get_tmp_dir] failed to make tmp directory at {} ({:})", dir, e);
@@ -376,6 +470,19 @@ pub fn get_tmp_dir() -> String
 
     return dir;
 }
+/// Location of the directory that `get_tmp_dir` returns, without creating it.
+pub fn tmp_dir_location() -> String
+{
+    let mut dir = std::env::temp_dir().to_str().unwrap_or("").to_string();
+    if cfg!(target_os = "android") {
+        dir = String::from("/data/var/tmp");
+    } else if cfg!(not(target_os = "windows")) {
+        dir = String::from("/var/tmp");
+    }
+    dir = format_directory_path(dir);
+    dir = join_path(dir, String::from("beans-rs"));
+    format_directory_path(dir)
+}
 /// Generate a full file location for a temporary file.
 pub fn get_tmp_file(filename: String) -> String
 {
@@ -515,5 +622,17 @@ pub struct GithubReleaseItem
     pub url: String,
     pub html_url: String,
     pub draft: bool,
-    pub prerelease: bool
+    pub prerelease: bool,
+    #[serde(default)]
+    pub assets: Vec<GithubReleaseAsset>
+}
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct GithubReleaseAsset
+{
+    pub name: String,
+    pub size: u64,
+    pub browser_download_url: String,
+    /// Hash of the asset, formatted as `<algorithm>:<hex>` (e.g; `sha256:abc123...`)
+    #[serde(default)]
+    pub digest: Option<String>
 }
\ No newline at end of file
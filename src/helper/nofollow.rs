@@ -0,0 +1,183 @@
+use std::ffi::{CStr, CString, OsStr};
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// File or directory that was found by `walk`.
+pub struct WalkEntry<'a>
+{
+    /// Directory that `name` is in. `libc::AT_FDCWD` for the location that is being walked.
+    pub dir: RawFd,
+    /// Name of the entry in `dir`, for the `*at` functions (e.g; `fchownat`). The full location
+    /// for the location that is being walked.
+    pub name: &'a CStr,
+    /// Location relative to where the walk started. Empty for the location itself.
+    pub relative: &'a Path,
+    /// From `fstatat` with `AT_SYMLINK_NOFOLLOW`.
+    pub stat: libc::stat
+}
+impl WalkEntry<'_>
+{
+    pub fn is_dir(&self) -> bool
+    {
+        self.stat.st_mode & libc::S_IFMT == libc::S_IFDIR
+    }
+    pub fn is_file(&self) -> bool
+    {
+        self.stat.st_mode & libc::S_IFMT == libc::S_IFREG
+    }
+}
+
+/// Call `visit` for `location`, and everything in it, without following symlinks.
+///
+/// Directories are opened with `O_NOFOLLOW`, and everything in them is found relative to that
+/// directory (with `openat` and `fstatat`), so a directory that is replaced with a symlink while
+/// this is running is never followed. `visit` should use `WalkEntry::dir` and `WalkEntry::name`
+/// with `AT_SYMLINK_NOFOLLOW` for the same reason. Directories are visited before what is in
+/// them. Entries that are deleted while this is running are skipped.
+///
+/// When it fails, the location that it failed on is returned with the error.
+pub fn walk<F>(location: &Path, mut visit: F) -> Result<(), (PathBuf, std::io::Error)>
+    where F: FnMut(&WalkEntry) -> std::io::Result<()>
+{
+    let c_location = CString::new(location.as_os_str().as_bytes())
+        .map_err(|e| (location.to_path_buf(), std::io::Error::new(std::io::ErrorKind::InvalidInput, e)))?;
+    let stat = match stat_at(libc::AT_FDCWD, &c_location) {
+        Ok(Some(v)) => v,
+        Ok(None) => return Ok(()),
+        Err(e) => return Err((location.to_path_buf(), e))
+    };
+    let entry = WalkEntry {
+        dir: libc::AT_FDCWD,
+        name: &c_location,
+        relative: Path::new(""),
+        stat
+    };
+    visit(&entry).map_err(|e| (location.to_path_buf(), e))?;
+    if !entry.is_dir() {
+        return Ok(());
+    }
+    match open_dir_at(libc::AT_FDCWD, &c_location) {
+        Ok(Some(dir)) => walk_dir(&dir, location, Path::new(""), &mut visit),
+        Ok(None) => Ok(()),
+        Err(e) => Err((location.to_path_buf(), e))
+    }
+}
+fn walk_dir<F>(dir: &OwnedFd, location: &Path, relative: &Path, visit: &mut F) -> Result<(), (PathBuf, std::io::Error)>
+    where F: FnMut(&WalkEntry) -> std::io::Result<()>
+{
+    let names = read_dir(dir).map_err(|e| (location.join(relative), e))?;
+    for name in names.iter() {
+        let child = relative.join(OsStr::from_bytes(name.to_bytes()));
+        let stat = match stat_at(dir.as_raw_fd(), name) {
+            Ok(Some(v)) => v,
+            Ok(None) => continue,
+            Err(e) => return Err((location.join(&child), e))
+        };
+        let entry = WalkEntry {
+            dir: dir.as_raw_fd(),
+            name,
+            relative: &child,
+            stat
+        };
+        visit(&entry).map_err(|e| (location.join(&child), e))?;
+        if !entry.is_dir() {
+            continue;
+        }
+        match open_dir_at(dir.as_raw_fd(), name) {
+            Ok(Some(v)) => walk_dir(&v, location, &child, visit)?,
+            Ok(None) => {},
+            Err(e) => return Err((location.join(&child), e))
+        }
+    }
+    Ok(())
+}
+
+/// `fstatat` without following `name` when it's a symlink. `None` when it doesn't exist.
+fn stat_at(dir: RawFd, name: &CStr) -> std::io::Result<Option<libc::stat>>
+{
+    // SAFETY: `stat` is only read once fstatat has filled it in, and name is a valid C string.
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstatat(dir, name.as_ptr(), &mut stat, libc::AT_SYMLINK_NOFOLLOW) } != 0 {
+        let error = std::io::Error::last_os_error();
+        return match error.kind() {
+            std::io::ErrorKind::NotFound => Ok(None),
+            _ => Err(error)
+        };
+    }
+    Ok(Some(stat))
+}
+
+/// Open the directory `name` in `dir` with `O_NOFOLLOW`. `None` when it doesn't exist, or isn't a
+/// directory anymore (e.g; it was replaced with a symlink).
+fn open_dir_at(dir: RawFd, name: &CStr) -> std::io::Result<Option<OwnedFd>>
+{
+    // SAFETY: name is a valid C string.
+    let fd = unsafe { libc::openat(dir, name.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC) };
+    if fd < 0 {
+        let error = std::io::Error::last_os_error();
+        return match error.raw_os_error() {
+            Some(libc::ENOENT) | Some(libc::ENOTDIR) | Some(libc::ELOOP) => Ok(None),
+            _ => Err(error)
+        };
+    }
+    // SAFETY: fd was just opened, and nothing else owns it.
+    Ok(Some(unsafe { OwnedFd::from_raw_fd(fd) }))
+}
+
+/// Get the names of everything in the directory `dir`, except for `.` and `..`.
+fn read_dir(dir: &OwnedFd) -> std::io::Result<Vec<CString>>
+{
+    // fdopendir takes ownership of the fd that it's given, so it gets a copy of it.
+    let copy = dir.try_clone()?;
+    // SAFETY: the fd is owned by the DIR from here on, and is closed by closedir.
+    let stream = unsafe { libc::fdopendir(copy.into_raw_fd()) };
+    if stream.is_null() {
+        return Err(std::io::Error::last_os_error());
+    }
+    let mut result: Vec<CString> = Vec::new();
+    loop {
+        // SAFETY: stream is a valid DIR until closedir is called below, and the dirent that
+        // readdir returns is valid until the next call to readdir.
+        let entry = unsafe { libc::readdir(stream) };
+        if entry.is_null() {
+            break;
+        }
+        let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) };
+        if name.to_bytes() != b"." && name.to_bytes() != b".." {
+            result.push(name.to_owned());
+        }
+    }
+    // SAFETY: stream hasn't been closed yet.
+    unsafe { libc::closedir(stream) };
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::test_dir::TestDir;
+
+    #[test]
+    fn symlinks_are_not_followed() {
+        let dir = TestDir::new("nofollow-symlinks");
+        std::fs::create_dir_all(dir.join("mod/cfg")).unwrap();
+        std::fs::create_dir_all(dir.join("outside")).unwrap();
+        std::fs::write(dir.join("mod/cfg/autoexec.cfg"), "exec").unwrap();
+        std::fs::write(dir.join("outside/secret"), "secret").unwrap();
+        std::os::unix::fs::symlink(dir.join("outside"), dir.join("mod/link")).unwrap();
+
+        let mut found: Vec<(String, bool, bool)> = Vec::new();
+        walk(&dir.join("mod"), |entry| {
+            found.push((entry.relative.to_string_lossy().to_string(), entry.is_dir(), entry.is_file()));
+            Ok(())
+        }).unwrap();
+        found.sort();
+        assert_eq!(found, vec![
+            ("".to_string(), true, false),
+            ("cfg".to_string(), true, false),
+            ("cfg/autoexec.cfg".to_string(), false, true),
+            ("link".to_string(), false, false)
+        ]);
+    }
+}
\ No newline at end of file
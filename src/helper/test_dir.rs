@@ -0,0 +1,41 @@
+use std::path::{Path, PathBuf};
+
+/// Folder in the temporary directory for a test, which is deleted when it's dropped (even when
+/// the test fails).
+pub struct TestDir
+{
+    location: PathBuf
+}
+impl TestDir
+{
+    /// Create an empty folder for the test `name`, which has to be unique in the crate (e.g;
+    /// `extract-normal`). Anything that was left over from a previous run is deleted first.
+    pub fn new(name: &str) -> Self
+    {
+        let location = std::env::temp_dir().join(format!("beans-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&location);
+        std::fs::create_dir_all(&location).unwrap();
+        Self {
+            location
+        }
+    }
+
+    pub fn path(&self) -> &Path
+    {
+        &self.location
+    }
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf
+    {
+        self.location.join(path)
+    }
+    /// Location of the folder as a `String`, for the functions that take one.
+    pub fn text(&self) -> String
+    {
+        self.location.to_string_lossy().to_string()
+    }
+}
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.location);
+    }
+}
\ No newline at end of file
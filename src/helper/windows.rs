@@ -33,4 +33,29 @@ pub fn find_sourcemod_path() -> Result<String, BeansError>
             });
         }
     }
+}
+
+/// get the steam installation directory (where `steamapps` is) from the registry.
+/// HKEY_CURRENT_USER\Software\Valve\Steam
+/// Key: SteamPath
+pub fn find_steam_path() -> Result<String, BeansError>
+{
+    match RegKey::predef(HKEY_CURRENT_USER).open_subkey(String::from("Software\\Valve\\Steam")) {
+        Ok(rkey) => {
+            let x: std::io::Result<String> = rkey.get_value("SteamPath");
+            match x {
+                Ok(val) => Ok(format_directory_path(val)),
+                Err(e) => Err(BeansError::RegistryKeyFailure {
+                    msg: "Failed to find SteamPath in HKCU\\Software\\Valve\\Steam. Steam might not be installed".to_string(),
+                    error: e,
+                    backtrace: Backtrace::capture()
+                })
+            }
+        },
+        Err(e) => Err(BeansError::RegistryKeyFailure {
+            msg: "Failed to find HKCU\\Software\\Valve. Steam might not be installed".to_string(),
+            error: e,
+            backtrace: Backtrace::capture()
+        })
+    }
 }
\ No newline at end of file
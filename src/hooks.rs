@@ -0,0 +1,275 @@
+use std::backtrace::Backtrace;
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::process::Stdio;
+use tokio::process::Command;
+use log::{debug, error, info};
+use crate::{BeansError, flags, HookFailureReason, RunnerContext};
+use crate::webhook::{WebhookConfig, WebhookEvent, WebhookPayload};
+use crate::workflows::{ActionPlan, PlannedAction};
+
+/// Commands that are run before and after workflows. Read from the `hooks` property in the config
+/// file (see `BeansConfig`), and every hook can be overridden with an environment variable (see
+/// `HookConfig::env_key`).
+///
+/// Hooks are run with `sh -c` (or `cmd /C` on Windows), and are given the following environment
+/// variables;
+/// - `BEANS_HOOK` Name of the hook (e.g; `pre_update`)
+/// - `BEANS_OPERATION` `install`, `update` or `verify`
+/// - `BEANS_MOD_PATH` Location of the mod (`RunnerContext::get_mod_location`)
+/// - `BEANS_OLD_VERSION` Version that was installed before the workflow started (empty when not installed)
+/// - `BEANS_NEW_VERSION` Version that the workflow is installing (empty when unknown)
+/// - `BEANS_OUTCOME` `pending` for pre-hooks, `success` for post-hooks, and `failure` for `on_failure`
+/// - `BEANS_ERROR` Error message, only set for `on_failure`
+/// - `BEANS_EXIT_CODE` Exit code that beans-rs will exit with, only set for `on_failure`
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct HookConfig
+{
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_install: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_install: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_update: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_update: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_verify: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_verify: Option<String>,
+    /// Run when any of the workflows fail, including when a pre-hook fails.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<String>
+}
+impl HookConfig
+{
+    /// Get the hooks for the install in `sourcemods_location`, with the environment variable
+    /// overrides applied. An empty environment variable disables that hook.
+    pub fn get(sourcemods_location: String) -> Self
+    {
+        let mut config = crate::config::BeansConfig::for_install(&sourcemods_location).hooks
+            .unwrap_or_default();
+        for name in HOOK_NAMES.iter() {
+            if let Ok(v) = std::env::var(Self::env_key(name)) {
+                debug!("[HookConfig::get] Using {} from environment", Self::env_key(name));
+                *config.get_mut(name) = match v.trim().is_empty() {
+                    true => None,
+                    false => Some(v)
+                };
+            }
+        }
+        config
+    }
+
+    /// Environment variable that overrides the hook `name`. (e.g; `BEANS_HOOK_PRE_UPDATE`)
+    pub fn env_key(name: &str) -> String
+    {
+        format!("BEANS_HOOK_{}", name.to_uppercase())
+    }
+
+    /// Get the command for the hook `name`.
+    pub fn get_command(&self, name: &str) -> Option<String>
+    {
+        match name {
+            "pre_install" => self.pre_install.clone(),
+            "post_install" => self.post_install.clone(),
+            "pre_update" => self.pre_update.clone(),
+            "post_update" => self.post_update.clone(),
+            "pre_verify" => self.pre_verify.clone(),
+            "post_verify" => self.post_verify.clone(),
+            "on_failure" => self.on_failure.clone(),
+            _ => None
+        }
+    }
+    fn get_mut(&mut self, name: &str) -> &mut Option<String>
+    {
+        match name {
+            "pre_install" => &mut self.pre_install,
+            "post_install" => &mut self.post_install,
+            "pre_update" => &mut self.pre_update,
+            "post_update" => &mut self.post_update,
+            "pre_verify" => &mut self.pre_verify,
+            "post_verify" => &mut self.post_verify,
+            _ => &mut self.on_failure
+        }
+    }
+}
+/// Names of every hook in `HookConfig`.
+pub const HOOK_NAMES: [&str; 7] = [
+    "pre_install", "post_install",
+    "pre_update", "post_update",
+    "pre_verify", "post_verify",
+    "on_failure"
+];
+/// Name of the hook that is run when a workflow fails.
+pub const FAILURE_HOOK: &str = "on_failure";
+
+/// Workflow that hooks are being run for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookOperation
+{
+    Install,
+    Update,
+    Verify
+}
+impl Display for HookOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HookOperation::Install => write!(f, "install"),
+            HookOperation::Update => write!(f, "update"),
+            HookOperation::Verify => write!(f, "verify")
+        }
+    }
+}
+
+/// Runs the hooks around a workflow. Created with `HookRunner::new`, then the workflow is run
+/// with `HookRunner::run`.
+#[derive(Debug, Clone)]
+pub struct HookRunner
+{
+    pub operation: HookOperation,
+    pub mod_path: String,
+    pub old_version: Option<usize>,
+    pub new_version: Option<usize>,
+    pub config: HookConfig,
+    /// Notified when the workflow fails.
+    pub webhooks: Vec<WebhookConfig>
+}
+impl HookRunner
+{
+    /// `new_version` is the version that the workflow is going to install.
+    pub fn new(ctx: &mut RunnerContext, operation: HookOperation, new_version: Option<usize>) -> Self
+    {
+        Self {
+            operation,
+            mod_path: ctx.get_mod_location(),
+            old_version: ctx.current_version,
+            new_version,
+            config: HookConfig::get(ctx.sourcemod_path.clone()),
+            webhooks: WebhookConfig::get(ctx.sourcemod_path.clone())
+        }
+    }
+
+    /// Run the pre-hook, then `workflow`, then the post-hook. When the pre-hook or `workflow`
+    /// fails, the `on_failure` hook is run, the `Failure` webhook is sent, and the error is
+    /// returned.
+    ///
+    /// Post-hooks and `on_failure` failing will only be logged, since the workflow has already
+    /// finished by then.
+    pub async fn run<F>(&self, workflow: F) -> Result<(), BeansError>
+        where F: Future<Output = Result<(), BeansError>>
+    {
+        let result = match self.run_hook(&format!("pre_{}", self.operation), "pending", None).await {
+            Ok(_) => workflow.await,
+            Err(e) => Err(e)
+        };
+        let (name, outcome) = match &result {
+            Ok(_) => (format!("post_{}", self.operation), "success"),
+            Err(_) => (FAILURE_HOOK.to_string(), "failure")
+        };
+        if let Err(e) = self.run_hook(&name, outcome, result.as_ref().err()).await {
+            error!("[HookRunner::run] {:}", e);
+        }
+        if let Err(e) = &result {
+            let payload = WebhookPayload::new(WebhookEvent::Failure, self.mod_path.clone(), self.old_version, self.new_version)
+                .with_error(e);
+            crate::webhook::send(&self.webhooks, &payload).await;
+        }
+        result
+    }
+
+    /// Add what `run` would do around `workflow` to `plan`, for a dry run. `workflow` adds the
+    /// actions of the workflow itself.
+    pub fn plan<F>(&self, plan: &mut ActionPlan, workflow: F) -> Result<(), BeansError>
+        where F: FnOnce(&mut ActionPlan) -> Result<(), BeansError>
+    {
+        let add_hook = |plan: &mut ActionPlan, name: String| {
+            if let Some(command) = self.config.get_command(&name) {
+                plan.add(PlannedAction::RunHook {
+                    hook: name,
+                    command
+                });
+            }
+        };
+        add_hook(plan, format!("pre_{}", self.operation));
+        workflow(plan)?;
+        add_hook(plan, format!("post_{}", self.operation));
+        if let Some(command) = self.config.get_command(FAILURE_HOOK) {
+            plan.note(format!("The {} hook is run when anything fails: {}", FAILURE_HOOK, command));
+        }
+        let failure_webhooks = self.webhooks.iter().filter(|v| v.wants(WebhookEvent::Failure)).count();
+        if failure_webhooks > 0 {
+            plan.note(format!("{} webhook(s) are sent when anything fails", failure_webhooks));
+        }
+        Ok(())
+    }
+
+    /// Run the hook `name` when it's been set. Returns `BeansError::HookFailure` when it couldn't
+    /// be started or exited with a non-zero exit code.
+    async fn run_hook(&self, name: &str, outcome: &str, workflow_error: Option<&BeansError>) -> Result<(), BeansError>
+    {
+        let command = match self.config.get_command(name) {
+            Some(v) => v,
+            None => return Ok(())
+        };
+        info!("[HookRunner::run_hook] Running {} hook: {}", name, command);
+
+        let mut cmd = shell_command(&command);
+        cmd.env("BEANS_HOOK", name)
+            .env("BEANS_OPERATION", self.operation.to_string())
+            .env("BEANS_MOD_PATH", &self.mod_path)
+            .env("BEANS_OLD_VERSION", self.old_version.map(|v| v.to_string()).unwrap_or_default())
+            .env("BEANS_NEW_VERSION", self.new_version.map(|v| v.to_string()).unwrap_or_default())
+            .env("BEANS_OUTCOME", outcome)
+            .stdin(Stdio::null());
+        if let Some(e) = workflow_error {
+            cmd.env("BEANS_ERROR", format!("{:}", e))
+                .env("BEANS_EXIT_CODE", (e.exit_code() as i32).to_string());
+        }
+        // stdout is reserved for the result when --json is used.
+        if flags::json_output() {
+            cmd.stdout(std::io::stderr());
+        }
+
+        let status = match cmd.status().await {
+            Ok(v) => v,
+            Err(e) => {
+                return Err(BeansError::HookFailure {
+                    hook: name.to_string(),
+                    command,
+                    reason: HookFailureReason::Spawn {
+                        error: e
+                    },
+                    backtrace: Backtrace::capture()
+                });
+            }
+        };
+        debug!("[HookRunner::run_hook] {} exited with {}", name, status);
+        if !status.success() {
+            return Err(BeansError::HookFailure {
+                hook: name.to_string(),
+                command,
+                reason: HookFailureReason::ExitCode {
+                    code: status.code()
+                },
+                backtrace: Backtrace::capture()
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn shell_command(command: &str) -> Command
+{
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+#[cfg(target_os = "windows")]
+fn shell_command(command: &str) -> Command
+{
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
\ No newline at end of file
@@ -16,6 +16,17 @@ pub mod butler;
 pub mod flags;
 pub mod appvar;
 pub mod logger;
+pub mod vdf;
+pub mod selfupdate;
+pub mod changelog;
+pub mod hooks;
+pub mod process;
+pub mod rcon;
+pub mod a2s;
+pub mod webhook;
+pub mod config;
+pub mod ownership;
+pub mod extract;
 
 /// NOTE do not change, fetches from the version of beans-rs on build
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -131,4 +131,8 @@ pub fn log_to<T: Write + Send + 'static>(sink: T) {
 }
 pub fn log_to_stdout() {
     log_to(io::stdout());
-}
\ No newline at end of file
+}
+/// Change where log messages are written to, after `log_to` has been called.
+pub fn redirect_to<T: Write + Send + 'static>(sink: T) {
+    LOGGER.renew(sink);
+}
@@ -1,13 +1,16 @@
 #![feature(panic_info_message)]
 
 use std::str::FromStr;
+use std::time::Duration;
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use log::{debug, error, info, LevelFilter, trace};
-use beans_rs::{flags, helper, PANIC_MSG_CONTENT, RunnerContext, wizard};
+use beans_rs::{BeansError, flags, helper, PANIC_MSG_CONTENT, RunnerContext, selfupdate, wizard};
 use beans_rs::flags::LaunchFlag;
+use beans_rs::hooks::{HookOperation, HookRunner};
+use beans_rs::ownership::OwnershipRepair;
 use beans_rs::helper::parse_location;
 use beans_rs::SourceModDirectoryParam;
-use beans_rs::workflows::{InstallWorkflow, UpdateWorkflow, VerifyWorkflow};
+use beans_rs::workflows::{AddonWorkflow, FleetManifest, FleetWorkflow, InstallWorkflow, LaunchWorkflow, ShortcutWorkflow, StatusWorkflow, UpdateWorkflow, VerifyWorkflow, WatchOptions, WatchWorkflow, DEFAULT_WATCH_INTERVAL, DEFAULT_WATCH_JITTER};
 
 pub const DEFAULT_LOG_LEVEL_RELEASE: LevelFilter = LevelFilter::Info;
 #[cfg(debug_assertions)]
@@ -50,6 +53,9 @@ fn init_flags()
     if std::env::var("BEANS_DEBUG").is_ok_and(|x| x == "1") {
         flags::add_flag(LaunchFlag::DEBUG_MODE);
     }
+    if std::env::var("BEANS_AUTOMATED").is_ok_and(|x| x == "1") {
+        flags::add_flag(LaunchFlag::AUTOMATED);
+    }
     flags::add_flag(LaunchFlag::STANDALONE_APP);
     beans_rs::logger::set_filter(DEFAULT_LOG_LEVEL);
     beans_rs::logger::log_to_stdout();
@@ -82,7 +88,7 @@ fn fix_msgbox_txt(txt: String) -> String {
 fn custom_panic_handle(msg: String)
 {
     unsafe {
-        if beans_rs::PAUSE_ONCE_DONE {
+        if beans_rs::PAUSE_ONCE_DONE && !flags::automated() {
             let mut txt = PANIC_MSG_CONTENT.to_string().replace("$err_msg", &msg);
             txt = fix_msgbox_txt(txt);
             std::thread::spawn(move || {
@@ -109,11 +115,37 @@ fn custom_panic_handle(msg: String)
 fn logic_done()
 {
     unsafe {
-        if beans_rs::PAUSE_ONCE_DONE {
+        if beans_rs::PAUSE_ONCE_DONE && !flags::automated() {
             let _ = helper::get_input("Press enter/return to exit");
         }
     }
 }
+/// should be called when the logic flow has failed.
+///
+/// the error is reported via sentry and shown to the user (as JSON when `--json` is provided,
+/// otherwise in the console and in a dialog), then the process exits with the exit code for
+/// the category of the error (see `BeansExitCode`).
+///
+/// the `Failure` webhook is sent when it hasn't been already, using the webhooks for the
+/// install in `sourcemods_location` when it's known.
+async fn logic_failed(e: BeansError, sourcemods_location: Option<String>) -> !
+{
+    sentry::capture_error(&e);
+    if !flags::dry_run() {
+        beans_rs::webhook::send_failure(sourcemods_location, &e).await;
+    }
+    let code = e.exit_code() as i32;
+    if flags::json_output() {
+        println!("{}", e.to_json());
+    } else {
+        error!("{:}", e);
+        trace!("======== Full Error ========");
+        trace!("{:#?}", &e);
+        show_msgbox_error(format!("{:}", &e));
+    }
+    logic_done();
+    std::process::exit(code);
+}
 pub struct Launcher {
     /// Output location. When none, `SourceModDirectoryParam::default()` will be used.
     pub to_location: Option<String>,
@@ -130,6 +162,36 @@ impl Launcher
             .help("Manually specify sourcemods directory. When not provided, beans-rs will automatically detect the sourcemods directory.")
             .required(false)
     }
+    /// Create argument for choosing the release channel.
+    fn create_channel_arg() -> Arg
+    {
+        Arg::new("channel")
+            .long("channel")
+            .help("Release channel to use for this install (e.g; stable, beta, playtest). Saved in .adastral, so it only needs to be provided once. Defaults to stable.")
+            .required(false)
+    }
+    /// Create arguments for choosing which shortcuts to create.
+    fn create_shortcut_args() -> [Arg; 2]
+    {
+        [
+            Arg::new("desktop-entry")
+                .long("desktop-entry")
+                .help("Create a desktop entry for the game in the applications menu (Linux only).")
+                .action(ArgAction::SetTrue),
+            Arg::new("steam-shortcut")
+                .long("steam-shortcut")
+                .help("Add the game as a non-Steam shortcut for every Steam user. Requires Source SDK Base 2013 Multiplayer.")
+                .action(ArgAction::SetTrue)
+        ]
+    }
+    /// Create argument for the add-on IDs that the `addons` subcommands use.
+    fn create_addon_ids_arg(required: bool) -> Arg
+    {
+        Arg::new("ids")
+            .help("IDs of the add-ons, as shown by \"beans-rs addons list\".")
+            .num_args(1..)
+            .required(required)
+    }
     pub async fn run()
     {
         let cmd = Command::new("beans-rs")
@@ -137,25 +199,102 @@ impl Launcher
             .bin_name(clap::crate_name!())
             .subcommand(Command::new("wizard")
                 .about("Use the wizard to install. (Default subcommand)")
-                .arg(Launcher::create_location_arg()))
+                .arg(Launcher::create_location_arg())
+                .arg(Launcher::create_channel_arg()))
             .subcommand(Command::new("install")
                 .about("Install to a custom location.")
                 .args([
                     Launcher::create_location_arg(),
+                    Launcher::create_channel_arg(),
                     Arg::new("from")
                         .long("from")
-                        .help("Location to where the .tar.zstd file is that you want to install from.")
+                        .help("Location or http(s) URL of the package (.tar.zst, .tar.gz, .tar.xz, .tar or .zip), or extracted directory to install from.")
+                        .required(false),
+                    Arg::new("sha256")
+                        .long("sha256")
+                        .help("SHA256 hash that the package from [--from] should have. Nothing is installed when it's different.")
+                        .requires("from")
+                        .required(false),
+                    Arg::new("version")
+                        .long("version")
+                        .help("Version of the package from [--from], which is written to .adastral.")
+                        .value_parser(clap::value_parser!(usize))
+                        .requires("from")
                         .required(false),
                     Arg::new("target-version")
                         .long("target-version")
                         .help("Specify the version to install. Ignored when [--from] is used.")
-                        .required(false)]))
+                        .required(false)])
+                .args(Launcher::create_shortcut_args()))
             .subcommand(Command::new("verify")
                 .about("Verify your current installation")
                 .arg(Launcher::create_location_arg()))
             .subcommand(Command::new("update")
                 .about("Update your installation")
+                .arg(Launcher::create_location_arg())
+                .arg(Launcher::create_channel_arg()))
+            .subcommand(Command::new("launch")
+                .about("Launch the currently installed game")
+                .args([
+                    Launcher::create_location_arg(),
+                    Arg::new("force")
+                        .long("force")
+                        .help("Launch even when the installed version is out of date.")
+                        .action(ArgAction::SetTrue),
+                    Arg::new("args")
+                        .help("Extra arguments to pass to the game. Must be provided after --")
+                        .num_args(0..)
+                        .last(true)
+                        .required(false)]))
+            .subcommand(Command::new("shortcut")
+                .about("Create a desktop entry and/or Steam shortcut for the game. When neither is specified, both are created.")
+                .arg(Launcher::create_location_arg())
+                .args(Launcher::create_shortcut_args()))
+            .subcommand(Command::new("status")
+                .about("Show the installed version, the latest version, and what has changed since the installed version. Use --json for machine-readable output.")
                 .arg(Launcher::create_location_arg()))
+            .subcommand(Command::new("watch")
+                .about("Stay running and install updates when they are released. Stops after the current check when SIGTERM or Ctrl+C is received, so it can be used as a systemd service.")
+                .args([
+                    Launcher::create_location_arg(),
+                    Arg::new("interval")
+                        .long("interval")
+                        .help(format!("Seconds to wait between checking for updates. Defaults to {}", DEFAULT_WATCH_INTERVAL))
+                        .value_parser(clap::value_parser!(u64).range(1..)),
+                    Arg::new("jitter")
+                        .long("jitter")
+                        .help(format!("Random amount of seconds (up to this value) to add to --interval, so servers don't all update at once. Defaults to {}", DEFAULT_WATCH_JITTER))
+                        .value_parser(clap::value_parser!(u64))]))
+            .subcommand(Command::new("fleet")
+                .about("Update or verify every install in a manifest (TOML or JSON). Packages and patches are only downloaded once, and a summary is shown at the end. See README.md for the manifest format.")
+                .args([
+                    Arg::new("manifest")
+                        .help("Location of the manifest file.")
+                        .required(true),
+                    Arg::new("jobs")
+                        .long("jobs")
+                        .help("Amount of installs to work on at the same time. Overrides \"jobs\" in the manifest, which defaults to 1")
+                        .value_parser(clap::value_parser!(u64).range(1..))]))
+            .subcommand(Command::new("addons")
+                .about("List, install, update and remove optional add-ons for the game.")
+                .subcommand_required(true)
+                .subcommand(Command::new("list")
+                    .about("Show the add-ons that are available, and which ones are installed. Use --json for machine-readable output.")
+                    .arg(Launcher::create_location_arg()))
+                .subcommand(Command::new("install")
+                    .about("Install the latest version of add-ons. Add-ons that are already installed will be re-installed.")
+                    .arg(Launcher::create_location_arg())
+                    .arg(Launcher::create_addon_ids_arg(true)))
+                .subcommand(Command::new("update")
+                    .about("Update add-ons to their latest version. When no add-ons are specified, every installed add-on is updated.")
+                    .arg(Launcher::create_location_arg())
+                    .arg(Launcher::create_addon_ids_arg(false)))
+                .subcommand(Command::new("remove")
+                    .about("Remove add-ons from the game.")
+                    .arg(Launcher::create_location_arg())
+                    .arg(Launcher::create_addon_ids_arg(true))))
+            .subcommand(Command::new("self-update")
+                .about("Update beans-rs to the latest release. The current version is kept as a backup, and is restored when the new version fails to run."))
             .args([
                 Arg::new("debug")
                     .long("debug")
@@ -169,25 +308,70 @@ impl Launcher
                     .long("no-pause")
                     .help("When provided, beans-rs will not wait for user input before exiting. It is suggested that server owners use this for any of their scripts.")
                     .action(ArgAction::SetTrue),
-                Launcher::create_location_arg()
+                Arg::new("json")
+                    .long("json")
+                    .help("Write errors as JSON to stdout, and write log messages to stderr. See README.md for the list of exit codes.")
+                    .action(ArgAction::SetTrue),
+                Arg::new("automated")
+                    .long("automated")
+                    .visible_alias("yes")
+                    .help("Run without any prompts or dialogs (implies --no-pause). Values that would be prompted for must be provided with arguments or environment variables, otherwise beans-rs will exit with an error. Can also be enabled with BEANS_AUTOMATED=1")
+                    .action(ArgAction::SetTrue),
+                Arg::new("dry-run")
+                    .long("dry-run")
+                    .global(true)
+                    .help("Show what install, update, verify, addons, watch and fleet would do (which URLs are downloaded, how much space is needed, and which files are deleted or overwritten) without doing it. Nothing except versions.json is downloaded, and nothing is written. Use --json for machine-readable output.")
+                    .action(ArgAction::SetTrue),
+                Arg::new("wait-for-exit")
+                    .long("wait-for-exit")
+                    .global(true)
+                    .help("When the game or dedicated server is running from the mod folder, wait for it to exit instead of failing. Used by install, update and verify.")
+                    .action(ArgAction::SetTrue),
+                Arg::new("allow-root")
+                    .long("allow-root")
+                    .global(true)
+                    .help("Allow running as root when the mod folder belongs to another user. The files that are written are given back to that user afterwards. Used by install, update, verify and addons.")
+                    .action(ArgAction::SetTrue),
+                Launcher::create_location_arg(),
+                Launcher::create_channel_arg()
             ]);
 
         let mut i = Self::new(&cmd.get_matches());
-        if let Ok(r) = helper::beans_has_update().await {
-            if let Some(v) = r {
+        if i.root_matches.subcommand_name() != Some("self-update") && !flags::dry_run() {
+            if let Ok(Some(v)) = helper::beans_has_update().await {
                 info!("A new version of beans-rs is available!");
                 info!("{}", v.html_url);
+                i.prompt_self_update(v).await;
+            }
+        }
+        match i.subcommand_processor().await {
+            Ok(_) => logic_done(),
+            Err(e) => {
+                let location = i.to_location.clone()
+                    .or_else(|| helper::find_sourcemod_path().ok())
+                    .map(helper::parse_location);
+                logic_failed(e, location).await
             }
         }
-        i.subcommand_processor().await;
     }
     pub fn new(matches: &ArgMatches) -> Self {
         let mut i = Self {
             to_location: None,
             root_matches: matches.clone()
         };
+        i.set_json();
         i.set_debug();
         i.set_no_pause();
+        i.set_automated();
+        if i.root_matches.get_flag("wait-for-exit") {
+            flags::add_flag(LaunchFlag::WAIT_FOR_EXIT);
+        }
+        if i.root_matches.get_flag("dry-run") {
+            flags::add_flag(LaunchFlag::DRY_RUN);
+        }
+        if i.root_matches.get_flag("allow-root") {
+            flags::add_flag(LaunchFlag::ALLOW_ROOT);
+        }
         i.to_location = Launcher::find_arg_sourcemods_location(&i.root_matches);
 
         return i;
@@ -215,7 +399,33 @@ impl Launcher
         }
     }
 
-    /// Set `self.to_location` when provided in the arguments.
+    /// add `LaunchFlag::AUTOMATED` to `flags` when the `--automated` parameter flag is used.
+    /// `PAUSE_ONCE_DONE` will also be set to `false`, since there is nobody to press enter.
+    pub fn set_automated(&mut self)
+    {
+        if self.root_matches.get_flag("automated") {
+            flags::add_flag(LaunchFlag::AUTOMATED);
+        }
+        if flags::automated() {
+            unsafe {
+                beans_rs::PAUSE_ONCE_DONE = false;
+            }
+            debug!("Automated mode enabled");
+        }
+    }
+
+    /// add `LaunchFlag::JSON_OUTPUT` to `flags` when `--json` is provided, and write log messages
+    /// to stderr so they don't get mixed up with the JSON that is written to stdout.
+    pub fn set_json(&mut self)
+    {
+        if self.root_matches.get_flag("json") {
+            flags::add_flag(LaunchFlag::JSON_OUTPUT);
+            beans_rs::logger::redirect_to(std::io::stderr());
+        }
+    }
+
+    /// Set `self.to_location` when provided in the arguments, or from the
+    /// `BEANS_SOURCEMODS_LOCATION` environment variable.
     pub fn find_arg_sourcemods_location(matches: &ArgMatches) -> Option<String>
     {
         let mut sml_dir_manual: Option<String> = None;
@@ -223,28 +433,75 @@ impl Launcher
             sml_dir_manual = Some(parse_location(x.to_string()));
             info!("[Launcher::set_to_location] Found in arguments! {}", x);
         }
+        else if let Ok(x) = std::env::var(wizard::SOURCEMODS_LOCATION_ENV) {
+            sml_dir_manual = Some(parse_location(x.clone()));
+            info!("[Launcher::set_to_location] Found in environment! {}", x);
+        }
         sml_dir_manual
     }
 
+    /// Ask if beans-rs should be updated to `release`. Only done when the wizard is used without
+    /// `--automated`, since the other subcommands are usually used in scripts.
+    async fn prompt_self_update(&mut self, release: helper::GithubReleaseItem)
+    {
+        let is_wizard = matches!(self.root_matches.subcommand_name(), None | Some("wizard"));
+        if !is_wizard || flags::automated() {
+            return;
+        }
+        let answer = helper::get_input(format!("Would you like to update to {} now? [y/N]", release.tag_name).as_str());
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            return;
+        }
+        match selfupdate::update(release).await {
+            Ok(_) => println!("Restart beans-rs to use the new version."),
+            Err(e) => {
+                error!("{:}", e);
+                debug!("{:#?}", e);
+                sentry::capture_error(&e);
+            }
+        }
+    }
+
     /// main handler for subcommand processing.
-    pub async fn subcommand_processor(&mut self)
+    pub async fn subcommand_processor(&mut self) -> Result<(), BeansError>
     {
         match self.root_matches.clone().subcommand() {
             Some(("install", i_matches)) => {
-                self.task_install(i_matches).await;
+                self.task_install(i_matches).await
             },
             Some(("verify", v_matches)) => {
-                self.task_verify(v_matches).await;
+                self.task_verify(v_matches).await
             },
             Some(("update", u_matches)) => {
-                self.task_update(u_matches).await;
+                self.task_update(u_matches).await
+            },
+            Some(("launch", l_matches)) => {
+                self.task_launch(l_matches).await
+            },
+            Some(("shortcut", s_matches)) => {
+                self.task_shortcut(s_matches).await
+            },
+            Some(("status", st_matches)) => {
+                self.task_status(st_matches).await
+            },
+            Some(("watch", wt_matches)) => {
+                self.task_watch(wt_matches).await
+            },
+            Some(("fleet", fl_matches)) => {
+                self.task_fleet(fl_matches).await
+            },
+            Some(("addons", ad_matches)) => {
+                self.task_addons(ad_matches).await
+            },
+            Some(("self-update", _)) => {
+                self.task_self_update().await
             },
             Some(("wizard", wz_matches)) => {
                 self.to_location = Launcher::find_arg_sourcemods_location(wz_matches);
-                self.task_wizard().await;
+                self.task_wizard(wz_matches).await
             },
             _ => {
-                self.task_wizard().await;
+                self.task_wizard(&self.root_matches.clone()).await
             }
         }
     }
@@ -262,24 +519,18 @@ impl Launcher
     }
 
     /// handler for the `wizard` subcommand. it's also the default subcommand.
-    pub async fn task_wizard(&mut self)
+    pub async fn task_wizard(&mut self, matches: &ArgMatches) -> Result<(), BeansError>
     {
         let x = self.try_get_smdp();
-        if let Err(e) = wizard::WizardContext::run(x).await {
-            panic!("Failed to run WizardContext {:#?}", e);
-        } else {
-            logic_done();
-        }
+        wizard::WizardContext::run(x, matches.get_one::<String>("channel").cloned()).await
     }
 
     /// handler for the `install` subcommand
-    ///
-    /// NOTE this function uses `panic!` when `InstallWorkflow::wizard` fails. panics are handled
-    /// and are reported via sentry.
-    pub async fn task_install(&mut self, matches: &ArgMatches)
+    pub async fn task_install(&mut self, matches: &ArgMatches) -> Result<(), BeansError>
     {
         self.to_location = Launcher::find_arg_sourcemods_location(&matches);
-        let mut ctx = self.try_create_context().await;
+        let mut ctx = self.try_create_context().await?;
+        Launcher::set_channel(&mut ctx, matches)?;
 
         // call install_version when target-version is found.
         // we do this since target-version overrides the `from` parameter.
@@ -287,113 +538,215 @@ impl Launcher
         // `else if let` is used for checking the `--from` parameter,
         // so a return isn't required.
         if let Some(x) = matches.get_one::<String>("target-version") {
-            self.task_install_version_specific(ctx, x.clone()).await;
+            self.task_install_version_specific(ctx.clone(), x.clone()).await?;
         }
 
-        // manually install from specific `.tar.zstd` file when the
+        // manually install from a specific package (or directory) when the
         // --from parameter is provided. otherwise we install/reinstall
         // the latest version to whatever sourcemods directory is used
         else if let Some(x) = matches.get_one::<String>("from") {
             info!("Manually installing from {} to {}", x.clone(), ctx.sourcemod_path.clone());
-            if let Err(e) = InstallWorkflow::install_from(x.clone(), ctx.sourcemod_path.clone(), None).await {
-                error!("Failed to run InstallWorkflow::install_from");
-                sentry::capture_error(&e);
-                panic!("{:#?}", e);
+            let version = matches.get_one::<usize>("version").copied();
+            let sha256 = match matches.get_one::<String>("sha256") {
+                Some(v) => Some(InstallWorkflow::parse_sha256(v)?),
+                None => None
+            };
+            if flags::dry_run() {
+                InstallWorkflow::plan_from_location(&mut ctx, x.clone(), version, sha256)?.print()?;
             } else {
-                logic_done();
+                ctx.remote_version_list.check_min_beans_version()?;
+                let repair = OwnershipRepair::new(&mut ctx)?;
+                HookRunner::new(&mut ctx, HookOperation::Install, version)
+                    .run(repair.run(InstallWorkflow::install_from_location(x.clone(), ctx.sourcemod_path.clone(), version, sha256))).await?;
             }
         } else {
-            if let Err(e) = InstallWorkflow::wizard(&mut ctx).await {
-                panic!("Failed to run InstallWorkflow {:#?}", e);
-            } else {
-                logic_done();
+            InstallWorkflow::wizard(&mut ctx).await?;
+        }
+
+        if flags::dry_run() {
+            if matches.get_flag("desktop-entry") || matches.get_flag("steam-shortcut") {
+                info!("Not creating shortcuts since this is a dry run");
             }
+            return Ok(());
         }
+
+        ShortcutWorkflow::create(&mut ctx, matches.get_flag("desktop-entry"), matches.get_flag("steam-shortcut"))
     }
     /// handler for the `install` subcommand where the `--target-version`
     /// parameter is provided.
-    ///
-    /// NOTE this function uses `expect` on `InstallWorkflow::install_version`. panics are handled
-    /// and are reported via sentry.
-    pub async fn task_install_version_specific(&mut self, ctx: RunnerContext, version_str: String)
+    pub async fn task_install_version_specific(&mut self, ctx: RunnerContext, version_str: String) -> Result<(), BeansError>
     {
         let version = match usize::from_str(&version_str) {
             Ok(v) => v,
             Err(e) => {
-                sentry::capture_error(&e);
-                error!("Failed to parse version argument \"{version_str}\": {:#?}", e);
-                logic_done();
-                return;
+                debug!("Failed to parse version argument \"{version_str}\": {:#?}", e);
+                return Err(BeansError::InvalidArgument {
+                    name: "target-version".to_string(),
+                    value: version_str,
+                    reason: format!("{:}", e)
+                });
             }
         };
         let mut wf = InstallWorkflow
         {
             context: ctx
         };
-        if let Err(e) = wf.install_version(version).await {
-            error!("Failed to run InstallWorkflow::install_version");
-            sentry::capture_error(&e);
-            panic!("{:#?}", e);
-        } else {
-            logic_done();
-        }
+        wf.install_version(version).await
     }
 
     /// handler for the `verify` subcommand
-    ///
-    /// NOTE this function uses `panic!` when `VerifyWorkflow::wizard` fails. panics are handled
-    /// and are reported via sentry.
-    pub async fn task_verify(&mut self, matches: &ArgMatches)
+    pub async fn task_verify(&mut self, matches: &ArgMatches) -> Result<(), BeansError>
     {
         self.to_location = Launcher::find_arg_sourcemods_location(&matches);
-        let mut ctx = self.try_create_context().await;
+        let mut ctx = self.try_create_context().await?;
 
-        if let Err(e) = VerifyWorkflow::wizard(&mut ctx).await {
-            panic!("Failed to run VerifyWorkflow {:#?}", e);
-        } else {
-            logic_done();
-        }
+        VerifyWorkflow::wizard(&mut ctx).await
     }
 
     /// handler for the `update` subcommand
-    ///
-    /// NOTE this function uses `panic!` when `UpdateWorkflow::wizard` fails. panics are handled
-    /// and are reported via sentry.
-    pub async fn task_update(&mut self, matches: &ArgMatches)
+    pub async fn task_update(&mut self, matches: &ArgMatches) -> Result<(), BeansError>
     {
         self.to_location = Launcher::find_arg_sourcemods_location(&matches);
-        let mut ctx = self.try_create_context().await;
+        let mut ctx = self.try_create_context().await?;
+        Launcher::set_channel(&mut ctx, matches)?;
 
-        if let Err(e) = UpdateWorkflow::wizard(&mut ctx).await {
-            panic!("Failed to run UpdateWorkflow {:#?}", e);
-        } else {
-            logic_done();
+        UpdateWorkflow::wizard(&mut ctx).await
+    }
+
+    /// handler for the `launch` subcommand
+    pub async fn task_launch(&mut self, matches: &ArgMatches) -> Result<(), BeansError>
+    {
+        self.to_location = Launcher::find_arg_sourcemods_location(&matches);
+        let mut ctx = self.try_create_context().await?;
+
+        let args: Vec<String> = match matches.get_many::<String>("args") {
+            Some(v) => v.cloned().collect(),
+            None => Vec::new()
+        };
+        LaunchWorkflow::launch(&mut ctx, args, matches.get_flag("force"))
+    }
+
+    /// handler for the `shortcut` subcommand
+    pub async fn task_shortcut(&mut self, matches: &ArgMatches) -> Result<(), BeansError>
+    {
+        self.to_location = Launcher::find_arg_sourcemods_location(&matches);
+        let mut ctx = self.try_create_context().await?;
+
+        let mut desktop_entry = matches.get_flag("desktop-entry");
+        let mut steam_shortcut = matches.get_flag("steam-shortcut");
+        if !desktop_entry && !steam_shortcut {
+            desktop_entry = true;
+            steam_shortcut = true;
+        }
+        ShortcutWorkflow::create(&mut ctx, desktop_entry, steam_shortcut)
+    }
+
+    /// handler for the `status` subcommand
+    pub async fn task_status(&mut self, matches: &ArgMatches) -> Result<(), BeansError>
+    {
+        self.to_location = Launcher::find_arg_sourcemods_location(&matches);
+        let mut ctx = self.try_create_context().await?;
+
+        StatusWorkflow::wizard(&mut ctx).await
+    }
+
+    /// handler for the `watch` subcommand
+    pub async fn task_watch(&mut self, matches: &ArgMatches) -> Result<(), BeansError>
+    {
+        self.to_location = Launcher::find_arg_sourcemods_location(&matches);
+        // nobody will be around to answer prompts.
+        flags::add_flag(LaunchFlag::AUTOMATED);
+        unsafe {
+            beans_rs::PAUSE_ONCE_DONE = false;
+        }
+        let mut ctx = self.try_create_context().await?;
+
+        let mut options = WatchOptions::default();
+        if let Some(v) = matches.get_one::<u64>("interval") {
+            options.interval = Duration::from_secs(*v);
+        }
+        if let Some(v) = matches.get_one::<u64>("jitter") {
+            options.jitter = Duration::from_secs(*v);
+        }
+        if flags::dry_run() {
+            // only the first cycle is planned, since it would be the same every time.
+            return UpdateWorkflow::plan(&mut ctx)?.print();
+        }
+        WatchWorkflow::watch(&mut ctx, options).await
+    }
+
+    /// handler for the `fleet` subcommand
+    pub async fn task_fleet(&mut self, matches: &ArgMatches) -> Result<(), BeansError>
+    {
+        // installs are worked on at the same time, so nothing can be prompted for.
+        flags::add_flag(LaunchFlag::AUTOMATED);
+        unsafe {
+            beans_rs::PAUSE_ONCE_DONE = false;
+        }
+        let location = matches.get_one::<String>("manifest").cloned().unwrap_or_default();
+        let manifest = FleetManifest::read(location)?;
+        let jobs = matches.get_one::<u64>("jobs").map(|v| *v as usize);
+        FleetWorkflow::run(manifest, jobs).await
+    }
+
+    /// handler for the `addons` subcommand
+    pub async fn task_addons(&mut self, matches: &ArgMatches) -> Result<(), BeansError>
+    {
+        let (name, sub_matches) = match matches.subcommand() {
+            Some(v) => v,
+            None => return Ok(())
+        };
+        self.to_location = Launcher::find_arg_sourcemods_location(&sub_matches);
+        let mut ctx = self.try_create_context().await?;
+
+        let ids: Vec<String> = match sub_matches.try_get_many::<String>("ids") {
+            Ok(Some(v)) => v.cloned().collect(),
+            _ => Vec::new()
+        };
+        match name {
+            "install" => AddonWorkflow::install(&mut ctx, ids).await,
+            "update" => AddonWorkflow::update(&mut ctx, ids).await,
+            "remove" => AddonWorkflow::remove(&mut ctx, ids),
+            _ => AddonWorkflow::list(&mut ctx)
+        }
+    }
+
+    /// handler for the `self-update` subcommand
+    pub async fn task_self_update(&mut self) -> Result<(), BeansError>
+    {
+        beans_rs::workflows::ensure_not_dry_run("self-update")?;
+        match helper::beans_has_update().await? {
+            Some(release) => selfupdate::update(release).await,
+            None => {
+                println!("beans-rs v{} is already the latest version.", beans_rs::VERSION);
+                Ok(())
+            }
+        }
+    }
+
+    /// Call `RunnerContext::set_channel` when `--channel` is provided.
+    fn set_channel(ctx: &mut RunnerContext, matches: &ArgMatches) -> Result<(), BeansError>
+    {
+        match matches.get_one::<String>("channel") {
+            Some(c) => ctx.set_channel(c.clone()),
+            None => Ok(())
         }
     }
 
     /// try and create an instance of `RunnerContext` via the `create_auto` method while setting
     /// the `sml_via` parameter to the output of `self.try_get_smdp()`
-    ///
-    /// on failure, `panic!` is called. but that's okay because a dialog is shown (in
-    /// `init_panic_handle`) and the error is reported via sentry.
-    async fn try_create_context(&mut self) -> RunnerContext {
-        match RunnerContext::create_auto(self.try_get_smdp()).await {
-            Ok(v) => v,
-            Err(e) => {
-                error!("[try_create_context] {:}", e);
-                trace!("======== Full Error ========");
-                trace!("{:#?}", &e);
-                show_msgbox_error(format!("{:}", &e));
-                sentry::capture_error(&e);
-                logic_done();
-                std::process::exit(1);
-            }
+    async fn try_create_context(&mut self) -> Result<RunnerContext, BeansError> {
+        let ctx = RunnerContext::create_auto(self.try_get_smdp()).await;
+        if let Err(e) = &ctx {
+            error!("[try_create_context] Failed to create RunnerContext");
+            debug!("[try_create_context] {:#?}", e);
         }
+        ctx
     }
 }
 fn show_msgbox_error(text: String) {
     unsafe {
-        if beans_rs::PAUSE_ONCE_DONE {
+        if beans_rs::PAUSE_ONCE_DONE && !flags::automated() {
             std::thread::spawn(move || {
                 let d = native_dialog::MessageDialog::new()
                     .set_type(native_dialog::MessageType::Error)
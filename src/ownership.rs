@@ -0,0 +1,204 @@
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use log::error;
+use crate::{BeansError, RunnerContext};
+use crate::workflows::{ActionPlan, PlannedAction};
+
+/// Where the owner of an install was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum OwnerSource
+{
+    /// Owner of the mod folder.
+    ModFolder,
+    /// Owner of the sourcemods folder, since the mod isn't installed yet.
+    SourcemodsFolder,
+    /// `SUDO_UID` and `SUDO_GID`, since neither folder exists.
+    Sudo
+}
+impl Display for OwnerSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OwnerSource::ModFolder => write!(f, "owner of the mod folder"),
+            OwnerSource::SourcemodsFolder => write!(f, "owner of the sourcemods folder"),
+            OwnerSource::Sudo => write!(f, "SUDO_UID and SUDO_GID")
+        }
+    }
+}
+
+/// User that should own the files in an install when beans-rs is running as root (e.g; with
+/// `sudo beans-rs update`), so the game can still write to them afterwards.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct InstallOwner
+{
+    pub uid: u32,
+    pub gid: u32,
+    pub source: OwnerSource
+}
+impl InstallOwner
+{
+    /// Get the owner for the install in `sourcemods_location`. Only returns something when
+    /// beans-rs is running as root, and the owner isn't root. Returns `BeansError::RunningAsRoot`
+    /// instead when `LaunchFlag::ALLOW_ROOT` isn't set.
+    ///
+    /// The owner of the mod folder is used first, then the owner of the sourcemods folder, then
+    /// `SUDO_UID` and `SUDO_GID`.
+    #[cfg(target_os = "linux")]
+    pub fn get(sourcemods_location: String) -> Result<Option<Self>, BeansError>
+    {
+        use std::os::unix::fs::MetadataExt;
+        // SAFETY: geteuid can't fail, and doesn't touch any memory.
+        if unsafe { libc::geteuid() } != 0 {
+            return Ok(None);
+        }
+        let mod_location = crate::helper::join_path(sourcemods_location.clone(), crate::data_dir());
+        let folder_owner = |location: &str, source: OwnerSource| std::fs::metadata(location).ok()
+            .map(|m| Self {
+                uid: m.uid(),
+                gid: m.gid(),
+                source
+            });
+        let sudo_owner = || {
+            let uid = std::env::var("SUDO_UID").ok()?.parse::<u32>().ok()?;
+            let gid = std::env::var("SUDO_GID").ok()?.parse::<u32>().ok()?;
+            Some(Self {
+                uid,
+                gid,
+                source: OwnerSource::Sudo
+            })
+        };
+        let result = match folder_owner(&mod_location, OwnerSource::ModFolder)
+            .or_else(|| folder_owner(&sourcemods_location, OwnerSource::SourcemodsFolder))
+            .or_else(sudo_owner) {
+            Some(v) => v,
+            None => return Ok(None)
+        };
+        if result.uid == 0 {
+            log::debug!("[InstallOwner::get] Files will be owned by root ({})", result.source);
+            return Ok(None);
+        }
+        if !crate::flags::allow_root() {
+            return Err(BeansError::RunningAsRoot {
+                location: match result.source {
+                    OwnerSource::ModFolder => mod_location,
+                    _ => sourcemods_location
+                },
+                uid: result.uid,
+                gid: result.gid
+            });
+        }
+        log::info!("[InstallOwner::get] Running as root, so files will be owned by {}:{} ({})", result.uid, result.gid, result.source);
+        Ok(Some(result))
+    }
+    #[cfg(not(target_os = "linux"))]
+    pub fn get(_sourcemods_location: String) -> Result<Option<Self>, BeansError>
+    {
+        // ownership is only fixed on linux
+        Ok(None)
+    }
+
+    /// Change the owner of `location`, and everything in it, to this user. Symlinks are changed
+    /// instead of what they point to, and are never followed (see `helper::nofollow::walk`).
+    /// Returns the amount of files that were changed.
+    #[cfg(target_os = "linux")]
+    pub fn apply(&self, location: String) -> Result<usize, BeansError>
+    {
+        let mut count: usize = 0;
+        let result = crate::helper::nofollow::walk(std::path::Path::new(&location), |entry| {
+            if entry.stat.st_uid == self.uid && entry.stat.st_gid == self.gid {
+                return Ok(());
+            }
+            // SAFETY: name is a valid C string.
+            if unsafe { libc::fchownat(entry.dir, entry.name.as_ptr(), self.uid, self.gid, libc::AT_SYMLINK_NOFOLLOW) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            log::trace!("[InstallOwner::apply] Changed owner of {}/{}", location, entry.relative.to_string_lossy());
+            count += 1;
+            Ok(())
+        });
+        if let Err((failed_location, error)) = result {
+            return Err(BeansError::OwnershipSetFailure {
+                location: failed_location.to_string_lossy().to_string(),
+                uid: self.uid,
+                gid: self.gid,
+                error
+            });
+        }
+        if count > 0 {
+            log::info!("[InstallOwner::apply] Changed the owner of {} file(s) in {} to {}:{}", count, location, self.uid, self.gid);
+        }
+        Ok(count)
+    }
+    #[cfg(not(target_os = "linux"))]
+    pub fn apply(&self, _location: String) -> Result<usize, BeansError>
+    {
+        Ok(0)
+    }
+}
+
+/// Gives everything that a workflow wrote back to the owner of the install once it's done, when
+/// running as root (see `InstallOwner`). This covers the mod folder, and the staging folder that
+/// butler uses when patching.
+#[derive(Debug, Clone)]
+pub struct OwnershipRepair
+{
+    pub owner: Option<InstallOwner>,
+    pub locations: Vec<String>
+}
+impl OwnershipRepair
+{
+    /// Find the owner of the install in `ctx`. Fails with `BeansError::RunningAsRoot` when running
+    /// as root without `--allow-root`, so this should be created before anything is written.
+    pub fn new(ctx: &mut RunnerContext) -> Result<Self, BeansError>
+    {
+        Ok(Self {
+            owner: InstallOwner::get(ctx.sourcemod_path.clone())?,
+            locations: vec![ctx.get_mod_location(), ctx.get_staging_location()]
+        })
+    }
+
+    /// Run `workflow`, then give the files back to the owner (even when it failed). See `finish`.
+    pub async fn run<F, T>(self, workflow: F) -> Result<T, BeansError>
+        where F: Future<Output = Result<T, BeansError>>
+    {
+        let result = workflow.await;
+        self.finish(result)
+    }
+
+    /// Give the files back to the owner once a workflow has finished with `result`. When that
+    /// fails, the error is returned instead, unless the workflow had already failed.
+    pub fn finish<T>(&self, result: Result<T, BeansError>) -> Result<T, BeansError>
+    {
+        let owner = match &self.owner {
+            Some(v) => v,
+            None => return result
+        };
+        for location in self.locations.iter() {
+            if let Err(e) = owner.apply(location.clone()) {
+                error!("[OwnershipRepair::finish] {:}", e);
+                if result.is_ok() {
+                    return Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    /// Add what `finish` would do to `plan`. The staging folder is only included when it exists.
+    pub fn plan(&self, plan: &mut ActionPlan)
+    {
+        let owner = match &self.owner {
+            Some(v) => v,
+            None => return
+        };
+        for (i, location) in self.locations.iter().enumerate() {
+            if i > 0 && !crate::helper::file_exists(location.clone()) {
+                continue;
+            }
+            plan.add(PlannedAction::ChangeOwner {
+                location: location.clone(),
+                uid: owner.uid,
+                gid: owner.gid
+            });
+        }
+    }
+}
\ No newline at end of file
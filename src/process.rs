@@ -0,0 +1,127 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use log::{debug, info};
+use sysinfo::{ProcessRefreshKind, System, UpdateKind};
+use crate::{BeansError, flags};
+
+/// Names of the game and dedicated server processes that keep the mod files open.
+pub const GAME_PROCESS_NAMES: &[&str] = &[
+    "hl2_linux",
+    "hl2_linux64",
+    "hl2.exe",
+    "hl2_win64.exe",
+    "srcds_linux",
+    "srcds_linux64",
+    "srcds.exe",
+    "srcds_win64.exe"
+];
+/// How often to check if the game has exited when `LaunchFlag::WAIT_FOR_EXIT` is set.
+pub const WAIT_FOR_EXIT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Game or dedicated server process that is using the mod.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct GameProcess
+{
+    pub pid: u32,
+    pub name: String,
+    /// Why this process is using the mod (the working directory or the `-game` argument).
+    pub reason: String
+}
+impl std::fmt::Display for GameProcess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (pid {}, {})", self.name, self.pid, self.reason)
+    }
+}
+
+/// Find the game and dedicated server processes that are using the mod at `mod_location`.
+/// A process is using the mod when its working directory is inside `mod_location`, or when its
+/// `-game` argument points to `mod_location`.
+pub fn find_game_processes(mod_location: String) -> Vec<GameProcess>
+{
+    let mod_path = normalize(Path::new(&mod_location));
+    let mut sys = System::new();
+    sys.refresh_processes_specifics(ProcessRefreshKind::new()
+        .with_cmd(UpdateKind::Always)
+        .with_cwd(UpdateKind::Always));
+
+    let mut result: Vec<GameProcess> = Vec::new();
+    for (pid, process) in sys.processes().iter() {
+        let name = process.name().to_string();
+        if !GAME_PROCESS_NAMES.iter().any(|v| v.eq_ignore_ascii_case(&name)) {
+            continue;
+        }
+        let cwd = process.cwd().map(normalize);
+        let reason = match get_game_arg(process.cmd()) {
+            Some(game) => {
+                let game_path = match (Path::new(&game).is_absolute(), &cwd) {
+                    (false, Some(c)) => normalize(&c.join(&game)),
+                    _ => normalize(Path::new(&game))
+                };
+                match game_path == mod_path {
+                    true => Some(format!("-game {}", game)),
+                    false => None
+                }
+            },
+            None => None
+        }.or_else(|| match &cwd {
+            Some(c) if c.starts_with(&mod_path) => Some(format!("running in {}", c.display())),
+            _ => None
+        });
+        debug!("[process::find_game_processes] {} (pid {}) cwd={:?} cmd={:?} match={:?}",
+            name, pid, cwd, process.cmd(), reason);
+        if let Some(reason) = reason {
+            result.push(GameProcess {
+                pid: pid.as_u32(),
+                name,
+                reason
+            });
+        }
+    }
+    result.sort_by_key(|v| v.pid);
+    result
+}
+
+/// Make sure that the game and dedicated server aren't using the mod at `mod_location`, since
+/// changing the files while they're open will fail or corrupt the install.
+///
+/// When `LaunchFlag::WAIT_FOR_EXIT` is set, this waits until they have exited. Otherwise,
+/// `BeansError::GameRunning` is returned.
+pub async fn ensure_game_not_running(mod_location: String) -> Result<(), BeansError>
+{
+    let mut processes = find_game_processes(mod_location.clone());
+    if processes.is_empty() {
+        return Ok(());
+    }
+    if !flags::wait_for_exit() {
+        return Err(BeansError::GameRunning {
+            location: mod_location,
+            processes
+        });
+    }
+    let list: Vec<String> = processes.iter().map(|v| v.to_string()).collect();
+    println!("Waiting for the game to exit before continuing: {}", list.join(", "));
+    while !processes.is_empty() {
+        tokio::time::sleep(WAIT_FOR_EXIT_INTERVAL).await;
+        processes = find_game_processes(mod_location.clone());
+    }
+    info!("[process::ensure_game_not_running] The game has exited, continuing");
+    Ok(())
+}
+
+/// Get the value of the `-game` argument.
+fn get_game_arg(cmd: &[String]) -> Option<String>
+{
+    let index = cmd.iter().position(|v| v.eq_ignore_ascii_case("-game"))?;
+    cmd.get(index + 1).cloned()
+}
+
+/// Resolve `path` to an absolute path without a trailing separator, so it can be compared with
+/// other paths. Symlinks are resolved when the path exists.
+fn normalize(path: &Path) -> PathBuf
+{
+    let resolved = match std::fs::canonicalize(path) {
+        Ok(v) => v,
+        Err(_) => path.to_path_buf()
+    };
+    resolved.components().collect()
+}
\ No newline at end of file
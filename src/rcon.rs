@@ -0,0 +1,435 @@
+use std::backtrace::Backtrace;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use log::{debug, info, warn};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use crate::{BeansError, RconFailureReason};
+
+pub const SERVERDATA_AUTH: i32 = 3;
+pub const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+pub const SERVERDATA_EXECCOMMAND: i32 = 2;
+pub const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+/// Largest packet that the Source engine will send or accept.
+pub const MAX_PACKET_SIZE: i32 = 4096;
+/// How long to wait when connecting, reading or writing.
+pub const RCON_TIMEOUT: Duration = Duration::from_secs(10);
+/// Environment variable that overrides `RconConfig.password`, so it doesn't have to be saved in
+/// the config file.
+pub const RCON_PASSWORD_ENV: &str = "BEANS_RCON_PASSWORD";
+
+/// Default value for `RconConfig.countdown`, in seconds.
+pub const DEFAULT_COUNTDOWN: u64 = 60;
+/// Default value for `RconConfig.message`. `{seconds}` is replaced with the time left.
+pub const DEFAULT_COUNTDOWN_MESSAGE: &str = "The server is restarting for an update in {seconds} seconds";
+/// Default value for `RconConfig.exit_timeout`, in seconds.
+pub const DEFAULT_EXIT_TIMEOUT: u64 = 60;
+/// Seconds left in the countdown when the message is sent again.
+pub const COUNTDOWN_ANNOUNCEMENTS: [u64; 6] = [300, 120, 60, 30, 10, 5];
+
+/// Details for stopping the dedicated server with RCON before it's updated. Read from the `rcon`
+/// property in the config file (see `BeansConfig`).
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RconConfig
+{
+    /// Address of the server (e.g; `127.0.0.1:27015`)
+    pub address: String,
+    /// Value of `rcon_password` on the server. Can be set with `RCON_PASSWORD_ENV` instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Seconds to warn players for before the server is stopped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub countdown: Option<u64>,
+    /// Message that is sent with `say` during the countdown. `{seconds}` is replaced with the
+    /// time left.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Seconds to wait for the server to exit after `quit` is sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_timeout: Option<u64>
+}
+impl RconConfig
+{
+    /// Get the RCON config for the install in `sourcemods_location`, when it has been set.
+    pub fn get(sourcemods_location: String) -> Option<Self>
+    {
+        crate::config::BeansConfig::for_install(&sourcemods_location).rcon
+    }
+    /// Get the password, preferring `RCON_PASSWORD_ENV` over `password`.
+    pub fn get_password(&self) -> String
+    {
+        match std::env::var(RCON_PASSWORD_ENV) {
+            Ok(v) => v,
+            Err(_) => self.password.clone().unwrap_or_default()
+        }
+    }
+}
+
+/// Client for the Source RCON protocol.
+/// https://developer.valvesoftware.com/wiki/Source_RCON_Protocol
+#[derive(Debug)]
+pub struct RconClient
+{
+    pub address: String,
+    stream: TcpStream,
+    next_id: i32
+}
+impl RconClient
+{
+    /// Connect to `address` and authenticate with `password`.
+    pub async fn connect(address: String, password: String) -> Result<Self, BeansError>
+    {
+        let stream = match tokio::time::timeout(RCON_TIMEOUT, TcpStream::connect(address.as_str())).await {
+            Ok(Ok(v)) => v,
+            Ok(Err(e)) => return Err(rcon_error(&address, RconFailureReason::Connect {
+                error: e
+            })),
+            Err(_) => return Err(rcon_error(&address, RconFailureReason::Connect {
+                error: std::io::Error::new(std::io::ErrorKind::TimedOut, "Timed out while connecting")
+            }))
+        };
+
+        let mut client = Self {
+            address: address.clone(),
+            stream,
+            next_id: 1
+        };
+        let id = client.send(SERVERDATA_AUTH, &password).await?;
+        // an empty SERVERDATA_RESPONSE_VALUE is sent before SERVERDATA_AUTH_RESPONSE.
+        loop {
+            let (response_id, kind, _) = client.receive().await?;
+            if kind != SERVERDATA_AUTH_RESPONSE {
+                continue;
+            }
+            if response_id == -1 || response_id != id {
+                return Err(rcon_error(&address, RconFailureReason::AuthFailed));
+            }
+            break;
+        }
+        debug!("[RconClient::connect] Authenticated with {}", address);
+        Ok(client)
+    }
+
+    /// Run `command` on the server, and return the response.
+    pub async fn exec(&mut self, command: &str) -> Result<String, BeansError>
+    {
+        let id = self.send(SERVERDATA_EXECCOMMAND, command).await?;
+        // responses can be split over multiple packets, so an empty command is sent after it.
+        // the server answers in order, so everything before the reply to it is our response.
+        let end_id = self.send(SERVERDATA_RESPONSE_VALUE, "").await?;
+        let mut result = String::new();
+        loop {
+            let (response_id, kind, body) = self.receive().await?;
+            if response_id == end_id {
+                break;
+            }
+            if response_id == id && kind == SERVERDATA_RESPONSE_VALUE {
+                result.push_str(&body);
+            }
+        }
+        debug!("[RconClient::exec] {} -> {}", command, result);
+        Ok(result)
+    }
+
+    /// Run `command` without waiting for a response. Used for `quit`, since the server closes
+    /// the connection without replying.
+    pub async fn exec_no_response(&mut self, command: &str) -> Result<(), BeansError>
+    {
+        self.send(SERVERDATA_EXECCOMMAND, command).await?;
+        Ok(())
+    }
+
+    /// Write a packet, and return its ID.
+    async fn send(&mut self, kind: i32, body: &str) -> Result<i32, BeansError>
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+        let body = body.as_bytes();
+        // id, type, body and two null terminators.
+        let size = 4 + 4 + body.len() as i32 + 2;
+        if size > MAX_PACKET_SIZE {
+            return Err(rcon_error(&self.address, RconFailureReason::InvalidPacket {
+                message: format!("Command is too long ({} bytes)", body.len())
+            }));
+        }
+        let mut packet: Vec<u8> = Vec::with_capacity(size as usize + 4);
+        packet.extend_from_slice(&size.to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&kind.to_le_bytes());
+        packet.extend_from_slice(body);
+        packet.extend_from_slice(&[0, 0]);
+        with_timeout(&self.address, self.stream.write_all(&packet)).await?;
+        Ok(id)
+    }
+
+    /// Read a packet, and return the ID, type and body of it.
+    async fn receive(&mut self) -> Result<(i32, i32, String), BeansError>
+    {
+        let mut size_buffer = [0u8; 4];
+        with_timeout(&self.address, self.stream.read_exact(&mut size_buffer)).await?;
+        let size = i32::from_le_bytes(size_buffer);
+        if !(10..=MAX_PACKET_SIZE).contains(&size) {
+            return Err(rcon_error(&self.address, RconFailureReason::InvalidPacket {
+                message: format!("Invalid packet size {}", size)
+            }));
+        }
+        let mut buffer = vec![0u8; size as usize];
+        with_timeout(&self.address, self.stream.read_exact(&mut buffer)).await?;
+        let id = i32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+        let kind = i32::from_le_bytes([buffer[4], buffer[5], buffer[6], buffer[7]]);
+        let body = String::from_utf8_lossy(&buffer[8..buffer.len() - 2]).to_string();
+        Ok((id, kind, body))
+    }
+}
+
+/// Warn the players on the server with a countdown, then stop it with `quit` and wait for the
+/// server process to exit. Used before updating, so players aren't kicked without warning.
+pub async fn shutdown_server(config: &RconConfig, mod_location: String) -> Result<(), BeansError>
+{
+    let countdown = config.countdown.unwrap_or(DEFAULT_COUNTDOWN);
+    let message = config.message.clone().unwrap_or(DEFAULT_COUNTDOWN_MESSAGE.to_string());
+    let mut client = RconClient::connect(config.address.clone(), config.get_password()).await?;
+
+    info!("[rcon::shutdown_server] Stopping the server at {} in {}s", config.address, countdown);
+    let mut remaining = countdown;
+    for at in announcements(countdown).into_iter() {
+        tokio::time::sleep(Duration::from_secs(remaining - at)).await;
+        remaining = at;
+        let text = message.replace("{seconds}", &at.to_string());
+        // quotes would end the argument early, so they're swapped for single quotes.
+        client.exec(&format!("say \"{}\"", text.replace('"', "'"))).await?;
+    }
+    tokio::time::sleep(Duration::from_secs(remaining)).await;
+    info!("[rcon::shutdown_server] Sending quit to {}", config.address);
+    client.exec_no_response("quit").await?;
+
+    let exit_timeout = Duration::from_secs(config.exit_timeout.unwrap_or(DEFAULT_EXIT_TIMEOUT));
+    let started = Instant::now();
+    loop {
+        let processes = crate::process::find_game_processes(mod_location.clone());
+        if processes.is_empty() {
+            info!("[rcon::shutdown_server] The server has exited");
+            return Ok(());
+        }
+        if started.elapsed() > exit_timeout {
+            warn!("[rcon::shutdown_server] Server is still running after {}s", exit_timeout.as_secs());
+            return Err(rcon_error(&config.address, RconFailureReason::ServerDidNotExit {
+                timeout: exit_timeout.as_secs()
+            }));
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Same as `shutdown_server`, but when nothing is listening on the address the server is assumed
+/// to not be running, and nothing is done. Returns `false` when the server wasn't running.
+pub async fn shutdown_server_if_running(config: &RconConfig, mod_location: String) -> Result<bool, BeansError>
+{
+    match shutdown_server(config, mod_location).await {
+        Ok(()) => Ok(true),
+        Err(BeansError::RconFailure { reason: RconFailureReason::Connect { error }, .. }) => {
+            info!("[rcon::shutdown_server_if_running] Couldn't connect to {}, assuming that the server isn't running ({:})", config.address, error);
+            Ok(false)
+        },
+        Err(e) => Err(e)
+    }
+}
+
+/// Seconds left in a countdown of `countdown` seconds when the players are warned. At the start,
+/// then at every item in `COUNTDOWN_ANNOUNCEMENTS` that is shorter than the countdown.
+fn announcements(countdown: u64) -> Vec<u64>
+{
+    let mut result: Vec<u64> = vec![countdown];
+    result.extend(COUNTDOWN_ANNOUNCEMENTS.iter().filter(|v| **v < countdown));
+    result
+}
+
+/// Wait for `io` (reading or writing to the server) for up to `RCON_TIMEOUT`.
+async fn with_timeout<T, F>(address: &str, io: F) -> Result<T, BeansError>
+    where F: Future<Output = std::io::Result<T>>
+{
+    match tokio::time::timeout(RCON_TIMEOUT, io).await {
+        Ok(Ok(v)) => Ok(v),
+        Ok(Err(e)) => Err(rcon_error(address, RconFailureReason::Io {
+            error: e
+        })),
+        Err(_) => Err(rcon_error(address, RconFailureReason::Io {
+            error: std::io::Error::new(std::io::ErrorKind::TimedOut, format!("No response after {}s", RCON_TIMEOUT.as_secs()))
+        }))
+    }
+}
+
+fn rcon_error(address: &str, reason: RconFailureReason) -> BeansError
+{
+    BeansError::RconFailure {
+        address: address.to_string(),
+        reason,
+        backtrace: Backtrace::capture()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+
+    /// Stand-in for the RCON server in a dedicated server. Accepts one connection, and records
+    /// every command that is run. Responses are split over two packets, like the Source engine
+    /// does with long responses.
+    struct StandInServer
+    {
+        address: String,
+        commands: Arc<Mutex<Vec<String>>>,
+        thread: std::thread::JoinHandle<()>
+    }
+    impl StandInServer
+    {
+        fn start(password: &'static str) -> Self
+        {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap().to_string();
+            let commands: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+            let recorded = commands.clone();
+            let thread = std::thread::spawn(move || {
+                let (mut stream, _) = listener.accept().unwrap();
+                while let Some((id, kind, body)) = read_packet(&mut stream) {
+                    match kind {
+                        SERVERDATA_AUTH => {
+                            write_packet(&mut stream, id, SERVERDATA_RESPONSE_VALUE, "");
+                            let response_id = if body == password { id } else { -1 };
+                            write_packet(&mut stream, response_id, SERVERDATA_AUTH_RESPONSE, "");
+                        },
+                        SERVERDATA_EXECCOMMAND => {
+                            recorded.lock().unwrap().push(body.clone());
+                            if body == "quit" {
+                                return;
+                            }
+                            write_packet(&mut stream, id, SERVERDATA_RESPONSE_VALUE, "ran: ");
+                            write_packet(&mut stream, id, SERVERDATA_RESPONSE_VALUE, &body);
+                        },
+                        _ => write_packet(&mut stream, id, SERVERDATA_RESPONSE_VALUE, "")
+                    }
+                }
+            });
+            Self {
+                address,
+                commands,
+                thread
+            }
+        }
+        fn commands(self) -> Vec<String>
+        {
+            self.thread.join().unwrap();
+            self.commands.lock().unwrap().clone()
+        }
+    }
+    fn read_packet(stream: &mut TcpStream) -> Option<(i32, i32, String)>
+    {
+        let mut size = [0u8; 4];
+        stream.read_exact(&mut size).ok()?;
+        let mut buffer = vec![0u8; i32::from_le_bytes(size) as usize];
+        stream.read_exact(&mut buffer).ok()?;
+        let id = i32::from_le_bytes(buffer[0..4].try_into().unwrap());
+        let kind = i32::from_le_bytes(buffer[4..8].try_into().unwrap());
+        Some((id, kind, String::from_utf8_lossy(&buffer[8..buffer.len() - 2]).to_string()))
+    }
+    fn write_packet(stream: &mut TcpStream, id: i32, kind: i32, body: &str)
+    {
+        let mut packet: Vec<u8> = Vec::new();
+        packet.extend_from_slice(&(10 + body.len() as i32).to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&kind.to_le_bytes());
+        packet.extend_from_slice(body.as_bytes());
+        packet.extend_from_slice(&[0, 0]);
+        stream.write_all(&packet).unwrap();
+    }
+    fn config(address: String, countdown: u64) -> RconConfig
+    {
+        RconConfig {
+            address,
+            password: Some(String::from("hunter2")),
+            countdown: Some(countdown),
+            message: Some(String::from("Restarting in {seconds} seconds, \"sorry\"")),
+            exit_timeout: Some(1)
+        }
+    }
+    /// Address that nothing is listening on.
+    fn unused_address() -> String
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn auth_and_exec()
+    {
+        let server = StandInServer::start("hunter2");
+        let mut client = RconClient::connect(server.address.clone(), String::from("hunter2")).await.unwrap();
+        assert_eq!(client.exec("status").await.unwrap(), "ran: status");
+        assert_eq!(client.exec("users").await.unwrap(), "ran: users");
+        client.exec_no_response("quit").await.unwrap();
+        assert_eq!(server.commands(), vec!["status", "users", "quit"]);
+    }
+
+    #[tokio::test]
+    async fn auth_rejected()
+    {
+        let server = StandInServer::start("hunter2");
+        let err = RconClient::connect(server.address.clone(), String::from("wrong")).await.unwrap_err();
+        assert!(matches!(err, BeansError::RconFailure { reason: RconFailureReason::AuthFailed, .. }));
+        assert_eq!(err.exit_code(), crate::BeansExitCode::Rcon);
+        assert_eq!(err.exit_code() as i32, 91);
+        drop(err);
+        assert!(server.commands().is_empty());
+    }
+
+    #[tokio::test]
+    async fn connection_refused()
+    {
+        let err = RconClient::connect(unused_address(), String::from("hunter2")).await.unwrap_err();
+        assert!(matches!(err, BeansError::RconFailure { reason: RconFailureReason::Connect { .. }, .. }));
+    }
+
+    #[tokio::test]
+    async fn shutdown_when_not_running()
+    {
+        let config = config(unused_address(), 0);
+        let stopped = shutdown_server_if_running(&config, String::from("/nonexistent/open_fortress")).await.unwrap();
+        assert!(!stopped);
+    }
+
+    #[tokio::test]
+    async fn shutdown_with_wrong_password()
+    {
+        let server = StandInServer::start("something else");
+        let config = config(server.address.clone(), 0);
+        let err = shutdown_server_if_running(&config, String::from("/nonexistent/open_fortress")).await.unwrap_err();
+        assert_eq!(err.exit_code(), crate::BeansExitCode::Rcon);
+        assert!(server.commands().is_empty());
+    }
+
+    #[tokio::test]
+    async fn shutdown_then_quit()
+    {
+        let server = StandInServer::start("hunter2");
+        let config = config(server.address.clone(), 0);
+        let stopped = shutdown_server_if_running(&config, String::from("/nonexistent/open_fortress")).await.unwrap();
+        assert!(stopped);
+        assert_eq!(server.commands(), vec![
+            "say \"Restarting in 0 seconds, 'sorry'\"",
+            "quit"
+        ]);
+    }
+
+    #[test]
+    fn countdown_announcements()
+    {
+        assert_eq!(announcements(12), vec![12, 10, 5]);
+        assert_eq!(announcements(60), vec![60, 30, 10, 5]);
+        assert_eq!(announcements(600), vec![600, 300, 120, 60, 30, 10, 5]);
+        assert_eq!(announcements(5), vec![5]);
+        assert_eq!(announcements(0), vec![0]);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,328 @@
+use std::backtrace::Backtrace;
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use log::{debug, info, warn};
+use reqwest::header::USER_AGENT;
+use sha2::{Digest, Sha256};
+use crate::{BeansError, helper, SelfUpdateFailureReason};
+use crate::helper::{GithubReleaseAsset, GithubReleaseItem};
+
+/// Name of the release asset for the current platform.
+#[cfg(target_os = "windows")]
+pub const RELEASE_ASSET_NAME: &str = "beans-rs.exe";
+#[cfg(not(target_os = "windows"))]
+pub const RELEASE_ASSET_NAME: &str = "beans-rs";
+/// Extension of the release asset that has the SHA256 hash for `RELEASE_ASSET_NAME`. Only used
+/// when GitHub didn't provide a digest for the asset.
+pub const HASH_ASSET_EXTENSION: &str = ".sha256";
+/// Extension that is added to the current executable when it's replaced.
+pub const BACKUP_EXTENSION: &str = ".bak";
+/// How long to wait for the new executable to respond to `--version` before restoring the backup.
+pub const SMOKE_TEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Download the release asset for the current platform from `release`, check its hash, then
+/// replace the running executable with it.
+///
+/// The current executable is kept with `BACKUP_EXTENSION` added to it. When the new executable
+/// fails to run with `--version`, the backup is restored and an error is returned.
+pub async fn update(release: GithubReleaseItem) -> Result<(), BeansError>
+{
+    let asset = match release.assets.iter().find(|v| v.name == RELEASE_ASSET_NAME) {
+        Some(v) => v.clone(),
+        None => {
+            return Err(BeansError::SelfUpdateFailure {
+                reason: SelfUpdateFailureReason::AssetNotFound {
+                    tag: release.tag_name.clone(),
+                    asset_name: RELEASE_ASSET_NAME.to_string()
+                },
+                backtrace: Backtrace::capture()
+            });
+        }
+    };
+    let expected_hash = get_expected_hash(&release, &asset).await?;
+    debug!("[selfupdate::update] expected hash for {} is {}", asset.name, expected_hash);
+
+    let exe_location = std::env::current_exe()?.to_string_lossy().to_string();
+    let backup_location = format!("{}{}", exe_location, BACKUP_EXTENSION);
+    let tmp_file = helper::get_tmp_file(asset.name.clone());
+    let result = download_and_replace(&asset, tmp_file.clone(), expected_hash, exe_location.clone(), backup_location.clone()).await;
+    // the download is only needed until it's been copied over the executable, or it failed.
+    if let Err(e) = std::fs::remove_file(&tmp_file) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            warn!("[selfupdate::update] Failed to remove {} ({:})", tmp_file, e);
+        }
+    }
+    result?;
+
+    let expected_version = release.tag_name.trim_start_matches('v').to_string();
+    if let Err(reason) = smoke_test(exe_location.clone(), expected_version).await {
+        warn!("[selfupdate::update] New executable failed smoke test, restoring {}", backup_location);
+        if let Err(e) = restore_backup(exe_location.clone(), backup_location.clone()) {
+            return Err(BeansError::SelfUpdateFailure {
+                reason: SelfUpdateFailureReason::Restore {
+                    location: exe_location,
+                    error: e
+                },
+                backtrace: Backtrace::capture()
+            });
+        }
+        return Err(BeansError::SelfUpdateFailure {
+            reason,
+            backtrace: Backtrace::capture()
+        });
+    }
+
+    println!("Updated beans-rs from v{} to {}. The previous version was saved to {}", crate::VERSION, release.tag_name, backup_location);
+    Ok(())
+}
+
+/// Download `asset` to `tmp_file`, check that it has the hash `expected_hash`, then replace the
+/// executable at `exe_location` with it (see `replace_executable`). `tmp_file` is left for the
+/// caller to remove, even when this fails.
+async fn download_and_replace(asset: &GithubReleaseAsset, tmp_file: String, expected_hash: String, exe_location: String, backup_location: String) -> Result<(), BeansError>
+{
+    info!("[selfupdate::download_and_replace] Downloading {} to {}", asset.browser_download_url, tmp_file);
+    helper::download_with_progress(asset.browser_download_url.clone(), tmp_file.clone()).await?;
+
+    let actual_hash = sha256_file(tmp_file.clone())?;
+    if actual_hash != expected_hash {
+        return Err(BeansError::SelfUpdateFailure {
+            reason: SelfUpdateFailureReason::HashMismatch {
+                expected: expected_hash,
+                actual: actual_hash
+            },
+            backtrace: Backtrace::capture()
+        });
+    }
+    replace_executable(tmp_file, exe_location, backup_location)
+}
+
+/// Get the SHA256 hash (as lowercase hex) that `asset` is expected to have.
+///
+/// The `digest` from GitHub is used when it exists, otherwise the content of the
+/// `HASH_ASSET_EXTENSION` asset is used.
+async fn get_expected_hash(release: &GithubReleaseItem, asset: &GithubReleaseAsset) -> Result<String, BeansError>
+{
+    if let Some(digest) = &asset.digest {
+        if let Some(hash) = digest.strip_prefix("sha256:") {
+            return Ok(hash.to_lowercase());
+        }
+        debug!("[selfupdate::get_expected_hash] Ignoring digest {} since it's not sha256", digest);
+    }
+
+    let hash_asset_name = format!("{}{}", asset.name, HASH_ASSET_EXTENSION);
+    let hash_asset = match release.assets.iter().find(|v| v.name == hash_asset_name) {
+        Some(v) => v,
+        None => {
+            return Err(BeansError::SelfUpdateFailure {
+                reason: SelfUpdateFailureReason::HashMissing {
+                    asset_name: asset.name.clone()
+                },
+                backtrace: Backtrace::capture()
+            });
+        }
+    };
+    let content = reqwest::Client::new()
+        .get(&hash_asset.browser_download_url)
+        .header(USER_AGENT, &format!("beans-rs/{}", crate::VERSION))
+        .send().await?
+        .error_for_status()?
+        .text().await?;
+    // same format as sha256sum, which is the hash then the filename.
+    match content.split_whitespace().next() {
+        Some(v) if v.len() == 64 && v.chars().all(|c| c.is_ascii_hexdigit()) => Ok(v.to_lowercase()),
+        _ => Err(BeansError::SelfUpdateFailure {
+            reason: SelfUpdateFailureReason::HashMissing {
+                asset_name: asset.name.clone()
+            },
+            backtrace: Backtrace::capture()
+        })
+    }
+}
+
+/// Calculate the SHA256 hash of the file at `location`, as lowercase hex.
+pub fn sha256_file(location: String) -> Result<String, BeansError>
+{
+    let mut file = match std::fs::File::open(&location) {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(BeansError::FileOpenFailure {
+                location,
+                error: e
+            });
+        }
+    };
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 65536];
+    loop {
+        let count = file.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+        hasher.update(&buffer[..count]);
+    }
+    let mut result = String::new();
+    for b in hasher.finalize().iter() {
+        result.push_str(&format!("{:02x}", b));
+    }
+    Ok(result)
+}
+
+/// Move the executable at `exe_location` to `backup_location`, then copy `source` to
+/// `exe_location`. Renaming is used for the current executable since Windows doesn't let a
+/// running executable be deleted or overwritten, but does let it be renamed.
+fn replace_executable(source: String, exe_location: String, backup_location: String) -> Result<(), BeansError>
+{
+    if helper::file_exists(backup_location.clone()) {
+        if let Err(e) = std::fs::remove_file(&backup_location) {
+            return Err(BeansError::SelfUpdateFailure {
+                reason: SelfUpdateFailureReason::Replace {
+                    location: backup_location,
+                    error: e
+                },
+                backtrace: Backtrace::capture()
+            });
+        }
+    }
+    if let Err(e) = std::fs::rename(&exe_location, &backup_location) {
+        return Err(BeansError::SelfUpdateFailure {
+            reason: SelfUpdateFailureReason::Replace {
+                location: exe_location,
+                error: e
+            },
+            backtrace: Backtrace::capture()
+        });
+    }
+    if let Err(e) = std::fs::copy(&source, &exe_location).and_then(|_| set_executable(&exe_location)) {
+        let _ = restore_backup(exe_location.clone(), backup_location);
+        return Err(BeansError::SelfUpdateFailure {
+            reason: SelfUpdateFailureReason::Replace {
+                location: exe_location,
+                error: e
+            },
+            backtrace: Backtrace::capture()
+        });
+    }
+    Ok(())
+}
+
+/// Put the executable at `backup_location` back to `exe_location`.
+fn restore_backup(exe_location: String, backup_location: String) -> Result<(), std::io::Error>
+{
+    if helper::file_exists(exe_location.clone()) {
+        std::fs::remove_file(&exe_location)?;
+    }
+    std::fs::rename(&backup_location, &exe_location)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn set_executable(location: &str) -> Result<(), std::io::Error>
+{
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(location, std::fs::Permissions::from_mode(0o755))
+}
+#[cfg(target_os = "windows")]
+fn set_executable(_location: &str) -> Result<(), std::io::Error>
+{
+    Ok(())
+}
+
+/// Run the executable at `location` with `--version`, and make sure that it exits successfully
+/// and prints `expected_version`. The child is polled with `tokio::time::sleep`, so the runtime
+/// isn't blocked while waiting for it.
+async fn smoke_test(location: String, expected_version: String) -> Result<(), SelfUpdateFailureReason>
+{
+    let mut child = Command::new(&location)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| SelfUpdateFailureReason::SmokeTest {
+            message: format!("Failed to run {} ({:})", location, e)
+        })?;
+
+    let started = tokio::time::Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(v)) => break v,
+            Ok(None) if started.elapsed() > SMOKE_TEST_TIMEOUT => {
+                let _ = child.kill();
+                return Err(SelfUpdateFailureReason::SmokeTest {
+                    message: format!("Timed out after {}s", SMOKE_TEST_TIMEOUT.as_secs())
+                });
+            },
+            Ok(None) => tokio::time::sleep(Duration::from_millis(100)).await,
+            Err(e) => {
+                return Err(SelfUpdateFailureReason::SmokeTest {
+                    message: format!("Failed to wait for {} ({:})", location, e)
+                });
+            }
+        }
+    };
+    let mut output = String::new();
+    if let Some(mut stdout) = child.stdout.take() {
+        let _ = stdout.read_to_string(&mut output);
+    }
+    debug!("[selfupdate::smoke_test] {} exited with {:?}: {}", location, status, output);
+    if !status.success() {
+        return Err(SelfUpdateFailureReason::SmokeTest {
+            message: format!("Exited with {}", status)
+        });
+    }
+    if !output.contains(&expected_version) {
+        return Err(SelfUpdateFailureReason::SmokeTest {
+            message: format!("Expected version {}, but it printed \"{}\"", expected_version, output.trim())
+        });
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::helper::test_dir::TestDir;
+
+    /// Write a shell script to `dir` that is used as the new executable.
+    fn script(dir: &TestDir, name: &str, content: &str) -> String
+    {
+        let location = dir.join(name);
+        std::fs::write(&location, format!("#!/bin/sh\n{}\n", content)).unwrap();
+        let location = location.to_string_lossy().to_string();
+        set_executable(&location).unwrap();
+        location
+    }
+
+    #[tokio::test]
+    async fn smoke_test_passes() {
+        let dir = TestDir::new("selfupdate-passes");
+        let location = script(&dir, "beans-rs", "echo \"beans-rs 1.5.0\"");
+        assert!(smoke_test(location, "1.5.0".to_string()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn smoke_test_wrong_version_or_exit_code() {
+        let dir = TestDir::new("selfupdate-wrong");
+        let location = script(&dir, "wrong_version", "echo \"beans-rs 1.4.5\"");
+        let result = smoke_test(location, "1.5.0".to_string()).await;
+        assert!(matches!(result, Err(SelfUpdateFailureReason::SmokeTest { .. })));
+
+        let location = script(&dir, "exit_code", "echo \"beans-rs 1.5.0\"; exit 3");
+        let result = smoke_test(location, "1.5.0".to_string()).await;
+        assert!(matches!(result, Err(SelfUpdateFailureReason::SmokeTest { .. })));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn smoke_test_times_out() {
+        // time only moves forward while smoke_test is sleeping, so this only finishes quickly
+        // when it doesn't block the runtime.
+        let dir = TestDir::new("selfupdate-times-out");
+        let location = script(&dir, "times_out", "sleep 120");
+        let result = smoke_test(location, "1.5.0".to_string()).await;
+        match result {
+            Err(SelfUpdateFailureReason::SmokeTest { message }) => assert!(message.starts_with("Timed out")),
+            v => panic!("expected a timeout, got {:?}", v)
+        }
+    }
+}
\ No newline at end of file
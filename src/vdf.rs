@@ -0,0 +1,276 @@
+//! Reader and writer for the binary KeyValues format that Steam uses for files like
+//! `userdata/<id>/config/shortcuts.vdf`.
+//!
+//! Every item starts with a single byte for the type, then the key as a null-terminated string,
+//! then the value. Maps are terminated with `TYPE_MAP_END`.
+
+/// Item that contains more items, terminated with `TYPE_MAP_END`
+const TYPE_MAP: u8 = 0x00;
+/// Null-terminated string
+const TYPE_STRING: u8 = 0x01;
+/// Little-endian 32-bit integer
+const TYPE_INT32: u8 = 0x02;
+/// Little-endian 32-bit float
+const TYPE_FLOAT32: u8 = 0x03;
+/// Little-endian 64-bit unsigned integer
+const TYPE_UINT64: u8 = 0x07;
+/// End of the current map
+const TYPE_MAP_END: u8 = 0x08;
+/// Little-endian 64-bit signed integer
+const TYPE_INT64: u8 = 0x0A;
+
+/// Ordered list of key/value pairs. Order is kept so files are written back the same way that
+/// Steam wrote them.
+pub type VdfMap = Vec<(String, VdfValue)>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VdfValue
+{
+    Map(VdfMap),
+    String(String),
+    Int32(i32),
+    Float32(f32),
+    UInt64(u64),
+    Int64(i64)
+}
+impl VdfValue
+{
+    pub fn as_map(&self) -> Option<&VdfMap>
+    {
+        match self {
+            VdfValue::Map(v) => Some(v),
+            _ => None
+        }
+    }
+    pub fn as_map_mut(&mut self) -> Option<&mut VdfMap>
+    {
+        match self {
+            VdfValue::Map(v) => Some(v),
+            _ => None
+        }
+    }
+    pub fn as_str(&self) -> Option<&str>
+    {
+        match self {
+            VdfValue::String(v) => Some(v.as_str()),
+            _ => None
+        }
+    }
+}
+
+/// Get the value for `key` in `map`. Steam isn't consistent with the casing of keys
+/// (e.g; `AppName` and `appname`), so the comparison is case-insensitive.
+pub fn get<'a>(map: &'a VdfMap, key: &str) -> Option<&'a VdfValue>
+{
+    map.iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, v)| v)
+}
+/// Get a mutable reference to the value for `key` in `map`. See `get()`.
+pub fn get_mut<'a>(map: &'a mut VdfMap, key: &str) -> Option<&'a mut VdfValue>
+{
+    map.iter_mut()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, v)| v)
+}
+/// Set the value for `key` in `map`, replacing the existing value if there is one.
+pub fn set(map: &mut VdfMap, key: &str, value: VdfValue)
+{
+    match get_mut(map, key) {
+        Some(v) => *v = value,
+        None => map.push((key.to_string(), value))
+    }
+}
+
+/// Error that is returned by `parse()`.
+#[derive(Debug, Clone)]
+pub struct VdfParseError
+{
+    /// Position in the data where the error happened.
+    pub offset: usize,
+    pub reason: String
+}
+
+/// Parse binary VDF data. The result is the content of the root map.
+pub fn parse(data: &[u8]) -> Result<VdfMap, VdfParseError>
+{
+    let mut reader = Reader {
+        data,
+        position: 0
+    };
+    let map = reader.read_map()?;
+    // Steam ends the root map with an extra TYPE_MAP_END, which read_map() consumes. Anything
+    // after that isn't valid.
+    if reader.position < data.len() {
+        return Err(reader.error(format!("Unexpected data after the root map ({} bytes)", data.len() - reader.position)));
+    }
+    Ok(map)
+}
+
+/// Write `map` as binary VDF data. This is the inverse of `parse()`.
+pub fn write(map: &VdfMap) -> Vec<u8>
+{
+    let mut result: Vec<u8> = Vec::new();
+    write_map(&mut result, map);
+    result
+}
+
+fn write_map(out: &mut Vec<u8>, map: &VdfMap)
+{
+    for (key, value) in map.iter() {
+        let (kind, data): (u8, Vec<u8>) = match value {
+            VdfValue::Map(m) => {
+                let mut inner: Vec<u8> = Vec::new();
+                write_map(&mut inner, m);
+                (TYPE_MAP, inner)
+            },
+            VdfValue::String(s) => {
+                let mut inner = s.as_bytes().to_vec();
+                inner.push(0);
+                (TYPE_STRING, inner)
+            },
+            VdfValue::Int32(v) => (TYPE_INT32, v.to_le_bytes().to_vec()),
+            VdfValue::Float32(v) => (TYPE_FLOAT32, v.to_le_bytes().to_vec()),
+            VdfValue::UInt64(v) => (TYPE_UINT64, v.to_le_bytes().to_vec()),
+            VdfValue::Int64(v) => (TYPE_INT64, v.to_le_bytes().to_vec())
+        };
+        out.push(kind);
+        out.extend_from_slice(key.as_bytes());
+        out.push(0);
+        out.extend(data);
+    }
+    out.push(TYPE_MAP_END);
+}
+
+struct Reader<'a>
+{
+    data: &'a [u8],
+    position: usize
+}
+impl<'a> Reader<'a>
+{
+    fn error(&self, reason: String) -> VdfParseError
+    {
+        VdfParseError {
+            offset: self.position,
+            reason
+        }
+    }
+    fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], VdfParseError>
+    {
+        if self.position + length > self.data.len() {
+            return Err(self.error(format!("Expected {} bytes, but the data ended", length)));
+        }
+        let x = &self.data[self.position..self.position + length];
+        self.position += length;
+        Ok(x)
+    }
+    fn read_string(&mut self) -> Result<String, VdfParseError>
+    {
+        let remaining = &self.data[self.position..];
+        match remaining.iter().position(|b| *b == 0) {
+            Some(end) => {
+                let s = String::from_utf8_lossy(&remaining[..end]).to_string();
+                self.position += end + 1;
+                Ok(s)
+            },
+            None => Err(self.error("Unterminated string".to_string()))
+        }
+    }
+    fn read_map(&mut self) -> Result<VdfMap, VdfParseError>
+    {
+        let mut result: VdfMap = Vec::new();
+        loop {
+            let kind = self.read_bytes(1)?[0];
+            if kind == TYPE_MAP_END {
+                return Ok(result);
+            }
+            let key = self.read_string()?;
+            let value = match kind {
+                TYPE_MAP => VdfValue::Map(self.read_map()?),
+                TYPE_STRING => VdfValue::String(self.read_string()?),
+                TYPE_INT32 => VdfValue::Int32(i32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap())),
+                TYPE_FLOAT32 => VdfValue::Float32(f32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap())),
+                TYPE_UINT64 => VdfValue::UInt64(u64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap())),
+                TYPE_INT64 => VdfValue::Int64(i64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap())),
+                _ => {
+                    return Err(self.error(format!("Unknown type 0x{:02x} for key \"{}\"", kind, key)));
+                }
+            };
+            result.push((key, value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `shortcuts.vdf` with two shortcuts (one with tags, one with non-ASCII text), laid out the
+    /// same way that Steam writes it.
+    const SAMPLE_SHORTCUTS: &[u8] = include_bytes!("../tests/data/shortcuts.vdf");
+
+    #[test]
+    fn parse_sample()
+    {
+        let root = parse(SAMPLE_SHORTCUTS).unwrap();
+        let shortcuts = get(&root, "shortcuts").and_then(|v| v.as_map()).unwrap();
+        assert_eq!(shortcuts.len(), 2);
+
+        let first = get(shortcuts, "0").and_then(|v| v.as_map()).unwrap();
+        assert_eq!(get(first, "appname").and_then(|v| v.as_str()), Some("Heroic Games Launcher"));
+        assert_eq!(get(first, "appid"), Some(&VdfValue::Int32(-1785367521)));
+        assert_eq!(get(first, "LastPlayTime"), Some(&VdfValue::Int32(1714503921)));
+        let tags = get(first, "tags").and_then(|v| v.as_map()).unwrap();
+        assert_eq!(get(tags, "1").and_then(|v| v.as_str()), Some("Launchers"));
+
+        let second = get(shortcuts, "1").and_then(|v| v.as_map()).unwrap();
+        assert_eq!(get(second, "AppName").and_then(|v| v.as_str()), Some("Café Émulateur"));
+    }
+
+    #[test]
+    fn round_trip_is_byte_identical()
+    {
+        let root = parse(SAMPLE_SHORTCUTS).unwrap();
+        assert_eq!(write(&root), SAMPLE_SHORTCUTS);
+    }
+
+    #[test]
+    fn round_trip_every_type()
+    {
+        let root: VdfMap = vec![
+            (String::from("map"), VdfValue::Map(vec![
+                (String::from("string"), VdfValue::String(String::from("value"))),
+                (String::from("int32"), VdfValue::Int32(-42)),
+                (String::from("float32"), VdfValue::Float32(1.5)),
+                (String::from("uint64"), VdfValue::UInt64(u64::MAX)),
+                (String::from("int64"), VdfValue::Int64(i64::MIN)),
+                (String::from("empty"), VdfValue::Map(Vec::new()))
+            ]))
+        ];
+        let data = write(&root);
+        assert_eq!(parse(&data).unwrap(), root);
+    }
+
+    #[test]
+    fn parse_rejects_bad_data()
+    {
+        // truncated in the middle of the first shortcut
+        let err = parse(&SAMPLE_SHORTCUTS[..40]).unwrap_err();
+        assert!(err.offset <= 40);
+        // unknown type
+        assert!(parse(b"\x05key\0\x08").is_err());
+        // data after the root map
+        let mut data = SAMPLE_SHORTCUTS.to_vec();
+        data.push(0x08);
+        assert!(parse(&data).is_err());
+    }
+
+    #[test]
+    fn set_replaces_case_insensitive()
+    {
+        let mut map: VdfMap = vec![(String::from("appname"), VdfValue::String(String::from("a")))];
+        set(&mut map, "AppName", VdfValue::String(String::from("b")));
+        assert_eq!(map, vec![(String::from("appname"), VdfValue::String(String::from("b")))]);
+    }
+}
\ No newline at end of file
@@ -1,8 +1,10 @@
 use std::backtrace::Backtrace;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fs::read_to_string;
 use std::io::Write;
-use log::{debug, error, trace};
+use colored::Colorize;
+use log::{debug, error, info, trace, warn};
 use crate::helper;
 use crate::helper::{find_sourcemod_path, InstallType};
 use crate::BeansError;
@@ -27,6 +29,30 @@ pub fn get_current_version(sourcemods_location: Option<String>) -> Option<usize>
         None => None
     }
 }
+/// read and parse the .adastral file in the sourcemod mod folder. returns `None` when it
+/// doesn't exist or when it couldn't be parsed.
+pub fn get_version_file(sourcemods_location: Option<String>) -> Option<AdastralVersionFile>
+{
+    let location = get_version_location(sourcemods_location)?;
+    if !helper::file_exists(location.clone()) {
+        return None;
+    }
+    let content = match read_to_string(&location) {
+        Ok(v) => v,
+        Err(e) => {
+            debug!("[version::get_version_file] Failed to read {} {:#?}", location, e);
+            return None;
+        }
+    };
+    crate::config::warn_moved_properties(&location, &content);
+    match serde_json::from_str::<AdastralVersionFile>(&content) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            debug!("[version::get_version_file] Failed to deserialize {} {:#?}", location, e);
+            None
+        }
+    }
+}
 fn get_version_location(sourcemods_location: Option<String>) -> Option<String>
 {
     match get_mod_location(sourcemods_location) {
@@ -111,7 +137,8 @@ pub fn update_version_file(sourcemods_location: Option<String>) -> Result<(), Be
 
     let new_file_content = AdastralVersionFile
     {
-        version: old_version_idx.to_string()
+        version: old_version_idx.to_string(),
+        ..Default::default()
     };
 
     let new_version_file_location = format!("{}.adastral", &data_dir);
@@ -166,10 +193,21 @@ pub async fn get_version_list() -> Result<RemoteVersionResponse, BeansError>
     return Ok(data);
 }
 
+/// Channel that is used when one isn't set in `.adastral`, and that versions without a channel
+/// are in.
+pub const DEFAULT_CHANNEL: &str = "stable";
+
 /// Version file that is used as `.adastral` in the sourcemod mod folder.
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct AdastralVersionFile {
-    pub version: String
+    pub version: String,
+    /// Release channel that was chosen for this install. `DEFAULT_CHANNEL` is used when `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    /// Used to wait for the dedicated server to be empty before updating, see
+    /// `WaitForEmptyConfig`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait_for_empty: Option<crate::a2s::WaitForEmptyConfig>
 }
 impl AdastralVersionFile {
     pub fn write(&self, sourcemods_location: Option<String>) -> Result<(), BeansError> {
@@ -217,15 +255,168 @@ pub struct RemoteVersion
     #[serde(rename = "signature")]
     pub signature_url: Option<String>,
     #[serde(rename = "heal")]
-    pub heal_url: Option<String>
+    pub heal_url: Option<String>,
+    /// Release channel that this version is in (e.g; `stable`, `beta`, `playtest`). When `None`,
+    /// the version is in `DEFAULT_CHANNEL`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    /// Release notes for this version, formatted with Markdown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changelog: Option<String>,
+    /// URL where the full release notes for this version can be read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changelog_url: Option<String>
+}
+impl RemoteVersion
+{
+    /// Get the channel that this version is in.
+    pub fn channel(&self) -> String
+    {
+        self.channel.clone().unwrap_or(DEFAULT_CHANNEL.to_string())
+    }
+    /// Check if this version should be offered to installs that use `channel`. Versions in
+    /// `DEFAULT_CHANNEL` are offered to every channel.
+    pub fn is_in_channel(&self, channel: &str) -> bool
+    {
+        let c = self.channel();
+        c == DEFAULT_CHANNEL || c == channel
+    }
 }
 /// `versions.json` response content from remote server.
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct RemoteVersionResponse
 {
     pub versions: HashMap<usize, RemoteVersion>,
-    pub patches: HashMap<usize, RemotePatch>
+    pub patches: HashMap<usize, RemotePatch>,
+    /// Announcement from the mod team that should be shown to users.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notice: Option<RemoteNotice>,
+    /// Oldest version of beans-rs that is allowed to install, update or verify the game.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_beans_version: Option<String>,
+    /// Optional packages that can be installed on top of the game, with the add-on ID as the key.
+    /// Add-ons with an ID that isn't valid (see `is_valid_addon_id`) are ignored.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty", deserialize_with = "deserialize_addons")]
+    pub addons: HashMap<String, RemoteAddon>
+}
+impl RemoteVersionResponse
+{
+    /// Show `notice` when it applies to `current_version`.
+    pub fn show_notice(&self, current_version: Option<usize>)
+    {
+        if let Some(notice) = &self.notice {
+            if notice.applies_to(current_version) {
+                notice.show();
+            }
+        }
+    }
+
+    /// Check if the running version of beans-rs (`crate::VERSION`) is allowed to install, update
+    /// or verify the game. Returns `BeansError::BeansVersionTooOld` when it's older than
+    /// `min_beans_version`.
+    pub fn check_min_beans_version(&self) -> Result<(), BeansError>
+    {
+        if let Some(minimum) = &self.min_beans_version {
+            if compare_beans_version(crate::VERSION, minimum) == Ordering::Less {
+                return Err(BeansError::BeansVersionTooOld {
+                    current: crate::VERSION.to_string(),
+                    minimum: minimum.clone()
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the names of all the channels that versions are in. `DEFAULT_CHANNEL` is always the
+    /// first item.
+    pub fn channels(&self) -> Vec<String>
+    {
+        let mut result: Vec<String> = self.versions.values()
+            .map(|v| v.channel())
+            .filter(|v| v != DEFAULT_CHANNEL)
+            .collect();
+        result.sort();
+        result.dedup();
+        result.insert(0, DEFAULT_CHANNEL.to_string());
+        result
+    }
 }
+/// Value of the `notice` property in `RemoteVersionResponse`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RemoteNotice
+{
+    #[serde(default)]
+    pub severity: NoticeSeverity,
+    pub message: String,
+    /// Installed versions that this notice is for. When `None`, it's shown to everyone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub affected_versions: Option<Vec<usize>>
+}
+impl RemoteNotice
+{
+    /// Check if this notice should be shown for an install that has `current_version` installed.
+    /// Notices with `affected_versions` aren't shown when the game isn't installed.
+    pub fn applies_to(&self, current_version: Option<usize>) -> bool
+    {
+        match &self.affected_versions {
+            Some(versions) => current_version.is_some_and(|v| versions.contains(&v)),
+            None => true
+        }
+    }
+    /// Print the notice to the console, or log it when `LaunchFlag::JSON_OUTPUT` is set.
+    pub fn show(&self)
+    {
+        if crate::flags::json_output() {
+            match self.severity {
+                NoticeSeverity::Info => info!("[notice] {}", self.message),
+                NoticeSeverity::Warning => warn!("[notice] {}", self.message),
+                NoticeSeverity::Critical => error!("[notice] {}", self.message)
+            }
+            return;
+        }
+        let title = format!("======== Notice from {} ========", crate::appvar::parse().mod_info.name_stylized);
+        match self.severity {
+            NoticeSeverity::Info => println!("{}\n{}", title.bold(), self.message),
+            NoticeSeverity::Warning => println!("{}\n{}", title.yellow().bold(), self.message.yellow()),
+            NoticeSeverity::Critical => println!("{}\n{}", title.red().bold(), self.message.red().bold())
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NoticeSeverity
+{
+    #[default]
+    Info,
+    Warning,
+    Critical
+}
+
+/// Compare two beans-rs versions (e.g; `1.4.5` and `1.5.0`). Each part is compared as a number,
+/// and anything after a `-` or `+` (like `1.5.0-beta`) is ignored.
+pub fn compare_beans_version(a: &str, b: &str) -> Ordering
+{
+    fn parts(v: &str) -> Vec<u64>
+    {
+        v.trim()
+            .trim_start_matches('v')
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|x| x.parse::<u64>().unwrap_or(0))
+            .collect()
+    }
+    let (pa, pb) = (parts(a), parts(b));
+    for i in 0..pa.len().max(pb.len()) {
+        let o = pa.get(i).unwrap_or(&0).cmp(pb.get(i).unwrap_or(&0));
+        if o != Ordering::Equal {
+            return o;
+        }
+    }
+    Ordering::Equal
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct RemotePatch
 {
@@ -233,4 +424,219 @@ pub struct RemotePatch
     pub file: String,
     /// Amount of file space required for temporary file. Assumed to be measured in bytes.
     pub tempreq: usize
+}
+/// Check if `id` can be used as the ID of an add-on. It's used as the name of the backup folder
+/// for the add-on (see `ADDONS_BACKUP_DIRNAME`), so it has to be a single folder name with only
+/// `A-Z`, `a-z`, `0-9`, `_`, `.` and `-` in it (and can't be `.` or `..`).
+pub fn is_valid_addon_id(id: &str) -> bool
+{
+    id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+        && matches!(std::path::Path::new(id).components().collect::<Vec<_>>().as_slice(), [std::path::Component::Normal(_)])
+}
+fn deserialize_addons<'de, D>(deserializer: D) -> Result<HashMap<String, RemoteAddon>, D::Error>
+    where D: serde::Deserializer<'de>
+{
+    let mut addons: HashMap<String, RemoteAddon> = serde::Deserialize::deserialize(deserializer)?;
+    addons.retain(|id, _| {
+        let valid = is_valid_addon_id(id);
+        if !valid {
+            warn!("[version::deserialize_addons] Ignoring the add-on \"{}\", since its ID isn't valid", id);
+        }
+        valid
+    });
+    Ok(addons)
+}
+/// Value of an item in the `addons` property in `RemoteVersionResponse`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RemoteAddon
+{
+    /// Name that is shown to the user.
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub versions: HashMap<usize, RemoteAddonVersion>
+}
+impl RemoteAddon
+{
+    /// Get the ID and details of the newest version of this add-on.
+    pub fn latest_version(&self) -> Option<(usize, RemoteAddonVersion)>
+    {
+        self.versions.iter()
+            .max_by_key(|(id, _)| **id)
+            .map(|(id, v)| (*id, v.clone()))
+    }
+}
+/// Value of an item in the `versions` property in `RemoteAddon`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RemoteAddonVersion
+{
+    /// Location of the `.tar.zst` file, relative to `base_url`. The content is extracted to the
+    /// mod folder.
+    pub file: String,
+    /// Size of `file` in bytes.
+    #[serde(rename = "presz")]
+    pub pre_sz: Option<usize>,
+    /// Size of the extracted files in bytes.
+    #[serde(rename = "postsz")]
+    pub post_sz: Option<usize>,
+    /// Oldest version of the game that this add-on can be installed on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_game_version: Option<usize>
+}
+
+/// Name of the file in the sourcemod mod folder that keeps track of installed add-ons.
+pub const ADDONS_FILENAME: &str = ".adastral_addons";
+/// Name of the folder in the sourcemod mod folder that files which were replaced by an add-on are
+/// backed up to, with a folder for every add-on.
+pub const ADDONS_BACKUP_DIRNAME: &str = ".adastral_addons_backup";
+/// Content of `ADDONS_FILENAME`.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct AdastralAddonsFile
+{
+    /// Installed add-ons, with the add-on ID as the key.
+    #[serde(default)]
+    pub addons: HashMap<String, InstalledAddon>
+}
+/// Value of an item in the `addons` property in `AdastralAddonsFile`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct InstalledAddon
+{
+    pub version: usize,
+    /// Files that were extracted for this add-on, relative to the mod folder. Used to remove
+    /// the add-on.
+    pub files: Vec<String>,
+    /// Files in `files` that were already in the mod folder before the add-on was installed
+    /// (like textures from the game). They're backed up to `ADDONS_BACKUP_DIRNAME`, and restored
+    /// instead of deleted when the add-on is removed. `None` when it isn't known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overwritten: Option<Vec<String>>
+}
+impl AdastralAddonsFile
+{
+    /// Read `ADDONS_FILENAME` from the mod folder. An empty file is returned when it doesn't exist.
+    pub fn read(sourcemods_location: Option<String>) -> Result<Self, BeansError>
+    {
+        let location = match get_addons_location(sourcemods_location) {
+            Some(v) => v,
+            None => return Err(BeansError::SourceModLocationNotFound)
+        };
+        if !helper::file_exists(location.clone()) {
+            return Ok(Self::default());
+        }
+        let content = match read_to_string(&location) {
+            Ok(v) => v,
+            Err(e) => {
+                return Err(BeansError::FileOpenFailure {
+                    location,
+                    error: e
+                });
+            }
+        };
+        Ok(serde_json::from_str(&content)?)
+    }
+    /// Write to `ADDONS_FILENAME` in the mod folder. The file is deleted when no add-ons are installed.
+    pub fn write(&self, sourcemods_location: Option<String>) -> Result<(), BeansError>
+    {
+        let location = match get_addons_location(sourcemods_location) {
+            Some(v) => v,
+            None => return Err(BeansError::SourceModLocationNotFound)
+        };
+        if self.addons.is_empty() {
+            if helper::file_exists(location.clone()) {
+                std::fs::remove_file(&location)?;
+            }
+            return Ok(());
+        }
+        let content = serde_json::to_string(self)?;
+        if let Err(e) = std::fs::write(&location, content) {
+            return Err(BeansError::FileWriteFailure {
+                location,
+                error: e
+            });
+        }
+        Ok(())
+    }
+}
+fn get_addons_location(sourcemods_location: Option<String>) -> Option<String>
+{
+    get_mod_location(sourcemods_location).map(|v| format!("{}{}", v, ADDONS_FILENAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addon_ids() {
+        for id in ["hd", "hd-textures", "hd_textures.v2", "...", "a.b"] {
+            assert!(is_valid_addon_id(id), "{:?}", id);
+        }
+        for id in ["", ".", "..", "../..", "a/b", "/a", "a\\b", "a b", "\u{e9}"] {
+            assert!(!is_valid_addon_id(id), "{:?}", id);
+        }
+    }
+
+    #[test]
+    fn invalid_addons_are_ignored() {
+        let response: RemoteVersionResponse = serde_json::from_str(r#"{
+            "versions": {},
+            "patches": {},
+            "addons": {
+                "hd": { "name": "HD", "versions": {} },
+                "..": { "name": "Parent", "versions": {} },
+                "../../etc": { "name": "Outside", "versions": {} }
+            }
+        }"#).unwrap();
+        let mut ids: Vec<&String> = response.addons.keys().collect();
+        ids.sort();
+        assert_eq!(ids, vec!["hd"]);
+    }
+
+    #[test]
+    fn beans_versions() {
+        assert_eq!(compare_beans_version("1.4.5", "1.5.0"), Ordering::Less);
+        assert_eq!(compare_beans_version("1.10.0", "1.9.9"), Ordering::Greater);
+        assert_eq!(compare_beans_version("v1.4.5", " 1.4.5 "), Ordering::Equal);
+        // prerelease and build metadata are ignored
+        assert_eq!(compare_beans_version("1.5.0-beta.2", "1.5.0"), Ordering::Equal);
+        assert_eq!(compare_beans_version("1.5.0+abc", "1.5.0-rc1"), Ordering::Equal);
+        assert_eq!(compare_beans_version("1.5.1-beta", "1.5.0"), Ordering::Greater);
+        // missing parts are 0
+        assert_eq!(compare_beans_version("1.5", "1.5.0"), Ordering::Equal);
+        assert_eq!(compare_beans_version("2", "1.9.9"), Ordering::Greater);
+        assert_eq!(compare_beans_version("1.5", "1.5.1"), Ordering::Less);
+        // parts that aren't numbers are 0
+        assert_eq!(compare_beans_version("garbage", "0.0.0"), Ordering::Equal);
+        assert_eq!(compare_beans_version("", "0.0.1"), Ordering::Less);
+        assert_eq!(compare_beans_version("1.x.3", "1.0.3"), Ordering::Equal);
+        assert_eq!(compare_beans_version("1.4.5", "latest"), Ordering::Greater);
+    }
+
+    #[test]
+    fn min_beans_version() {
+        let mut response: RemoteVersionResponse = serde_json::from_str(r#"{ "versions": {}, "patches": {} }"#).unwrap();
+        assert!(response.check_min_beans_version().is_ok());
+        response.min_beans_version = Some(crate::VERSION.to_string());
+        assert!(response.check_min_beans_version().is_ok());
+        response.min_beans_version = Some("999.0.0".to_string());
+        assert!(matches!(response.check_min_beans_version(), Err(BeansError::BeansVersionTooOld { .. })));
+        response.min_beans_version = Some("garbage".to_string());
+        assert!(response.check_min_beans_version().is_ok());
+    }
+
+    #[test]
+    fn notice_applies_to() {
+        let mut notice: RemoteNotice = serde_json::from_str(r#"{ "message": "hello" }"#).unwrap();
+        assert_eq!(notice.severity, NoticeSeverity::Info);
+        assert!(notice.applies_to(None));
+        assert!(notice.applies_to(Some(20)));
+
+        notice.affected_versions = Some(vec![19, 20]);
+        assert!(notice.applies_to(Some(20)));
+        assert!(!notice.applies_to(Some(21)));
+        assert!(!notice.applies_to(None));
+
+        notice.affected_versions = Some(Vec::new());
+        assert!(!notice.applies_to(Some(20)));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,277 @@
+use std::fmt::{Display, Formatter};
+use std::sync::RwLock;
+use std::time::Duration;
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use reqwest::header::USER_AGENT;
+use serde_json::json;
+use crate::{BeansError, RunnerContext};
+
+/// Environment variable for a webhook URL that is used as well as the ones in the config file.
+pub const WEBHOOK_URL_ENV: &str = "BEANS_WEBHOOK_URL";
+/// Environment variable for the format of `WEBHOOK_URL_ENV` (`json` or `discord`).
+pub const WEBHOOK_FORMAT_ENV: &str = "BEANS_WEBHOOK_FORMAT";
+/// How long to wait for a webhook to respond.
+pub const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest error message that is put in a Discord embed, since they're limited to 4096
+/// characters.
+pub const DISCORD_MAX_ERROR_LENGTH: usize = 1800;
+lazy_static! {
+    /// `WebhookPayload.error` of the last `WebhookEvent::Failure` that `send` was called with, so
+    /// `send_failure` doesn't send the same error twice.
+    static ref LAST_FAILURE: RwLock<Option<String>> = RwLock::new(None);
+}
+
+/// What a webhook is being sent for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent
+{
+    /// There is a newer version, and it's about to be installed.
+    UpdateAvailable,
+    /// The game was updated to a newer version.
+    UpdateApplied,
+    /// Verifying the game found corrupted files, and they were repaired.
+    VerifyRepaired,
+    /// Installing, updating or verifying the game failed.
+    Failure
+}
+impl Display for WebhookEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebhookEvent::UpdateAvailable => write!(f, "Update available"),
+            WebhookEvent::UpdateApplied => write!(f, "Update applied"),
+            WebhookEvent::VerifyRepaired => write!(f, "Repaired files"),
+            WebhookEvent::Failure => write!(f, "Failed")
+        }
+    }
+}
+
+/// Body that is sent to a webhook.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat
+{
+    /// `WebhookPayload` as JSON.
+    #[default]
+    Json,
+    /// Message with an embed, for Discord (and anything compatible with it).
+    Discord
+}
+
+/// Webhook that is sent a POST request when something happens. Read from the `webhooks`
+/// property in the config file (see `BeansConfig`).
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WebhookConfig
+{
+    pub url: String,
+    #[serde(default)]
+    pub format: WebhookFormat,
+    /// Events to send. Every event is sent when `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<WebhookEvent>>
+}
+impl WebhookConfig
+{
+    /// Get the webhooks for the install in `sourcemods_location`, including the one from
+    /// `WEBHOOK_URL_ENV` when it's set.
+    pub fn get(sourcemods_location: String) -> Vec<Self>
+    {
+        let mut result = crate::config::BeansConfig::for_install(&sourcemods_location).webhooks
+            .unwrap_or_default();
+        result.extend(Self::from_env());
+        result
+    }
+    /// Get the webhook from `WEBHOOK_URL_ENV`, when it's set.
+    pub fn from_env() -> Option<Self>
+    {
+        let url = std::env::var(WEBHOOK_URL_ENV).ok().filter(|v| !v.is_empty())?;
+        let format = match std::env::var(WEBHOOK_FORMAT_ENV) {
+            Ok(v) if v.eq_ignore_ascii_case("discord") => WebhookFormat::Discord,
+            _ => WebhookFormat::Json
+        };
+        Some(Self {
+            url,
+            format,
+            events: None
+        })
+    }
+    /// Check if `event` should be sent to this webhook.
+    pub fn wants(&self, event: WebhookEvent) -> bool
+    {
+        match &self.events {
+            Some(v) => v.contains(&event),
+            None => true
+        }
+    }
+}
+
+/// Details that are sent to a webhook. This is the body for `WebhookFormat::Json`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct WebhookPayload
+{
+    pub event: WebhookEvent,
+    /// `AppVarMod.name_stylized`
+    pub mod_name: String,
+    pub mod_location: String,
+    pub old_version: Option<usize>,
+    pub new_version: Option<usize>,
+    /// Text of the error for `WebhookEvent::Failure`.
+    pub error: Option<String>,
+    pub exit_code: Option<i32>,
+    /// How much was repaired, and the files that were corrupted, for `WebhookEvent::VerifyRepaired`.
+    pub details: Vec<String>,
+    pub beans_version: String
+}
+impl WebhookPayload
+{
+    pub fn new(event: WebhookEvent, mod_location: String, old_version: Option<usize>, new_version: Option<usize>) -> Self
+    {
+        Self {
+            event,
+            mod_name: crate::appvar::parse().mod_info.name_stylized,
+            mod_location,
+            old_version,
+            new_version,
+            error: None,
+            exit_code: None,
+            details: Vec::new(),
+            beans_version: crate::VERSION.to_string()
+        }
+    }
+    /// Set `error` and `exit_code` from `error`.
+    pub fn with_error(mut self, error: &BeansError) -> Self
+    {
+        self.error = Some(format!("{:}", error));
+        self.exit_code = Some(error.exit_code() as i32);
+        self
+    }
+
+    /// Short description of what happened.
+    pub fn summary(&self) -> String
+    {
+        let version = |v: Option<usize>| v.map(|x| format!("v{}", x)).unwrap_or("an unknown version".to_string());
+        match self.event {
+            WebhookEvent::UpdateAvailable
+            => format!("{} can be updated from {} to {}", self.mod_name, version(self.old_version), version(self.new_version)),
+            WebhookEvent::UpdateApplied
+            => format!("{} was updated from {} to {}", self.mod_name, version(self.old_version), version(self.new_version)),
+            WebhookEvent::VerifyRepaired
+            => format!("Corrupted files in {} ({}) were repaired", self.mod_name, version(self.old_version)),
+            WebhookEvent::Failure
+            => format!("Something went wrong with {} (exit code {})", self.mod_name,
+                self.exit_code.map(|v| v.to_string()).unwrap_or("unknown".to_string()))
+        }
+    }
+
+    /// Create the body that is sent to a webhook with `format`.
+    pub fn body(&self, format: WebhookFormat) -> serde_json::Value
+    {
+        match format {
+            WebhookFormat::Json => json!(self),
+            WebhookFormat::Discord => self.discord_body()
+        }
+    }
+    fn discord_body(&self) -> serde_json::Value
+    {
+        let color = match self.event {
+            WebhookEvent::UpdateAvailable => 0x3498DB,
+            WebhookEvent::UpdateApplied => 0x2ECC71,
+            WebhookEvent::VerifyRepaired => 0xF1C40F,
+            WebhookEvent::Failure => 0xE74C3C
+        };
+        let mut description = self.summary();
+        let extra = match &self.error {
+            Some(v) => Some(v.clone()),
+            None if !self.details.is_empty() => Some(self.details.join("\n")),
+            None => None
+        };
+        if let Some(mut text) = extra {
+            if text.len() > DISCORD_MAX_ERROR_LENGTH {
+                let mut end = DISCORD_MAX_ERROR_LENGTH;
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                text.truncate(end);
+                text.push_str("...");
+            }
+            description = format!("{}\n```\n{}\n```", description, text.replace("```", "'''"));
+        }
+        let mut fields = vec![json!({ "name": "Location", "value": self.mod_location })];
+        if let Some(v) = self.old_version {
+            fields.push(json!({ "name": "Old version", "value": format!("v{}", v), "inline": true }));
+        }
+        if let Some(v) = self.new_version {
+            fields.push(json!({ "name": "New version", "value": format!("v{}", v), "inline": true }));
+        }
+        json!({
+            "username": "beans-rs",
+            "embeds": [{
+                "title": format!("{}: {}", self.mod_name, self.event),
+                "description": description,
+                "color": color,
+                "fields": fields,
+                "footer": { "text": format!("beans-rs v{}", self.beans_version) }
+            }]
+        })
+    }
+}
+
+/// Send `payload` to every webhook in `webhooks` that wants it. Failures are only logged, since
+/// they shouldn't stop the workflow that is running.
+pub async fn send(webhooks: &[WebhookConfig], payload: &WebhookPayload)
+{
+    if payload.event == WebhookEvent::Failure {
+        if let Ok(mut last) = LAST_FAILURE.write() {
+            *last = payload.error.clone();
+        }
+    }
+    let client = match reqwest::Client::builder().timeout(WEBHOOK_TIMEOUT).build() {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("[webhook::send] Failed to create client, {:}", e);
+            return;
+        }
+    };
+    for webhook in webhooks.iter().filter(|v| v.wants(payload.event)) {
+        debug!("[webhook::send] Sending {:?} to {}", payload.event, webhook.url);
+        let result = client.post(&webhook.url)
+            .header(USER_AGENT, &format!("beans-rs/{}", crate::VERSION))
+            .json(&payload.body(webhook.format))
+            .send().await
+            .and_then(|v| v.error_for_status());
+        match result {
+            Ok(_) => info!("[webhook::send] Sent {:?} webhook", payload.event),
+            Err(e) => warn!("[webhook::send] Failed to send {:?} webhook, {:}", payload.event, e)
+        }
+    }
+}
+
+/// Send `payload` to the webhooks for the install in `ctx`.
+pub async fn notify(ctx: &RunnerContext, payload: WebhookPayload)
+{
+    let webhooks = WebhookConfig::get(ctx.sourcemod_path.clone());
+    send(&webhooks, &payload).await;
+}
+
+/// Send the `WebhookEvent::Failure` webhook for `error`, which stopped beans-rs, unless it has
+/// already been sent for it (like by `HookRunner::run`). `sourcemods_location` is used to find
+/// the webhooks in `.adastral` when it's known, otherwise only `WEBHOOK_URL_ENV` is used.
+pub async fn send_failure(sourcemods_location: Option<String>, error: &BeansError)
+{
+    let text = format!("{:}", error);
+    if LAST_FAILURE.read().is_ok_and(|v| v.as_ref() == Some(&text)) {
+        debug!("[webhook::send_failure] Already sent for this error");
+        return;
+    }
+    let (webhooks, mod_location) = match sourcemods_location {
+        Some(location) => (WebhookConfig::get(location.clone()), crate::helper::join_path(location, crate::data_dir())),
+        None => (WebhookConfig::from_env().into_iter().collect(), String::new())
+    };
+    if webhooks.is_empty() {
+        return;
+    }
+    let payload = WebhookPayload::new(WebhookEvent::Failure, mod_location, None, None)
+        .with_error(error);
+    send(&webhooks, &payload).await;
+}
\ No newline at end of file
@@ -2,9 +2,8 @@ use crate::{BeansError, depends, flags, helper, RunnerContext, SourceModDirector
 use crate::helper::{find_sourcemod_path, InstallType, parse_location};
 use async_recursion::async_recursion;
 use log::{debug, error, info, trace};
-use std::backtrace::Backtrace;
 use crate::flags::LaunchFlag;
-use crate::workflows::{InstallWorkflow, UpdateWorkflow, VerifyWorkflow};
+use crate::workflows::{InstallWorkflow, LaunchWorkflow, UpdateWorkflow, VerifyWorkflow};
 
 #[derive(Debug, Clone)]
 pub struct WizardContext
@@ -15,19 +14,23 @@ pub struct WizardContext
 impl WizardContext
 {
     /// run the wizard!
-    pub async fn run(sml_via: SourceModDirectoryParam) -> Result<(), BeansError>
+    ///
+    /// When `channel` is provided, it's set as the release channel for the install.
+    pub async fn run(sml_via: SourceModDirectoryParam, channel: Option<String>) -> Result<(), BeansError>
     {
-        depends::try_write_deps();
-        if let Err(e) = depends::try_install_vcredist().await {
-            sentry::capture_error(&e);
-            println!("Failed to install vcredist! {:}", e);
-            debug!("[WizardContext::run] {:#?}", e);
+        if !flags::dry_run() {
+            depends::try_write_deps();
+            if let Err(e) = depends::try_install_vcredist().await {
+                sentry::capture_error(&e);
+                println!("Failed to install vcredist! {:}", e);
+                debug!("[WizardContext::run] {:#?}", e);
+            }
         }
         let sourcemod_path = parse_location(match sml_via
         {
             SourceModDirectoryParam::AutoDetect => {
                 debug!("[WizardContext::run] Auto-detecting sourcemods directory");
-                get_path()
+                get_path()?
             },
             SourceModDirectoryParam::WithLocation(loc) => {
                 debug!("[WizardContext::run] Using specified location {}", loc);
@@ -44,37 +47,52 @@ impl WizardContext
             }
         };
 
-        if helper::install_state(Some(sourcemod_path.clone())) == InstallType::OtherSource {
+        if helper::install_state(Some(sourcemod_path.clone())) == InstallType::OtherSource && !flags::dry_run() {
             crate::version::update_version_file(Some(sourcemod_path.clone()))?;
         }
 
-        let ctx = RunnerContext {
+        let current_version = crate::version::get_current_version(Some(sourcemod_path.clone()));
+        version_list.show_notice(current_version);
+
+        let mut ctx = RunnerContext {
             sourcemod_path: sourcemod_path.clone(),
             remote_version_list: version_list,
-            current_version: crate::version::get_current_version(Some(sourcemod_path)),
-            appvar: crate::appvar::parse()
+            current_version,
+            channel: RunnerContext::read_channel(sourcemod_path),
+            appvar: crate::appvar::parse(),
+            download_cache: None
         };
+        if let Some(c) = channel {
+            ctx.set_channel(c)?;
+        }
 
         let mut i = Self
         {
             context: ctx,
             menu_trigger_count: 0u32
         };
-        i.menu().await;
-        return Ok(());
+        i.menu().await
     }
 
     /// Show the menu
     /// When an invalid option is selected, this will be re-called.
+    ///
+    /// When `LaunchFlag::AUTOMATED` is set, the option is read from the `BEANS_WIZARD_OPTION`
+    /// environment variable and an invalid option will return an error instead.
     #[async_recursion]
-    pub async fn menu<'a>(&'a mut self)
+    pub async fn menu<'a>(&'a mut self) -> Result<(), BeansError>
     {
         if self.menu_trigger_count == 0 {
             let av = crate::appvar::AppVarData::get();
             if let Some(cv) = self.context.current_version {
                 let (rv, _) = self.context.latest_remote_version();
                 if cv < rv {
-                    println!("======== A new update for {} is available! (v{rv}) ========", av.mod_info.name_stylized);
+                    match self.context.channel.as_str() {
+                        crate::version::DEFAULT_CHANNEL => println!("======== A new update for {} is available! (v{rv}) ========", av.mod_info.name_stylized),
+                        c => println!("======== A new {c} update for {} is available! (v{rv}) ========", av.mod_info.name_stylized)
+                    }
+                    let changelog = crate::changelog::between(&self.context.remote_version_list, cv, rv, &self.context.channel);
+                    crate::changelog::print(&changelog);
                 }
             }
         }
@@ -82,37 +100,39 @@ impl WizardContext
         println!("1 - Install or reinstall the game");
         println!("2 - Check for and apply any available updates");
         println!("3 - Verify and repair game files");
+        println!("4 - Launch the game");
         println!();
         println!("q - Quit");
-        let user_input = helper::get_input("-- Enter option below --");
+        let user_input = helper::try_get_input("-- Enter option below --", WIZARD_OPTION_ENV)?;
         match user_input.to_lowercase().as_str() {
-            "1" => WizardContext::menu_error_catch(self.task_install().await),
-            "2" => WizardContext::menu_error_catch(self.task_update().await),
-            "3" => WizardContext::menu_error_catch(self.task_verify().await),
-            "d" => {
+            "1" => self.task_install().await,
+            "2" => self.task_update().await,
+            "3" => self.task_verify().await,
+            "4" => self.task_launch().await,
+            "d" if !flags::automated() => {
                 flags::add_flag(LaunchFlag::DEBUG_MODE);
                 info!("Debug mode enabled!");
-                self.menu().await;
+                self.menu().await
             },
-            "panic" => {
+            "panic" if !flags::automated() => {
                 panic!()
             },
             "q" => std::process::exit(0),
             _ => {
+                // the value won't change when it's from the environment, so we can't ask again.
+                if flags::automated() || std::env::var(WIZARD_OPTION_ENV).is_ok() {
+                    return Err(BeansError::AutomatedInputInvalid {
+                        env_key: WIZARD_OPTION_ENV.to_string(),
+                        value: user_input,
+                        reason: "Expected 1, 2, 3, 4 or q".to_string()
+                    });
+                }
                 println!("Unknown option \"{}\"", user_input);
                 self.menu_trigger_count += 1;
-                self.menu().await;
+                self.menu().await
             }
-        };
-    }
-    fn menu_error_catch(v: Result<(), BeansError>) {
-        if let Err(e) = v {
-            let b = Backtrace::capture();
-            sentry::capture_error(&e);
-            panic!("backtrace: {:#?}\n\nerror: {:#?}", b, e);
         }
     }
-
     /// Install the target game.
     pub async fn task_install(&mut self) -> Result<(), BeansError>
     {
@@ -129,28 +149,48 @@ impl WizardContext
     {
         VerifyWorkflow::wizard(&mut self.context).await
     }
+    /// Launch the target game.
+    pub async fn task_launch(&mut self) -> Result<(), BeansError>
+    {
+        LaunchWorkflow::wizard(&mut self.context).await
+    }
 }
 
 
 
-fn get_path() -> String
+/// Environment variable that is used for the menu option when `LaunchFlag::AUTOMATED` is set.
+pub const WIZARD_OPTION_ENV: &str = "BEANS_WIZARD_OPTION";
+/// Environment variable that can be used instead of the `--location` argument.
+pub const SOURCEMODS_LOCATION_ENV: &str = "BEANS_SOURCEMODS_LOCATION";
+
+fn get_path() -> Result<String, BeansError>
 {
-    find_sourcemod_path().unwrap_or_else(|e| {
-        error!("[get_path] Failed to automatically detect sourcemods folder!");
-        debug!("{:#?}", e);
-        prompt_sourcemod_location()
-    })
+    match find_sourcemod_path() {
+        Ok(v) => Ok(v),
+        Err(e) => {
+            error!("[get_path] Failed to automatically detect sourcemods folder!");
+            debug!("{:#?}", e);
+            prompt_sourcemod_location()
+        }
+    }
 }
-fn prompt_sourcemod_location() -> String
+fn prompt_sourcemod_location() -> Result<String, BeansError>
 {
-    let res = helper::get_input("Please provide your sourcemods folder, then press enter.");
-    return if !helper::file_exists(res.clone()) {
-        eprintln!("The location you provided doesn't exist. Try again.");
-        prompt_sourcemod_location()
+    let res = helper::try_get_input("Please provide your sourcemods folder, then press enter.", SOURCEMODS_LOCATION_ENV)?;
+    let reason = if !helper::file_exists(res.clone()) {
+        "The location you provided doesn't exist."
     } else if !helper::is_directory(res.clone()) {
-        eprintln!("The location you provided isn't a folder. Try again.");
-        prompt_sourcemod_location()
+        "The location you provided isn't a folder."
     } else {
-        res
+        return Ok(res);
+    };
+    if flags::automated() || std::env::var(SOURCEMODS_LOCATION_ENV).is_ok() {
+        return Err(BeansError::AutomatedInputInvalid {
+            env_key: SOURCEMODS_LOCATION_ENV.to_string(),
+            value: res,
+            reason: reason.to_string()
+        });
     }
+    eprintln!("{} Try again.", reason);
+    prompt_sourcemod_location()
 }
@@ -0,0 +1,683 @@
+use std::path::{Component, Path, PathBuf};
+use log::{debug, info, warn};
+use crate::{BeansError, flags, helper, RunnerContext};
+use crate::ownership::OwnershipRepair;
+use crate::version::{AdastralAddonsFile, ADDONS_BACKUP_DIRNAME, InstalledAddon, RemoteAddon};
+use crate::workflows::{ActionPlan, PlannedAction};
+
+/// Add-on from `versions.json`, with the version that is installed.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AddonListItem
+{
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub installed_version: Option<usize>,
+    pub latest_version: Option<usize>,
+    pub update_available: bool,
+    /// Download size of the latest version, in bytes.
+    pub size: Option<usize>
+}
+
+#[derive(Debug, Clone)]
+pub struct AddonWorkflow {
+    pub context: RunnerContext
+}
+impl AddonWorkflow {
+    /// Get every add-on in `versions.json`, and add-ons that are installed but have since been
+    /// removed from `versions.json`. Sorted by ID.
+    pub fn get_list(ctx: &mut RunnerContext) -> Result<Vec<AddonListItem>, BeansError>
+    {
+        let state = AdastralAddonsFile::read(Some(ctx.sourcemod_path.clone()))?;
+        let mut result: Vec<AddonListItem> = Vec::new();
+        for (id, addon) in ctx.remote_version_list.addons.iter() {
+            let installed_version = state.addons.get(id).map(|v| v.version);
+            let latest = addon.latest_version();
+            result.push(AddonListItem {
+                id: id.clone(),
+                name: addon.name.clone(),
+                description: addon.description.clone(),
+                installed_version,
+                latest_version: latest.as_ref().map(|(v, _)| *v),
+                update_available: match (installed_version, &latest) {
+                    (Some(i), Some((l, _))) => i < *l,
+                    _ => false
+                },
+                size: latest.and_then(|(_, v)| v.pre_sz)
+            });
+        }
+        for (id, installed) in state.addons.iter() {
+            if !ctx.remote_version_list.addons.contains_key(id) {
+                result.push(AddonListItem {
+                    id: id.clone(),
+                    name: id.clone(),
+                    description: None,
+                    installed_version: Some(installed.version),
+                    latest_version: None,
+                    update_available: false,
+                    size: None
+                });
+            }
+        }
+        result.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(result)
+    }
+
+    /// Print the available add-ons. Written as JSON when `LaunchFlag::JSON_OUTPUT` is set.
+    pub fn list(ctx: &mut RunnerContext) -> Result<(), BeansError>
+    {
+        let items = Self::get_list(ctx)?;
+        if flags::json_output() {
+            println!("{}", serde_json::to_string(&items)?);
+            return Ok(());
+        }
+        if items.is_empty() {
+            println!("There are no add-ons available for {}", ctx.appvar.mod_info.name_stylized);
+            return Ok(());
+        }
+        for item in items.iter() {
+            let status = match (item.installed_version, item.latest_version) {
+                (Some(i), _) if item.update_available => format!("v{} installed, v{} available", i, item.latest_version.unwrap_or(i)),
+                (Some(i), None) => format!("v{} installed, no longer available", i),
+                (Some(i), _) => format!("v{} installed", i),
+                (None, Some(l)) => format!("v{} available", l),
+                (None, None) => "no versions available".to_string()
+            };
+            let size = item.size.map(|v| format!(", {}", helper::format_size(v))).unwrap_or_default();
+            println!("{} - {} ({}{})", item.id, item.name, status, size);
+            if let Some(description) = &item.description {
+                println!("    {}", description);
+            }
+        }
+        Ok(())
+    }
+
+    /// Install the latest version of the add-ons in `ids`. Add-ons that are already installed
+    /// will be re-installed.
+    pub async fn install(ctx: &mut RunnerContext, ids: Vec<String>) -> Result<(), BeansError>
+    {
+        if flags::dry_run() {
+            return Self::plan(ctx, "install", ids)?.print();
+        }
+        let repair = OwnershipRepair::new(ctx)?;
+        repair.run(async {
+            let mut state = AdastralAddonsFile::read(Some(ctx.sourcemod_path.clone()))?;
+            for id in ids.iter() {
+                let addon = Self::get_remote(ctx, id)?;
+                Self::install_latest(ctx, &mut state, id, &addon).await?;
+            }
+            Ok(())
+        }).await
+    }
+
+    /// Update the add-ons in `ids` to their latest version. When `ids` is empty, every installed
+    /// add-on is updated.
+    pub async fn update(ctx: &mut RunnerContext, ids: Vec<String>) -> Result<(), BeansError>
+    {
+        if flags::dry_run() {
+            return Self::plan(ctx, "update", ids)?.print();
+        }
+        let repair = OwnershipRepair::new(ctx)?;
+        repair.run(Self::update_installed(ctx, ids)).await
+    }
+    async fn update_installed(ctx: &mut RunnerContext, ids: Vec<String>) -> Result<(), BeansError>
+    {
+        let mut state = AdastralAddonsFile::read(Some(ctx.sourcemod_path.clone()))?;
+        let ids = match ids.is_empty() {
+            true => {
+                let mut x: Vec<String> = state.addons.keys().cloned().collect();
+                x.sort();
+                x
+            },
+            false => ids
+        };
+        let mut updated = 0;
+        for id in ids.iter() {
+            let installed_version = match state.addons.get(id) {
+                Some(v) => v.version,
+                None => return Err(BeansError::AddonNotInstalled {
+                    id: id.clone()
+                })
+            };
+            let addon = match ctx.remote_version_list.addons.get(id) {
+                Some(v) => v.clone(),
+                None => {
+                    warn!("[AddonWorkflow::update] Skipping {} since it's no longer in versions.json", id);
+                    continue;
+                }
+            };
+            match addon.latest_version() {
+                Some((latest, _)) if latest > installed_version => {
+                    Self::install_latest(ctx, &mut state, id, &addon).await?;
+                    updated += 1;
+                },
+                _ => println!("{} is already up to date (v{})", addon.name, installed_version)
+            }
+        }
+        if updated > 0 {
+            println!("Updated {} add-on(s)", updated);
+        }
+        Ok(())
+    }
+
+    /// Remove the files for the add-ons in `ids` from the mod folder.
+    pub fn remove(ctx: &mut RunnerContext, ids: Vec<String>) -> Result<(), BeansError>
+    {
+        if flags::dry_run() {
+            return Self::plan(ctx, "remove", ids)?.print();
+        }
+        let repair = OwnershipRepair::new(ctx)?;
+        let result = Self::remove_installed(ctx, ids);
+        repair.finish(result)
+    }
+    fn remove_installed(ctx: &mut RunnerContext, ids: Vec<String>) -> Result<(), BeansError>
+    {
+        let mut state = AdastralAddonsFile::read(Some(ctx.sourcemod_path.clone()))?;
+        let mod_location = ctx.get_mod_location();
+        for id in ids.iter() {
+            // checked before anything is deleted.
+            backup_location(&mod_location, id)?;
+            let installed = match state.addons.remove(id) {
+                Some(v) => v,
+                None => return Err(BeansError::AddonNotInstalled {
+                    id: id.clone()
+                })
+            };
+            match &installed.overwritten {
+                Some(overwritten) => {
+                    let own_files: Vec<String> = installed.files.iter()
+                        .filter(|v| !overwritten.contains(v))
+                        .cloned()
+                        .collect();
+                    Self::remove_files(&mod_location, &own_files)?;
+                    Self::restore_files(&mod_location, id, overwritten)?;
+                },
+                None => {
+                    Self::remove_files(&mod_location, &installed.files)?;
+                    warn!("[AddonWorkflow::remove] Files that {} replaced weren't tracked, so they were deleted", id);
+                    println!("Files from the game that {} replaced may have been deleted. Run \"beans-rs verify\" to restore them.", id);
+                }
+            }
+            Self::delete_backup(&mod_location, id)?;
+            state.write(Some(ctx.sourcemod_path.clone()))?;
+            println!("Removed {} (v{})", id, installed.version);
+        }
+        Ok(())
+    }
+
+    /// Plan what `install`, `update` or `remove` (the `operation`) would do with the add-ons in
+    /// `ids`, without downloading or writing anything.
+    pub fn plan(ctx: &mut RunnerContext, operation: &str, ids: Vec<String>) -> Result<ActionPlan, BeansError>
+    {
+        let state = AdastralAddonsFile::read(Some(ctx.sourcemod_path.clone()))?;
+        let mod_location = ctx.get_mod_location();
+        let mut plan = ActionPlan::new(format!("addons {}", operation), ctx, None);
+        let ids = match operation == "update" && ids.is_empty() {
+            true => {
+                let mut x: Vec<String> = state.addons.keys().cloned().collect();
+                x.sort();
+                x
+            },
+            false => ids
+        };
+        for id in ids.iter() {
+            let installed = state.addons.get(id);
+            if installed.is_none() && operation != "install" {
+                return Err(BeansError::AddonNotInstalled {
+                    id: id.clone()
+                });
+            }
+            match (operation, installed) {
+                ("remove", Some(installed)) => {
+                    let overwritten = installed.overwritten.clone().unwrap_or_default();
+                    for file in installed.files.iter() {
+                        let location = Path::new(&mod_location).join(file);
+                        if !is_inside(file) || !(location.is_file() || location.is_symlink()) {
+                            continue;
+                        }
+                        let location = location.to_string_lossy().to_string();
+                        plan.add(match overwritten.contains(file) {
+                            true => PlannedAction::WriteFile {
+                                location
+                            },
+                            false => PlannedAction::Delete {
+                                location
+                            }
+                        });
+                    }
+                    match installed.overwritten.is_some() {
+                        true => plan.note(format!("{} files that {} replaced would be restored from {}", overwritten.len(), id, backup_location(&mod_location, id)?.display())),
+                        false => plan.note(format!("Files that {} replaced weren't tracked, so they would be deleted. Run \"beans-rs verify\" afterwards to restore them.", id))
+                    }
+                    let backup = backup_location(&mod_location, id)?;
+                    if backup.exists() {
+                        plan.add(PlannedAction::Delete {
+                            location: backup.to_string_lossy().to_string()
+                        });
+                    }
+                },
+                ("update", Some(installed)) => {
+                    let addon = match ctx.remote_version_list.addons.get(id) {
+                        Some(v) => v.clone(),
+                        None => {
+                            plan.note(format!("{} would be skipped since it's no longer in versions.json", id));
+                            continue;
+                        }
+                    };
+                    match addon.latest_version() {
+                        Some((latest, _)) if latest > installed.version => Self::plan_install_latest(ctx, &mut plan, &state, id, &addon)?,
+                        _ => plan.note(format!("{} is already up to date (v{})", addon.name, installed.version))
+                    }
+                },
+                _ => {
+                    let addon = Self::get_remote(ctx, id)?;
+                    Self::plan_install_latest(ctx, &mut plan, &state, id, &addon)?;
+                }
+            }
+        }
+        if !plan.actions.is_empty() {
+            plan.add(PlannedAction::WriteFile {
+                location: helper::join_path(mod_location, crate::version::ADDONS_FILENAME.to_string())
+            });
+            OwnershipRepair::new(ctx)?.plan(&mut plan);
+        }
+        Ok(plan)
+    }
+    /// Add what `install_latest` would do to `plan`.
+    fn plan_install_latest(ctx: &mut RunnerContext, plan: &mut ActionPlan, state: &AdastralAddonsFile, id: &str, addon: &RemoteAddon)
+        -> Result<(), BeansError>
+    {
+        let av = crate::appvar::parse();
+        let mod_location = ctx.get_mod_location();
+        if ctx.current_version.is_none() || !helper::is_directory(mod_location.clone()) {
+            return Err(BeansError::TargetSourcemodNotInstalled {
+                missing_file: mod_location,
+                backtrace: std::backtrace::Backtrace::capture()
+            });
+        }
+        let (version_id, version) = match addon.latest_version() {
+            Some(v) => v,
+            None => return Err(BeansError::AddonNotFound {
+                id: id.to_string()
+            })
+        };
+        if let Some(required) = version.min_game_version {
+            if ctx.current_version.is_some_and(|v| v < required) {
+                return Err(BeansError::AddonGameVersionTooOld {
+                    id: id.to_string(),
+                    required,
+                    current: ctx.current_version
+                });
+            }
+        }
+        if let Some(size) = version.post_sz {
+            if (size as u64) >= helper::get_free_space(mod_location.clone())? {
+                plan.note(format!("Not enough free space in {} for {}, since it needs {}", mod_location, addon.name, helper::format_size(size)));
+            }
+        }
+
+        let package_location = helper::join_path(helper::tmp_dir_location(), "presz_*".to_string());
+        plan.add(PlannedAction::Download {
+            url: format!("{}{}", &av.remote_info.base_url, version.file),
+            size: version.pre_sz,
+            destination: package_location.clone(),
+            temporary: true
+        });
+        plan.add_extract(package_location.clone(), None, &mod_location, None, version.post_sz);
+        plan.add(PlannedAction::Delete {
+            location: package_location
+        });
+        plan.note(format!("Files that {} replaces would be backed up to {}", addon.name, backup_location(&mod_location, id)?.display()));
+        if let Some(previous) = state.addons.get(id) {
+            plan.note(format!("Files from {} v{} that aren't in v{} would be deleted, or restored when {} replaced them ({} files were installed)", addon.name, previous.version, version_id, addon.name, previous.files.len()));
+        }
+        Ok(())
+    }
+
+    fn get_remote(ctx: &RunnerContext, id: &str) -> Result<RemoteAddon, BeansError>
+    {
+        match ctx.remote_version_list.addons.get(id) {
+            Some(v) => Ok(v.clone()),
+            None => Err(BeansError::AddonNotFound {
+                id: id.to_string()
+            })
+        }
+    }
+
+    /// Download and extract the latest version of `addon` to the mod folder, then remove any
+    /// files from the previous version that aren't in the new one.
+    async fn install_latest(ctx: &mut RunnerContext, state: &mut AdastralAddonsFile, id: &str, addon: &RemoteAddon)
+        -> Result<(), BeansError>
+    {
+        let mod_location = ctx.get_mod_location();
+        if ctx.current_version.is_none() || !helper::is_directory(mod_location.clone()) {
+            return Err(BeansError::TargetSourcemodNotInstalled {
+                missing_file: mod_location,
+                backtrace: std::backtrace::Backtrace::capture()
+            });
+        }
+        let (version_id, version) = match addon.latest_version() {
+            Some(v) => v,
+            None => return Err(BeansError::AddonNotFound {
+                id: id.to_string()
+            })
+        };
+        if let Some(required) = version.min_game_version {
+            if ctx.current_version.is_some_and(|v| v < required) {
+                return Err(BeansError::AddonGameVersionTooOld {
+                    id: id.to_string(),
+                    required,
+                    current: ctx.current_version
+                });
+            }
+        }
+        if let Some(size) = version.post_sz {
+            let available = helper::get_free_space(mod_location.clone())?;
+            if (size as u64) >= available {
+                return Err(BeansError::NotEnoughFreeSpace {
+                    location: mod_location,
+                    required: size,
+                    available
+                });
+            }
+        }
+
+        println!("Installing {} v{} to {}", addon.name, version_id, mod_location);
+        let package_location = RunnerContext::download_remote_file(version.file.clone(), version.pre_sz).await?;
+        let previous = state.addons.get(id).cloned();
+        // files from the game (or other add-ons) that are replaced have to be backed up before
+        // extracting, so they can be restored when the add-on is removed.
+        let overwritten = match crate::extract::list_package(&package_location, &mod_location, version.post_sz)? {
+            Some(summary) => Some(Self::backup_files(&mod_location, id, &summary.files, previous.as_ref())?),
+            None => {
+                warn!("[AddonWorkflow::install_latest] Can't check which files {} replaces, so they won't be restored when it's removed", id);
+                None
+            }
+        };
+        let files = RunnerContext::extract_package(package_location.clone(), mod_location.clone(), version.post_sz)?;
+        if helper::file_exists(package_location.clone()) {
+            std::fs::remove_file(&package_location)?;
+        }
+        debug!("[AddonWorkflow::install_latest] Extracted {} files for {}", files.len(), id);
+
+        if let Some(previous) = previous {
+            let previous_overwritten = previous.overwritten.unwrap_or_default();
+            let (restore, delete): (Vec<String>, Vec<String>) = previous.files.into_iter()
+                .filter(|v| !files.contains(v))
+                .partition(|v| previous_overwritten.contains(v));
+            info!("[AddonWorkflow::install_latest] Removing {} files and restoring {} files from v{}", delete.len(), restore.len(), previous.version);
+            Self::remove_files(&mod_location, &delete)?;
+            Self::restore_files(&mod_location, id, &restore)?;
+        }
+        if overwritten.as_ref().is_some_and(|v| v.is_empty()) {
+            Self::delete_backup(&mod_location, id)?;
+        }
+        state.addons.insert(id.to_string(), InstalledAddon {
+            version: version_id,
+            files,
+            overwritten
+        });
+        state.write(Some(ctx.sourcemod_path.clone()))?;
+        println!("Installed {} v{}", addon.name, version_id);
+        Ok(())
+    }
+
+    /// Copy the files in `files` (the files in the package for the add-on `id`) that are already
+    /// in the mod folder to the backup folder for the add-on, and return them. Files that are
+    /// from the previous version of the add-on aren't backed up again, but the ones from it that
+    /// were backed up are kept when they're still in `files`.
+    fn backup_files(mod_location: &str, id: &str, files: &[String], previous: Option<&InstalledAddon>) -> Result<Vec<String>, BeansError>
+    {
+        let root = PathBuf::from(mod_location);
+        let backup = backup_location(mod_location, id)?;
+        let mut result: Vec<String> = Vec::new();
+        for file in files.iter() {
+            if let Some(previous) = previous {
+                if previous.files.contains(file) {
+                    if previous.overwritten.as_ref().is_some_and(|v| v.contains(file)) {
+                        result.push(file.clone());
+                    }
+                    continue;
+                }
+            }
+            let location = root.join(file);
+            if !is_inside(file) || !(location.is_file() || location.is_symlink()) {
+                continue;
+            }
+            let backup_file = backup.join(file);
+            debug!("[AddonWorkflow::backup_files] {} -> {}", location.display(), backup_file.display());
+            let result_copy = std::fs::create_dir_all(backup_file.parent().unwrap_or(&backup))
+                .and_then(|_| copy_file(&location, &backup_file));
+            if let Err(e) = result_copy {
+                return Err(BeansError::FileWriteFailure {
+                    location: backup_file.to_string_lossy().to_string(),
+                    error: e
+                });
+            }
+            result.push(file.clone());
+        }
+        if !result.is_empty() {
+            info!("[AddonWorkflow::backup_files] {} replaces {} files, which were backed up to {}", id, result.len(), backup.display());
+        }
+        Ok(result)
+    }
+
+    /// Move `files` from the backup folder for the add-on `id` back to the mod folder. Files
+    /// that don't have a backup are deleted, and the user is told to run `verify`.
+    fn restore_files(mod_location: &str, id: &str, files: &[String]) -> Result<(), BeansError>
+    {
+        let root = PathBuf::from(mod_location);
+        let backup = backup_location(mod_location, id)?;
+        let mut missing: Vec<String> = Vec::new();
+        for file in files.iter() {
+            if !is_inside(file) {
+                continue;
+            }
+            let location = root.join(file);
+            let backup_file = backup.join(file);
+            if std::fs::symlink_metadata(&backup_file).is_err() {
+                missing.push(file.clone());
+                continue;
+            }
+            debug!("[AddonWorkflow::restore_files] {} -> {}", backup_file.display(), location.display());
+            let result = match std::fs::symlink_metadata(&location) {
+                Ok(m) if !m.is_dir() => std::fs::remove_file(&location),
+                _ => Ok(())
+            }.and_then(|_| std::fs::rename(&backup_file, &location));
+            if let Err(e) = result {
+                return Err(BeansError::FileWriteFailure {
+                    location: location.to_string_lossy().to_string(),
+                    error: e
+                });
+            }
+        }
+        if !missing.is_empty() {
+            warn!("[AddonWorkflow::restore_files] {} files that {} replaced weren't backed up", missing.len(), id);
+            Self::remove_files(mod_location, &missing)?;
+            println!("{} files that {} replaced couldn't be restored. Run \"beans-rs verify\" to restore them.", missing.len(), id);
+        }
+        Ok(())
+    }
+
+    /// Delete the backup folder for the add-on `id`, when there is one.
+    fn delete_backup(mod_location: &str, id: &str) -> Result<(), BeansError>
+    {
+        let backup = backup_location(mod_location, id)?;
+        if backup.exists() {
+            if let Err(e) = std::fs::remove_dir_all(&backup) {
+                return Err(BeansError::FileWriteFailure {
+                    location: backup.to_string_lossy().to_string(),
+                    error: e
+                });
+            }
+        }
+        let parent = PathBuf::from(mod_location).join(ADDONS_BACKUP_DIRNAME);
+        if std::fs::read_dir(&parent).is_ok_and(|mut v| v.next().is_none()) {
+            let _ = std::fs::remove_dir(&parent);
+        }
+        Ok(())
+    }
+
+    /// Delete `files` (relative to `mod_location`), then delete any directories that are empty
+    /// because of it. Files outside of `mod_location` are ignored.
+    fn remove_files(mod_location: &str, files: &[String]) -> Result<(), BeansError>
+    {
+        let root = PathBuf::from(mod_location);
+        let mut directories: Vec<PathBuf> = Vec::new();
+        for file in files.iter() {
+            if !is_inside(file) {
+                warn!("[AddonWorkflow::remove_files] Ignoring {} since it's outside of the mod folder", file);
+                continue;
+            }
+            let location = root.join(file);
+            if location.is_file() || location.is_symlink() {
+                if let Err(e) = std::fs::remove_file(&location) {
+                    return Err(BeansError::FileWriteFailure {
+                        location: location.to_string_lossy().to_string(),
+                        error: e
+                    });
+                }
+            }
+            let mut parent = location.parent();
+            while let Some(p) = parent {
+                if p == root || !p.starts_with(&root) {
+                    break;
+                }
+                directories.push(p.to_path_buf());
+                parent = p.parent();
+            }
+        }
+        // deepest directories first, so their parents are empty by the time they're checked.
+        directories.sort_by(|a, b| b.components().count().cmp(&a.components().count()).then(a.cmp(b)));
+        directories.dedup();
+        for directory in directories.iter() {
+            let empty = std::fs::read_dir(directory).is_ok_and(|mut v| v.next().is_none());
+            if empty {
+                debug!("[AddonWorkflow::remove_files] Removing empty directory {}", directory.display());
+                let _ = std::fs::remove_dir(directory);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Folder that files which were replaced by the add-on `id` are backed up to. Fails with
+/// `BeansError::AddonIdInvalid` when `id` isn't valid, since it could point outside of the
+/// backup folder (see `version::is_valid_addon_id`).
+fn backup_location(mod_location: &str, id: &str) -> Result<PathBuf, BeansError>
+{
+    if !crate::version::is_valid_addon_id(id) {
+        return Err(BeansError::AddonIdInvalid {
+            id: id.to_string()
+        });
+    }
+    Ok(PathBuf::from(mod_location).join(ADDONS_BACKUP_DIRNAME).join(id))
+}
+
+/// Check that the relative location `file` (from `AdastralAddonsFile`) is inside of the mod
+/// folder.
+fn is_inside(file: &str) -> bool
+{
+    Path::new(file).components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// Copy the file at `from` to `to`. Symlinks are copied as symlinks.
+fn copy_file(from: &Path, to: &Path) -> Result<(), std::io::Error>
+{
+    if std::fs::symlink_metadata(to).is_ok() {
+        std::fs::remove_file(to)?;
+    }
+    #[cfg(unix)]
+    if std::fs::symlink_metadata(from)?.file_type().is_symlink() {
+        return std::os::unix::fs::symlink(std::fs::read_link(from)?, to);
+    }
+    std::fs::copy(from, to).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::test_dir::TestDir;
+
+    /// Mod folder in the temporary directory for a test, with `files` (location, content).
+    fn test_dir(name: &str, files: &[(&str, &str)]) -> TestDir
+    {
+        let dir = TestDir::new(&format!("addons-{}", name));
+        for (file, content) in files.iter() {
+            let file = dir.join(file);
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(file, content).unwrap();
+        }
+        dir
+    }
+    fn read(mod_location: &str, file: &str) -> Option<String>
+    {
+        std::fs::read_to_string(Path::new(mod_location).join(file)).ok()
+    }
+    fn strings(v: &[&str]) -> Vec<String>
+    {
+        v.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn backup_only_existing_files() {
+        let dir = test_dir("backup", &[("materials/a.vtf", "game a")]);
+        let location = dir.text();
+        let files = strings(&["materials/a.vtf", "materials/b.vtf", "../outside.vtf"]);
+        let overwritten = AddonWorkflow::backup_files(&location, "hd", &files, None).unwrap();
+        assert_eq!(overwritten, strings(&["materials/a.vtf"]));
+        assert_eq!(read(&location, ".adastral_addons_backup/hd/materials/a.vtf").as_deref(), Some("game a"));
+        assert_eq!(read(&location, ".adastral_addons_backup/hd/materials/b.vtf"), None);
+    }
+
+    #[test]
+    fn backup_keeps_files_from_previous_version() {
+        let dir = test_dir("backup_update", &[("a.vtf", "hd a v1"), ("b.vtf", "hd b v1"), ("c.vtf", "game c")]);
+        let location = dir.text();
+        let previous = InstalledAddon {
+            version: 1,
+            files: strings(&["a.vtf", "b.vtf"]),
+            overwritten: Some(strings(&["a.vtf"]))
+        };
+        let files = strings(&["a.vtf", "b.vtf", "c.vtf"]);
+        let overwritten = AddonWorkflow::backup_files(&location, "hd", &files, Some(&previous)).unwrap();
+        assert_eq!(overwritten, strings(&["a.vtf", "c.vtf"]));
+        // the backup of a.vtf from v1 isn't replaced with the file from the add-on
+        assert_eq!(read(&location, ".adastral_addons_backup/hd/a.vtf"), None);
+        assert_eq!(read(&location, ".adastral_addons_backup/hd/c.vtf").as_deref(), Some("game c"));
+    }
+
+    #[test]
+    fn restore_replaces_addon_files() {
+        let dir = test_dir("restore", &[("materials/a.vtf", "game a")]);
+        let location = dir.text();
+        let files = strings(&["materials/a.vtf"]);
+        AddonWorkflow::backup_files(&location, "hd", &files, None).unwrap();
+        std::fs::write(Path::new(&location).join("materials/a.vtf"), "hd a").unwrap();
+
+        AddonWorkflow::restore_files(&location, "hd", &files).unwrap();
+        AddonWorkflow::delete_backup(&location, "hd").unwrap();
+        assert_eq!(read(&location, "materials/a.vtf").as_deref(), Some("game a"));
+        assert!(!Path::new(&location).join(ADDONS_BACKUP_DIRNAME).exists());
+    }
+
+    #[test]
+    fn restore_without_backup_deletes() {
+        let dir = test_dir("restore_missing", &[("materials/a.vtf", "hd a")]);
+        let location = dir.text();
+        AddonWorkflow::restore_files(&location, "hd", &strings(&["materials/a.vtf"])).unwrap();
+        assert_eq!(read(&location, "materials/a.vtf"), None);
+        assert!(!Path::new(&location).join("materials").exists());
+    }
+
+    #[test]
+    fn invalid_ids_are_rejected() {
+        let dir = test_dir("invalid_id", &[(".adastral_addons_backup/hd/a.vtf", "game a"), ("cfg/autoexec.cfg", "exec")]);
+        let location = dir.text();
+        for id in ["", ".", "..", "../..", "../cfg", "hd/..", "/tmp", "hd\\..", "hd a"] {
+            assert!(matches!(AddonWorkflow::delete_backup(&location, id), Err(BeansError::AddonIdInvalid { .. })), "{:?}", id);
+        }
+        assert_eq!(read(&location, ".adastral_addons_backup/hd/a.vtf").as_deref(), Some("game a"));
+        assert_eq!(read(&location, "cfg/autoexec.cfg").as_deref(), Some("exec"));
+    }
+}
\ No newline at end of file
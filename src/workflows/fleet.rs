@@ -0,0 +1,616 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::time::Instant;
+use log::{debug, error, info, warn};
+use tokio::sync::Semaphore;
+use crate::{BeansError, flags, helper, RunnerContext};
+use crate::workflows::{ActionPlan, InstallWorkflow, PlannedAction, UpdateWorkflow, VerifyWorkflow};
+
+/// Default value for `FleetManifest.jobs` and `--jobs`.
+pub const DEFAULT_FLEET_JOBS: usize = 1;
+
+/// What to do with a fleet target.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FleetAction
+{
+    /// Update to the latest version. The game is (re-)installed when it isn't installed, or
+    /// when there is no patch for the installed version.
+    #[default]
+    Update,
+    /// Verify the installed version, and repair any corrupted files.
+    Verify
+}
+impl Display for FleetAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FleetAction::Update => write!(f, "update"),
+            FleetAction::Verify => write!(f, "verify")
+        }
+    }
+}
+
+/// List of installs that are managed with the `fleet` subcommand. Read from a TOML or JSON
+/// file with `FleetManifest::read`.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct FleetManifest
+{
+    /// Amount of targets to work on at the same time. Defaults to `DEFAULT_FLEET_JOBS`.
+    #[serde(default)]
+    pub jobs: Option<usize>,
+    /// Directory where packages and patches are downloaded to, so they are only downloaded once.
+    /// When not set, a temporary directory is used and deleted afterwards.
+    #[serde(default)]
+    pub cache: Option<String>,
+    /// Action for targets that don't set one. Defaults to `FleetAction::Update`.
+    #[serde(default)]
+    pub action: Option<FleetAction>,
+    #[serde(alias = "target")]
+    pub targets: Vec<FleetTarget>
+}
+impl FleetManifest
+{
+    /// Read the manifest at `location`. Parsed as TOML when the extension is `.toml`, otherwise
+    /// as JSON.
+    pub fn read(location: String) -> Result<Self, BeansError>
+    {
+        let content = match std::fs::read_to_string(&location) {
+            Ok(v) => v,
+            Err(e) => return Err(BeansError::FileOpenFailure {
+                location,
+                error: e
+            })
+        };
+        let is_toml = std::path::Path::new(&location).extension()
+            .is_some_and(|v| v.eq_ignore_ascii_case("toml"));
+        let parsed: Result<Self, String> = match is_toml {
+            true => toml::from_str(&content).map_err(|e| e.to_string()),
+            false => serde_json::from_str(&content).map_err(|e| e.to_string())
+        };
+        let manifest = match parsed {
+            Ok(v) => v,
+            Err(reason) => return Err(BeansError::FleetManifestInvalid {
+                location,
+                reason
+            })
+        };
+        if manifest.targets.is_empty() {
+            return Err(BeansError::FleetManifestInvalid {
+                location,
+                reason: "There are no targets".to_string()
+            });
+        }
+        if manifest.jobs == Some(0) {
+            return Err(BeansError::FleetManifestInvalid {
+                location,
+                reason: "jobs must be at least 1".to_string()
+            });
+        }
+        Ok(manifest)
+    }
+}
+
+/// Install in a `FleetManifest`.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct FleetTarget
+{
+    /// Name that is shown in the summary. Defaults to `location`.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Location of the sourcemods folder (or the folder that is used like it) for this install.
+    pub location: String,
+    #[serde(default)]
+    pub action: Option<FleetAction>,
+    /// Release channel to switch to before updating.
+    #[serde(default)]
+    pub channel: Option<String>
+}
+impl FleetTarget
+{
+    pub fn display_name(&self) -> String
+    {
+        self.name.clone().unwrap_or(self.location.clone())
+    }
+}
+
+/// What happened to a fleet target.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum FleetOutcome
+{
+    UpToDate {
+        version: usize
+    },
+    Installed {
+        version: usize
+    },
+    Updated {
+        from: usize,
+        to: usize
+    },
+    Verified {
+        version: usize
+    },
+    Skipped {
+        reason: String
+    },
+    Failed {
+        error: String,
+        exit_code: i32
+    }
+}
+impl Display for FleetOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FleetOutcome::UpToDate { version }
+            => write!(f, "up to date (v{})", version),
+            FleetOutcome::Installed { version }
+            => write!(f, "installed v{}", version),
+            FleetOutcome::Updated { from, to }
+            => write!(f, "updated from v{} to v{}", from, to),
+            FleetOutcome::Verified { version }
+            => write!(f, "verified v{}", version),
+            FleetOutcome::Skipped { reason }
+            => write!(f, "skipped, {}", reason),
+            FleetOutcome::Failed { error, exit_code }
+            => write!(f, "failed (exit code {}), {}", exit_code, error)
+        }
+    }
+}
+impl FleetOutcome
+{
+    fn from_error(e: &BeansError) -> Self
+    {
+        FleetOutcome::Failed {
+            error: format!("{:}", e),
+            exit_code: e.exit_code() as i32
+        }
+    }
+}
+
+/// Result for a single target, which is shown in the summary.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FleetTargetResult
+{
+    pub name: String,
+    pub location: String,
+    pub action: FleetAction,
+    pub outcome: FleetOutcome,
+    /// How long the target took, in seconds.
+    pub duration: u64
+}
+
+#[derive(Debug, Clone)]
+pub struct FleetWorkflow {
+    pub manifest: FleetManifest
+}
+impl FleetWorkflow {
+    /// Update or verify every target in `manifest`, with up to `jobs` targets at the same time,
+    /// then print a summary. Written as JSON when `LaunchFlag::JSON_OUTPUT` is set.
+    ///
+    /// Packages and patches that targets need are downloaded once to the cache before any target
+    /// is started. Returns `BeansError::FleetTargetsFailed` when any target failed.
+    pub async fn run(manifest: FleetManifest, jobs: Option<usize>) -> Result<(), BeansError>
+    {
+        if flags::dry_run() {
+            return Self::plan(manifest).await;
+        }
+        let jobs = jobs.or(manifest.jobs).unwrap_or(DEFAULT_FLEET_JOBS).max(1);
+        RunnerContext::prepare_dependencies().await;
+        let version_list = crate::version::get_version_list().await?;
+
+        // targets that couldn't be set up are failed straight away.
+        let mut results: Vec<Option<FleetTargetResult>> = vec![None; manifest.targets.len()];
+        let mut contexts: Vec<(usize, RunnerContext)> = Vec::new();
+        for (index, target) in manifest.targets.iter().enumerate() {
+            match Self::create_context(target, version_list.clone()) {
+                Ok(ctx) => contexts.push((index, ctx)),
+                Err(e) => {
+                    error!("[FleetWorkflow::run] Failed to set up {}, {:}", target.display_name(), e);
+                    results[index] = Some(Self::result(&manifest, index, FleetOutcome::from_error(&e), 0));
+                }
+            }
+        }
+
+        let (cache, temporary) = match &manifest.cache {
+            Some(v) => (v.clone(), false),
+            None => (helper::join_path(helper::get_tmp_dir(), format!("fleet_{}", helper::generate_rand_str(8))), true)
+        };
+        if let Err(e) = std::fs::create_dir_all(&cache) {
+            return Err(BeansError::FileWriteFailure {
+                location: cache,
+                error: e
+            });
+        }
+        let mut downloads: HashMap<String, Option<usize>> = HashMap::new();
+        for (index, ctx) in contexts.iter_mut() {
+            if Self::get_action(&manifest, *index) != FleetAction::Update {
+                continue;
+            }
+            if let Some((file, size)) = Self::required_download(ctx) {
+                downloads.insert(file, size);
+            }
+        }
+        info!("[FleetWorkflow::run] {} file(s) are needed by {} target(s)", downloads.len(), contexts.len());
+        let mut files: Vec<(String, Option<usize>)> = downloads.into_iter().collect();
+        files.sort();
+        for (file, size) in files.iter() {
+            // targets will download it themselves when this fails, so it's only logged.
+            if let Err(e) = Self::download_to_cache(&cache, file, *size).await {
+                error!("[FleetWorkflow::run] Failed to download {} to the cache, {:}", file, e);
+            }
+        }
+
+        info!("[FleetWorkflow::run] Running {} target(s), {} at a time", contexts.len(), jobs);
+        let semaphore = Arc::new(Semaphore::new(jobs));
+        let mut tasks = Vec::new();
+        for (index, mut ctx) in contexts.into_iter() {
+            ctx.download_cache = Some(cache.clone());
+            let action = Self::get_action(&manifest, index);
+            let name = manifest.targets[index].display_name();
+            let semaphore = semaphore.clone();
+            // butler and extracting block the thread they run on, so every target gets its own
+            // thread instead of sharing the runtime's workers.
+            let handle = tokio::runtime::Handle::current();
+            tasks.push((index, tokio::task::spawn_blocking(move || handle.block_on(async move {
+                let _permit = semaphore.acquire_owned().await;
+                let started = Instant::now();
+                info!("[FleetWorkflow::run] Starting {} on {}", action, name);
+                let outcome = Self::run_target(&mut ctx, action).await;
+                info!("[FleetWorkflow::run] {}: {}", name, outcome);
+                (outcome, started.elapsed().as_secs())
+            }))));
+        }
+        for (index, task) in tasks.into_iter() {
+            let (outcome, duration) = match task.await {
+                Ok(v) => v,
+                Err(e) => (FleetOutcome::Failed {
+                    error: format!("Task failed, {:}", e),
+                    exit_code: crate::BeansExitCode::General as i32
+                }, 0)
+            };
+            results[index] = Some(Self::result(&manifest, index, outcome, duration));
+        }
+
+        if temporary {
+            if let Err(e) = std::fs::remove_dir_all(&cache) {
+                warn!("[FleetWorkflow::run] Failed to delete {}, {:}", cache, e);
+            }
+        }
+
+        let results: Vec<FleetTargetResult> = results.into_iter().flatten().collect();
+        Self::print_summary(&results)?;
+        let failed: Vec<String> = results.iter()
+            .filter(|v| matches!(v.outcome, FleetOutcome::Failed { .. }))
+            .map(|v| v.name.clone())
+            .collect();
+        if !failed.is_empty() {
+            return Err(BeansError::FleetTargetsFailed {
+                failed,
+                total: results.len()
+            });
+        }
+        Ok(())
+    }
+
+    /// Print what `run` would do, without downloading anything except `versions.json`, or
+    /// writing anything. The first plan has the files that would be downloaded to the cache,
+    /// then there is a plan for every target (which uses those files from the cache).
+    pub async fn plan(manifest: FleetManifest) -> Result<(), BeansError>
+    {
+        let av = crate::appvar::parse();
+        let version_list = crate::version::get_version_list().await?;
+        let (cache, temporary) = match &manifest.cache {
+            Some(v) => (v.clone(), false),
+            None => (helper::join_path(helper::tmp_dir_location(), "fleet_*".to_string()), true)
+        };
+
+        let mut downloads: HashMap<String, Option<usize>> = HashMap::new();
+        let mut plans: Vec<ActionPlan> = Vec::new();
+        for (index, target) in manifest.targets.iter().enumerate() {
+            let action = Self::get_action(&manifest, index);
+            let result = match Self::create_context(target, version_list.clone()) {
+                Ok(mut ctx) => {
+                    ctx.download_cache = Some(cache.clone());
+                    if action == FleetAction::Update {
+                        if let Some((file, size)) = Self::required_download(&mut ctx) {
+                            downloads.insert(file, size);
+                        }
+                    }
+                    Self::plan_target(&mut ctx, action)
+                },
+                Err(e) => Err(e)
+            };
+            plans.push(match result {
+                Ok(v) => v,
+                Err(e) => {
+                    let mut plan = ActionPlan::for_location(action, target.location.clone());
+                    plan.note(format!("{} would fail, {:}", target.display_name(), e));
+                    plan
+                }
+            });
+        }
+
+        let mut cache_plan = ActionPlan::for_location("fleet download cache", cache.clone());
+        let mut files: Vec<(String, Option<usize>)> = downloads.into_iter().collect();
+        files.sort();
+        for (file, size) in files.iter() {
+            let url = format!("{}{}", &av.remote_info.base_url, file);
+            let location = helper::join_path(cache.clone(), crate::cache_filename(file));
+            let cached_size = std::fs::metadata(&location).map(|v| v.len() as usize).ok();
+            match cached_size.is_some() && (size.is_none() || cached_size == *size) {
+                true => cache_plan.add(PlannedAction::UseCachedFile {
+                    location: location.clone()
+                }),
+                false => cache_plan.add(PlannedAction::Download {
+                    url: url.clone(),
+                    size: *size,
+                    destination: location.clone(),
+                    temporary
+                })
+            }
+            for plan in plans.iter_mut() {
+                plan.use_cached(&url, location.clone());
+            }
+        }
+        if temporary {
+            cache_plan.add(PlannedAction::Delete {
+                location: cache
+            });
+        }
+        plans.insert(0, cache_plan);
+        ActionPlan::print_all(plans, true)
+    }
+    /// Plan what `run_target` would do.
+    fn plan_target(ctx: &mut RunnerContext, action: FleetAction) -> Result<ActionPlan, BeansError>
+    {
+        let (latest_id, latest) = ctx.latest_remote_version();
+        let has_patch = ctx.has_patch_available().is_some();
+        match action {
+            FleetAction::Verify => VerifyWorkflow::plan(ctx),
+            FleetAction::Update => match ctx.current_version {
+                Some(v) if v >= latest_id => {
+                    let mut plan = ActionPlan::new(action, ctx, Some(v));
+                    plan.note(format!("v{} is already the latest version", v));
+                    Ok(plan)
+                },
+                Some(_) if has_patch => UpdateWorkflow::plan(ctx),
+                _ => InstallWorkflow::plan(ctx, latest_id, latest)
+            }
+        }
+    }
+
+    fn create_context(target: &FleetTarget, version_list: crate::version::RemoteVersionResponse) -> Result<RunnerContext, BeansError>
+    {
+        if !helper::is_directory(target.location.clone()) {
+            return Err(BeansError::FileNotFound {
+                location: target.location.clone(),
+                backtrace: std::backtrace::Backtrace::capture()
+            });
+        }
+        let mut ctx = RunnerContext::create_with_version_list(target.location.clone(), version_list)?;
+        if let Some(channel) = &target.channel {
+            ctx.set_channel(channel.clone())?;
+        }
+        Ok(ctx)
+    }
+
+    fn get_action(manifest: &FleetManifest, index: usize) -> FleetAction
+    {
+        manifest.targets[index].action
+            .or(manifest.action)
+            .unwrap_or_default()
+    }
+
+    fn result(manifest: &FleetManifest, index: usize, outcome: FleetOutcome, duration: u64) -> FleetTargetResult
+    {
+        let target = &manifest.targets[index];
+        FleetTargetResult {
+            name: target.display_name(),
+            location: target.location.clone(),
+            action: Self::get_action(manifest, index),
+            outcome,
+            duration
+        }
+    }
+
+    /// Get the file (and the size of it, when known) that updating `ctx` will download.
+    fn required_download(ctx: &mut RunnerContext) -> Option<(String, Option<usize>)>
+    {
+        let (latest_id, latest) = ctx.latest_remote_version();
+        if ctx.current_version.is_some_and(|v| v >= latest_id) {
+            return None;
+        }
+        let patch = ctx.current_version.and_then(|_| ctx.has_patch_available());
+        match patch {
+            Some(p) => Some((p.file, None)),
+            None => latest.file.map(|f| (f, latest.pre_sz))
+        }
+    }
+
+    /// Download `file` (relative to `base_url` in the AppVar) to `cache`, unless it's already
+    /// there.
+    async fn download_to_cache(cache: &str, file: &str, size: Option<usize>) -> Result<(), BeansError>
+    {
+        let location = helper::join_path(cache.to_string(), crate::cache_filename(file));
+        if helper::file_exists(location.clone()) {
+            let cached_size = std::fs::metadata(&location).map(|v| v.len() as usize).ok();
+            if size.is_none() || cached_size == size {
+                info!("[FleetWorkflow::download_to_cache] {} is already cached", file);
+                return Ok(());
+            }
+            debug!("[FleetWorkflow::download_to_cache] Size of {} is {:?}, expected {:?}", location, cached_size, size);
+        }
+        if let Some(size) = size {
+            let available = helper::get_free_space(cache.to_string())?;
+            if (size as u64) >= available {
+                return Err(BeansError::NotEnoughFreeSpace {
+                    location: cache.to_string(),
+                    required: size,
+                    available
+                });
+            }
+        }
+        // downloaded to a separate file, so an interrupted download is never used.
+        let partial = format!("{}.part", location);
+        let av = crate::appvar::parse();
+        info!("[FleetWorkflow::download_to_cache] Downloading {} to {}", file, location);
+        helper::download_with_progress(format!("{}{}", &av.remote_info.base_url, file), partial.clone()).await?;
+        if let Err(e) = std::fs::rename(&partial, &location) {
+            return Err(BeansError::FileWriteFailure {
+                location,
+                error: e
+            });
+        }
+        Ok(())
+    }
+
+    async fn run_target(ctx: &mut RunnerContext, action: FleetAction) -> FleetOutcome
+    {
+        let result = match action {
+            FleetAction::Update => Self::update_target(ctx).await,
+            FleetAction::Verify => Self::verify_target(ctx).await
+        };
+        match result {
+            Ok(v) => v,
+            Err(e) => FleetOutcome::from_error(&e)
+        }
+    }
+
+    async fn update_target(ctx: &mut RunnerContext) -> Result<FleetOutcome, BeansError>
+    {
+        let (latest_id, latest) = ctx.latest_remote_version();
+        let has_patch = ctx.has_patch_available().is_some();
+        let current_version = match ctx.current_version {
+            Some(v) if v >= latest_id => return Ok(FleetOutcome::UpToDate {
+                version: v
+            }),
+            Some(v) if has_patch => v,
+            v => {
+                InstallWorkflow::install_with_remote_version(ctx, latest_id, latest).await?;
+                return Ok(match v {
+                    Some(from) => FleetOutcome::Updated {
+                        from,
+                        to: latest_id
+                    },
+                    None => FleetOutcome::Installed {
+                        version: latest_id
+                    }
+                });
+            }
+        };
+        UpdateWorkflow::wizard(ctx).await?;
+        Ok(match ctx.current_version {
+            Some(v) if v == latest_id => FleetOutcome::Updated {
+                from: current_version,
+                to: v
+            },
+            _ => FleetOutcome::Skipped {
+                reason: "the update didn't finish, check the log for details".to_string()
+            }
+        })
+    }
+
+    async fn verify_target(ctx: &mut RunnerContext) -> Result<FleetOutcome, BeansError>
+    {
+        let version = match ctx.current_version {
+            Some(v) => v,
+            None => return Ok(FleetOutcome::Skipped {
+                reason: "the game isn't installed".to_string()
+            })
+        };
+        VerifyWorkflow::wizard(ctx).await?;
+        Ok(FleetOutcome::Verified {
+            version
+        })
+    }
+
+    fn print_summary(results: &[FleetTargetResult]) -> Result<(), BeansError>
+    {
+        if flags::json_output() {
+            println!("{}", serde_json::to_string(results)?);
+            return Ok(());
+        }
+        let width = results.iter().map(|v| v.name.len()).max().unwrap_or(0);
+        println!("{:=>60}", "=");
+        for result in results.iter() {
+            println!("{:width$}  {:6}  {:>5}s  {}", result.name, result.action.to_string(), result.duration, result.outcome, width = width);
+        }
+        println!("{:=>60}", "=");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::test_dir::TestDir;
+
+    /// Write `content` to `name` in `dir`, then read it with `FleetManifest::read`.
+    fn read(dir: &TestDir, name: &str, content: &str) -> Result<FleetManifest, BeansError>
+    {
+        let location = dir.join(name);
+        std::fs::write(&location, content).unwrap();
+        FleetManifest::read(location.to_string_lossy().to_string())
+    }
+
+    #[test]
+    fn toml_manifest() {
+        let dir = TestDir::new("fleet-toml");
+        let manifest = read(&dir, "fleet.TOML", r#"
+            jobs = 2
+            cache = "/var/cache/beans-rs"
+            action = "verify"
+
+            [[target]]
+            name = "public-1"
+            location = "/srv/tf/public-1/sourcemods"
+            channel = "beta"
+
+            [[target]]
+            location = "/srv/tf/event/sourcemods"
+            action = "update"
+        "#).unwrap();
+        assert_eq!(manifest.jobs, Some(2));
+        assert_eq!(manifest.cache.as_deref(), Some("/var/cache/beans-rs"));
+        assert_eq!(manifest.action, Some(FleetAction::Verify));
+        assert_eq!(manifest.targets.len(), 2);
+        assert_eq!(manifest.targets[0].display_name(), "public-1");
+        assert_eq!(manifest.targets[0].channel.as_deref(), Some("beta"));
+        assert_eq!(manifest.targets[0].action, None);
+        assert_eq!(manifest.targets[1].display_name(), "/srv/tf/event/sourcemods");
+        assert_eq!(manifest.targets[1].action, Some(FleetAction::Update));
+    }
+
+    #[test]
+    fn json_manifest() {
+        let dir = TestDir::new("fleet-json");
+        let manifest = read(&dir, "fleet.json", r#"{
+            "targets": [{ "location": "/srv/tf/public-1/sourcemods" }]
+        }"#).unwrap();
+        assert_eq!(manifest.jobs, None);
+        assert_eq!(manifest.action, None);
+        assert_eq!(manifest.targets[0].location, "/srv/tf/public-1/sourcemods");
+
+        // `target` works in JSON too, and anything that isn't `.toml` is read as JSON.
+        let manifest = read(&dir, "fleet", r#"{ "target": [{ "location": "/srv/a" }, { "location": "/srv/b" }] }"#).unwrap();
+        assert_eq!(manifest.targets.len(), 2);
+        assert!(matches!(read(&dir, "fleet.conf", "[[target]]\nlocation = \"/srv/a\""), Err(BeansError::FleetManifestInvalid { .. })));
+    }
+
+    #[test]
+    fn invalid_manifest() {
+        let dir = TestDir::new("fleet-invalid");
+        let reason = |v: Result<FleetManifest, BeansError>| match v {
+            Err(BeansError::FleetManifestInvalid { reason, .. }) => reason,
+            v => panic!("expected FleetManifestInvalid, got {:?}", v)
+        };
+        assert_eq!(reason(read(&dir, "empty.json", r#"{ "targets": [] }"#)), "There are no targets");
+        assert_eq!(reason(read(&dir, "jobs.toml", "jobs = 0\n[[target]]\nlocation = \"/srv/a\"")), "jobs must be at least 1");
+        reason(read(&dir, "action.json", r#"{ "targets": [{ "location": "/srv/a", "action": "install" }] }"#));
+        reason(read(&dir, "location.toml", "[[target]]\nname = \"a\""));
+        assert!(matches!(FleetManifest::read(dir.join("missing.toml").to_string_lossy().to_string()),
+            Err(BeansError::FileOpenFailure { .. })));
+    }
+}
\ No newline at end of file
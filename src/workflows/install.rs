@@ -1,7 +1,10 @@
-use log::{debug, error, warn};
-use crate::{DownloadFailureReason, helper, RunnerContext};
+use log::{debug, error, info, warn};
+use crate::{DownloadFailureReason, flags, helper, RunnerContext};
 use crate::BeansError;
-use crate::version::{AdastralVersionFile, RemoteVersion};
+use crate::hooks::{HookOperation, HookRunner};
+use crate::ownership::OwnershipRepair;
+use crate::version::RemoteVersion;
+use crate::workflows::{ActionPlan, PlannedAction};
 
 #[derive(Debug, Clone)]
 pub struct InstallWorkflow {
@@ -11,6 +14,9 @@ impl InstallWorkflow {
     pub async fn wizard(ctx: &mut RunnerContext) -> Result<(), BeansError>
     {
         let (latest_remote_id, latest_remote) = ctx.latest_remote_version();
+        if flags::dry_run() {
+            return Self::plan(ctx, latest_remote_id, latest_remote)?.print();
+        }
         if let Some(_cv) = ctx.current_version {
             println!("[InstallWorkflow::wizard] re-installing! game files will not be touched until extraction");
         }
@@ -31,27 +37,261 @@ impl InstallWorkflow {
             }
         };
         let mut ctx = self.context.clone();
+        if flags::dry_run() {
+            return Self::plan(&mut ctx, version_id, target_version.clone())?.print();
+        }
         InstallWorkflow::install_with_remote_version(&mut ctx, version_id, target_version.clone()).await
     }
 
+    /// Plan what `install_with_remote_version` would do, without downloading the package or
+    /// writing anything.
+    pub fn plan(ctx: &mut RunnerContext, version_id: usize, version: RemoteVersion) -> Result<ActionPlan, BeansError>
+    {
+        ctx.remote_version_list.check_min_beans_version()?;
+        let mut plan = ActionPlan::new(HookOperation::Install, ctx, Some(version_id));
+        let out_dir = ctx.sourcemod_path.clone();
+        let cached = version.file.as_ref().and_then(|v| ctx.get_cached_file(v));
+        let repair = OwnershipRepair::new(ctx)?;
+        HookRunner::new(ctx, HookOperation::Install, Some(version_id)).plan(&mut plan, |plan| {
+            let package = match (&cached, &version.file) {
+                (Some(v), _) => {
+                    plan.add(PlannedAction::UseCachedFile {
+                        location: v.clone()
+                    });
+                    v.clone()
+                },
+                (None, Some(file)) => {
+                    let av = crate::appvar::parse();
+                    let location = helper::join_path(helper::tmp_dir_location(), "presz_*".to_string());
+                    plan.add(PlannedAction::Download {
+                        url: format!("{}{}", &av.remote_info.base_url, file),
+                        size: version.pre_sz,
+                        destination: location.clone(),
+                        temporary: true
+                    });
+                    location
+                },
+                (None, None) => return Err(BeansError::RemoteVersionNotFound {
+                    version: Some(version_id)
+                })
+            };
+            plan.add_extract(package.clone(), None, &out_dir, None, version.post_sz);
+            Self::plan_after_extract(plan, &out_dir, Some(version_id), &repair);
+            if cached.is_none() {
+                plan.add(PlannedAction::Delete {
+                    location: package
+                });
+            }
+            Ok(())
+        })?;
+        Ok(plan)
+    }
+
+    /// Install `version` to `ctx.sourcemod_path`, and run the install hooks around it.
     pub async fn install_with_remote_version(ctx: &mut RunnerContext, version_id: usize, version: RemoteVersion)
         -> Result<(), BeansError>
     {
+        let repair = OwnershipRepair::new(ctx)?;
+        HookRunner::new(ctx, HookOperation::Install, Some(version_id))
+            .run(repair.run(Self::download_and_install(ctx, version_id, version))).await
+    }
+    async fn download_and_install(ctx: &mut RunnerContext, version_id: usize, version: RemoteVersion)
+        -> Result<(), BeansError>
+    {
+        ctx.remote_version_list.check_min_beans_version()?;
         println!("{:=>60}\nInstalling version {} to {}\n{0:=>60}", "=", version_id, &ctx.sourcemod_path);
-        let presz_loc = RunnerContext::download_package(version).await?;
-        Self::install_from(presz_loc.clone(), ctx.sourcemod_path.clone(), Some(version_id)).await?;
-        if helper::file_exists(presz_loc.clone()) {
+        let cached = version.file.as_ref().and_then(|v| ctx.get_cached_file(v));
+        let presz_loc = match &cached {
+            Some(v) => {
+                info!("[InstallWorkflow::download_and_install] Using cached package {}", v);
+                v.clone()
+            },
+            None => RunnerContext::download_package(version.clone()).await?
+        };
+        Self::install_from(presz_loc.clone(), ctx.sourcemod_path.clone(), Some(version_id), version.post_sz).await?;
+        ctx.current_version = Some(version_id);
+        ctx.write_version_file()?;
+        if cached.is_none() && helper::file_exists(presz_loc.clone()) {
             std::fs::remove_file(presz_loc)?;
         }
         Ok(())
     }
 
-    /// Install the `.tar.zstd` file at `package_loc` to `out_dir`
-    /// package_loc: Location to a file that is a `.tar.zstd` file.
+    /// Install the package at `location` (which can also be an `http://` or `https://` URL) to
+    /// `out_dir` with `install_from`. Packages from a URL are downloaded to a temporary file,
+    /// which is deleted afterwards.
+    /// sha256: Expected SHA256 hash of the package (as hex). Nothing is installed when it's
+    /// different. Ignored for directories.
+    pub async fn install_from_location(location: String, out_dir: String, version_id: Option<usize>, sha256: Option<String>)
+        -> Result<(), BeansError>
+    {
+        let sha256 = match sha256 {
+            Some(v) => Some(Self::parse_sha256(&v)?),
+            None => None
+        };
+        let url_filename = Self::url_filename(&location);
+        let is_url = url_filename.is_some();
+        let (package_loc, result) = match url_filename {
+            Some(filename) => {
+                let out_loc = helper::get_tmp_file(filename);
+                println!("[InstallWorkflow::install_from_location] Downloading {}", location);
+                let result = helper::download_with_progress(location.clone(), out_loc.clone()).await;
+                (out_loc, result)
+            },
+            None => (location.clone(), Ok(()))
+        };
+        let result = match (result, sha256) {
+            (Err(e), _) => Err(e),
+            (Ok(_), Some(expected)) if !helper::is_directory(package_loc.clone()) => {
+                match crate::selfupdate::sha256_file(package_loc.clone()) {
+                    Ok(actual) if actual != expected.to_lowercase() => Err(BeansError::PackageHashMismatch {
+                        location: location.clone(),
+                        expected,
+                        actual
+                    }),
+                    Ok(_) => {
+                        info!("[InstallWorkflow::install_from_location] SHA256 of {} matches", location);
+                        Ok(())
+                    },
+                    Err(e) => Err(e)
+                }
+            },
+            (Ok(_), _) => Ok(())
+        };
+        let result = match result {
+            Ok(_) => Self::install_from(package_loc.clone(), out_dir, version_id, None).await,
+            Err(e) => Err(e)
+        };
+        if is_url && helper::file_exists(package_loc.clone()) {
+            if let Err(e) = std::fs::remove_file(&package_loc) {
+                warn!("[InstallWorkflow::install_from_location] Failed to delete {} ({:})", package_loc, e);
+            }
+        }
+        result
+    }
+
+    /// When `location` is an HTTP(S) URL, get the filename that the package is downloaded as (the
+    /// last part of the path, or `package` when it's empty). `None` when it's a local location.
+    fn url_filename(location: &str) -> Option<String>
+    {
+        if !location.starts_with("http://") && !location.starts_with("https://") {
+            return None;
+        }
+        let filename = location.split(['?', '#']).next().unwrap_or_default()
+            .rsplit('/').next()
+            .filter(|v| !v.is_empty())
+            .unwrap_or("package");
+        Some(filename.to_string())
+    }
+
+    /// Check that `value` is a SHA256 hash (64 hex characters), and make it lowercase.
+    pub fn parse_sha256(value: &str) -> Result<String, BeansError>
+    {
+        let value = value.trim().to_lowercase();
+        if value.len() != 64 || !value.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(BeansError::InvalidArgument {
+                name: "sha256".to_string(),
+                value,
+                reason: "should be 64 hex characters".to_string()
+            });
+        }
+        Ok(value)
+    }
+
+    /// Plan what `install_from_location` (with the install hooks around it) would do, without
+    /// downloading or writing anything. Local packages are checked like `install_from` does, so
+    /// this fails the same way when the package isn't safe to extract or the hash is different.
+    pub fn plan_from_location(ctx: &mut RunnerContext, location: String, version_id: Option<usize>, sha256: Option<String>)
+        -> Result<ActionPlan, BeansError>
+    {
+        let sha256 = match sha256 {
+            Some(v) => Some(Self::parse_sha256(&v)?),
+            None => None
+        };
+        ctx.remote_version_list.check_min_beans_version()?;
+        let mut plan = ActionPlan::new(HookOperation::Install, ctx, version_id);
+        let out_dir = ctx.sourcemod_path.clone();
+        let repair = OwnershipRepair::new(ctx)?;
+        HookRunner::new(ctx, HookOperation::Install, version_id).plan(&mut plan, |plan| {
+            if let Some(filename) = Self::url_filename(&location) {
+                let package_loc = helper::join_path(helper::tmp_dir_location(), format!("*_{}", filename));
+                plan.add(PlannedAction::Download {
+                    url: location.clone(),
+                    size: None,
+                    destination: package_loc.clone(),
+                    temporary: true
+                });
+                if let Some(v) = &sha256 {
+                    plan.add(PlannedAction::CheckHash {
+                        location: package_loc.clone(),
+                        sha256: v.to_lowercase()
+                    });
+                }
+                plan.add_extract(package_loc.clone(), None, &out_dir, None, None);
+                Self::plan_after_extract(plan, &out_dir, version_id, &repair);
+                plan.add(PlannedAction::Delete {
+                    location: package_loc
+                });
+                return Ok(());
+            }
+
+            if helper::file_exists(location.clone()) == false {
+                return Err(BeansError::DownloadFailure {
+                    reason: DownloadFailureReason::FileNotFound {
+                        location: location.clone()
+                    }
+                });
+            }
+            let format = crate::extract::PackageFormat::detect(&location)?;
+            match &sha256 {
+                Some(expected) if format != crate::extract::PackageFormat::Directory => {
+                    let actual = crate::selfupdate::sha256_file(location.clone())?;
+                    if actual != expected.to_lowercase() {
+                        return Err(BeansError::PackageHashMismatch {
+                            location: location.clone(),
+                            expected: expected.clone(),
+                            actual
+                        });
+                    }
+                    plan.add(PlannedAction::CheckHash {
+                        location: location.clone(),
+                        sha256: actual
+                    });
+                },
+                _ => {}
+            }
+            let summary = crate::extract::list_package(&location, &out_dir, None)?;
+            if summary.is_none() {
+                plan.note(format!("{} packages can only be checked once they've been decompressed", format));
+            }
+            plan.add_extract(location.clone(), Some(format), &out_dir, summary, None);
+            Self::plan_after_extract(plan, &out_dir, version_id, &repair);
+            Ok(())
+        })?;
+        Ok(plan)
+    }
+    /// Add what `install_from` does after extracting, and what `repair` does once it's done.
+    fn plan_after_extract(plan: &mut ActionPlan, out_dir: &str, version_id: Option<usize>, repair: &OwnershipRepair)
+    {
+        let mod_location = helper::join_path(out_dir.to_string(), crate::data_dir());
+        plan.add_mod_folder_fixes(mod_location.clone());
+        match version_id {
+            Some(_) => plan.add(PlannedAction::WriteFile {
+                location: helper::join_path(mod_location, ".adastral".to_string())
+            }),
+            None => plan.note("Not writing .adastral since the version wasn't provided")
+        }
+        repair.plan(plan);
+    }
+
+    /// Install the package at `package_loc` to `out_dir`
+    /// package_loc: Location of a `.tar.zst`, `.tar.gz`, `.tar.xz`, `.tar` or `.zip` file, or
+    /// a directory that has already been extracted.
     /// out_dir: should be `RunnerContext.sourcemod_path`
     /// version_id: Version that is from `package_loc`. When not specified, `.adastral` will not be written to.
-    /// Note: This function doesn't check the extension when extracting.
-    pub async fn install_from(package_loc: String, out_dir: String, version_id: Option<usize>)
+    /// max_size: `postsz` of the package. Extracting fails when the files in it are larger than this.
+    /// Note: The format is detected with the content of the file instead of the extension.
+    pub async fn install_from(package_loc: String, out_dir: String, version_id: Option<usize>, max_size: Option<usize>)
         -> Result<(), BeansError>
     {
         if helper::file_exists(package_loc.clone()) == false {
@@ -63,12 +303,16 @@ impl InstallWorkflow {
             });
         }
 
+        crate::process::ensure_game_not_running(helper::join_path(out_dir.clone(), crate::data_dir())).await?;
         println!("[InstallWorkflow::Wizard] Extracting to {out_dir}");
-        RunnerContext::extract_package(package_loc, out_dir.clone())?;
+        RunnerContext::extract_package(package_loc, out_dir.clone(), max_size)?;
+        RunnerContext::normalize_permissions(helper::join_path(out_dir.clone(), crate::data_dir()))?;
+        RunnerContext::repair_symlinks(helper::join_path(out_dir.clone(), crate::data_dir()))?;
         if let Some(lri) = version_id {
-            let x = AdastralVersionFile {
-                version: lri.to_string()
-            }.write(Some(out_dir.clone()));
+            // keep everything else in .adastral (like the channel) when re-installing.
+            let mut x = crate::version::get_version_file(Some(out_dir.clone())).unwrap_or_default();
+            x.version = lri.to_string();
+            let x = x.write(Some(out_dir.clone()));
             if let Err(e) = x {
                 println!("[InstallWorkflow::install_from] Failed to set version to {} in .adastral", lri);
                 debug!("{:#?}", e);
@@ -85,4 +329,17 @@ impl InstallWorkflow {
 #[cfg(not(target_os = "windows"))]
 pub const INSTALL_FINISH_MSG: &str = include_str!("../text/install_complete_linux.txt");
 #[cfg(target_os = "windows")]
-pub const INSTALL_FINISH_MSG: &str = include_str!("../text/install_complete_windows.txt");
\ No newline at end of file
+pub const INSTALL_FINISH_MSG: &str = include_str!("../text/install_complete_windows.txt");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_filename() {
+        assert_eq!(InstallWorkflow::url_filename("https://example.com/of/open_fortress.tar.zst?token=a#b").as_deref(), Some("open_fortress.tar.zst"));
+        assert_eq!(InstallWorkflow::url_filename("http://example.com/").as_deref(), Some("package"));
+        assert_eq!(InstallWorkflow::url_filename("/srv/packages/open_fortress.tar.zst"), None);
+        assert_eq!(InstallWorkflow::url_filename("ftp://example.com/a.tar"), None);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,182 @@
+use std::backtrace::Backtrace;
+use std::process::Command;
+use log::{debug, info, warn};
+use crate::{BeansError, helper, RunnerContext};
+
+/// App ID for Source SDK Base 2013 Multiplayer.
+pub const SOURCE_SDK_APPID: u32 = 243750;
+/// Name of the folder in `steamapps/common` where Source SDK Base 2013 Multiplayer is installed.
+pub const SOURCE_SDK_DIRNAME: &str = "Source SDK Base 2013 Multiplayer";
+/// Executables to look for in the Source SDK Base 2013 folder, in order of preference.
+#[cfg(target_os = "windows")]
+pub const SOURCE_SDK_EXECUTABLES: &[&str] = &["hl2.exe"];
+#[cfg(not(target_os = "windows"))]
+pub const SOURCE_SDK_EXECUTABLES: &[&str] = &["hl2.sh", "hl2_linux"];
+
+/// What will be run to launch the game.
+#[derive(Debug, Clone)]
+pub enum LaunchTarget
+{
+    /// Run the executable from Source SDK Base 2013 directly.
+    Executable {
+        program: String,
+        working_directory: String,
+        args: Vec<String>
+    },
+    /// Open a `steam://rungameid` URL. Used when Source SDK Base 2013 couldn't be found.
+    SteamUrl(String)
+}
+
+#[derive(Debug, Clone)]
+pub struct LaunchWorkflow {
+    pub context: RunnerContext
+}
+impl LaunchWorkflow {
+    pub async fn wizard(ctx: &mut RunnerContext) -> Result<(), BeansError>
+    {
+        Self::launch(ctx, Vec::new(), false)
+    }
+
+    /// Launch the installed mod with the `args` provided.
+    ///
+    /// When the installed version is older than the latest version, `BeansError::LaunchOutOfDate`
+    /// is returned, unless `force` is `true`.
+    pub fn launch(ctx: &mut RunnerContext, args: Vec<String>, force: bool) -> Result<(), BeansError>
+    {
+        crate::workflows::ensure_not_dry_run("launch")?;
+        let current_version = match ctx.current_version {
+            Some(v) => v,
+            None => {
+                return Err(BeansError::TargetSourcemodNotInstalled {
+                    missing_file: format!("{}.adastral", ctx.get_mod_location()),
+                    backtrace: Backtrace::capture()
+                });
+            }
+        };
+        let (latest_version, _) = ctx.latest_remote_version();
+        if current_version < latest_version {
+            if !force {
+                return Err(BeansError::LaunchOutOfDate {
+                    current: current_version,
+                    latest: latest_version
+                });
+            }
+            warn!("[LaunchWorkflow::launch] Launching v{} even though v{} is available", current_version, latest_version);
+        }
+
+        let target = Self::get_target(ctx, args);
+        Self::run_target(target)
+    }
+
+    /// Figure out what should be run to launch the game with the `args` provided.
+    pub fn get_target(ctx: &mut RunnerContext, args: Vec<String>) -> LaunchTarget
+    {
+        let mut mod_location = ctx.get_mod_location();
+        while mod_location.ends_with(crate::PATH_SEP) {
+            mod_location.pop();
+        }
+        let mut game_args = vec![String::from("-game"), mod_location];
+        game_args.extend(args);
+
+        match Self::find_sdk_executable(ctx.sourcemod_path.clone()) {
+            Some((program, working_directory)) => LaunchTarget::Executable {
+                program,
+                working_directory,
+                args: game_args
+            },
+            None => {
+                let launch_options = join_args(&game_args);
+                LaunchTarget::SteamUrl(format!("steam://rungameid/{}//{}", SOURCE_SDK_APPID, url_encode(&launch_options)))
+            }
+        }
+    }
+
+    /// Find the executable for Source SDK Base 2013 in any of the steam library folders.
+    /// Returns the location of the executable, and the folder that it's in.
+    pub fn find_sdk_executable(sourcemods_location: String) -> Option<(String, String)>
+    {
+        let steam_location = match helper::get_steam_location(sourcemods_location) {
+            Ok(v) => v,
+            Err(e) => {
+                debug!("[LaunchWorkflow::find_sdk_executable] Failed to find steam {:#?}", e);
+                return None;
+            }
+        };
+        for library in helper::find_steam_library_folders(steam_location) {
+            let sdk_location = helper::join_path(library, format!("steamapps/common/{}", SOURCE_SDK_DIRNAME));
+            for exe in SOURCE_SDK_EXECUTABLES.iter() {
+                let exe_location = helper::join_path(sdk_location.clone(), exe.to_string());
+                if helper::file_exists(exe_location.clone()) {
+                    debug!("[LaunchWorkflow::find_sdk_executable] Found {}", exe_location);
+                    return Some((exe_location, sdk_location));
+                }
+            }
+        }
+        None
+    }
+
+    fn run_target(target: LaunchTarget) -> Result<(), BeansError>
+    {
+        let mut cmd = match &target {
+            LaunchTarget::Executable { program, working_directory, args } => {
+                let mut c = Command::new(program);
+                c.args(args).current_dir(working_directory);
+                c
+            },
+            LaunchTarget::SteamUrl(url) => open_url_command(url)
+        };
+        debug!("[LaunchWorkflow::run_target] {:#?}", cmd);
+        match cmd.spawn() {
+            Ok(_) => {
+                info!("Launching {}", crate::appvar::parse().mod_info.name_stylized);
+                Ok(())
+            },
+            Err(e) => Err(BeansError::LaunchFailure {
+                program: format!("{:?}", cmd.get_program()),
+                error: e,
+                backtrace: Backtrace::capture()
+            })
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn open_url_command(url: &str) -> Command
+{
+    let mut c = Command::new("cmd");
+    c.args(["/C", "start", "", url]);
+    c
+}
+#[cfg(not(target_os = "windows"))]
+fn open_url_command(url: &str) -> Command
+{
+    let mut c = Command::new("xdg-open");
+    c.arg(url);
+    c
+}
+
+/// Join `args` into a single string like Steam launch options, wrapping arguments that have
+/// spaces in them with double quotes.
+pub fn join_args(args: &[String]) -> String
+{
+    args.iter()
+        .map(|v| match v.contains(' ') {
+            true => format!("\"{}\"", v),
+            false => v.clone()
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Percent-encode everything that isn't safe to put in a `steam://` URL.
+fn url_encode(value: &str) -> String
+{
+    let mut result = String::new();
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => result.push(b as char),
+            _ => result.push_str(&format!("%{:02X}", b))
+        }
+    }
+    result
+}
\ No newline at end of file
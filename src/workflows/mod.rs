@@ -2,8 +2,22 @@ mod install;
 mod update;
 mod verify;
 mod clean;
+mod launch;
+mod shortcut;
+mod status;
+mod addons;
+mod watch;
+mod fleet;
+mod plan;
 
 pub use install::*;
 pub use update::*;
 pub use verify::*;
-pub use clean::*;
\ No newline at end of file
+pub use clean::*;
+pub use launch::*;
+pub use shortcut::*;
+pub use status::*;
+pub use addons::*;
+pub use watch::*;
+pub use fleet::*;
+pub use plan::*;
\ No newline at end of file
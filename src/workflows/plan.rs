@@ -0,0 +1,615 @@
+use std::fmt::{Display, Formatter};
+use crate::{BeansError, flags, helper, RunnerContext};
+use crate::extract::{ArchiveSummary, PackageFormat};
+use crate::webhook::{WebhookConfig, WebhookEvent};
+
+/// Amount of deleted or overwritten files that are shown in the text output. Every file is
+/// included in the JSON output.
+pub const PLAN_MAX_LISTED_FILES: usize = 20;
+
+/// Something that a workflow would do. Shown with `ActionPlan` when `--dry-run` is used, instead
+/// of doing it.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PlannedAction
+{
+    /// Run a hook from `HookConfig`.
+    RunHook {
+        hook: String,
+        command: String
+    },
+    /// Wait for the players to leave the dedicated server (see `WaitForEmptyConfig`).
+    WaitForEmptyServer {
+        address: String,
+        timeout: u64
+    },
+    /// Stop the dedicated server with RCON (see `RconConfig`).
+    StopServer {
+        address: String
+    },
+    /// Download `url` to `destination`. `size` is `None` when it isn't in `versions.json`.
+    Download {
+        url: String,
+        size: Option<usize>,
+        destination: String,
+        /// `destination` is deleted once the workflow is done with it.
+        temporary: bool
+    },
+    /// Use a file that has already been downloaded to the download cache.
+    UseCachedFile {
+        location: String
+    },
+    /// Check that the SHA256 hash of a package is `sha256`.
+    CheckHash {
+        location: String,
+        sha256: String
+    },
+    /// Copy `gameinfo.txt` to the backup folder in the mod folder.
+    BackupGameinfo {
+        location: String
+    },
+    /// Verify the mod folder with butler, and download the files that are corrupted from the
+    /// heal archive.
+    Verify {
+        location: String,
+        signature_url: String,
+        heal_url: String
+    },
+    /// Apply a patch with butler.
+    Patch {
+        from: usize,
+        to: usize,
+        patch: String,
+        location: String,
+        staging: String,
+        /// Amount of space that butler needs while patching (`tempreq`).
+        temp_space: usize
+    },
+    /// Extract a package (or copy a directory) to `destination`.
+    Extract {
+        package: String,
+        /// `None` when the package hasn't been downloaded yet.
+        format: Option<PackageFormat>,
+        destination: String,
+        /// Size of the files in the package.
+        size: Option<usize>,
+        /// Amount of files in the package, when it can be checked before downloading.
+        files: Option<usize>,
+        /// Size of the `.tar` that compressed packages are decompressed to.
+        temp_space: usize
+    },
+    /// Delete a file or directory.
+    Delete {
+        location: String
+    },
+    /// Create or replace a file that beans-rs manages (e.g; `.adastral`).
+    WriteFile {
+        location: String
+    },
+    /// Add missing permissions to the files in the mod folder (see `RunnerContext::normalize_permissions`).
+    NormalizePermissions {
+        location: String
+    },
+    /// Create the symlinks from `AppVarMod.symlinks`. `links` are the ones that are currently
+    /// missing or broken.
+    RepairSymlinks {
+        location: String,
+        links: Vec<String>
+    },
+    /// Change the owner of every file in `location` (see `InstallOwner`).
+    ChangeOwner {
+        location: String,
+        uid: u32,
+        gid: u32
+    },
+    /// Send a webhook. Only the host of `url` is included, since the rest can be a secret.
+    SendWebhook {
+        event: WebhookEvent,
+        url: String
+    }
+}
+impl Display for PlannedAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let size = |v: &Option<usize>| match v {
+            Some(v) => helper::format_size(*v),
+            None => "unknown size".to_string()
+        };
+        match self {
+            PlannedAction::RunHook { hook, command }
+            => write!(f, "Run the {} hook: {}", hook, command),
+            PlannedAction::WaitForEmptyServer { address, timeout }
+            => write!(f, "Wait up to {}s for {} to be empty", timeout, address),
+            PlannedAction::StopServer { address }
+            => write!(f, "Stop the server at {} with RCON", address),
+            PlannedAction::Download { url, size: s, destination, temporary }
+            => write!(f, "Download {} ({}) to {}{}", url, size(s), destination, if *temporary { ", deleted afterwards" } else { "" }),
+            PlannedAction::UseCachedFile { location }
+            => write!(f, "Use {} from the download cache", location),
+            PlannedAction::CheckHash { location, sha256 }
+            => write!(f, "Check that the SHA256 of {} is {}", location, sha256),
+            PlannedAction::BackupGameinfo { location }
+            => write!(f, "Back up {}", location),
+            PlannedAction::Verify { location, signature_url, heal_url }
+            => write!(f, "Verify {} with {}, and repair corrupted files from {}", location, signature_url, heal_url),
+            PlannedAction::Patch { from, to, patch, location, staging, temp_space }
+            => write!(f, "Patch {} from v{} to v{} with {} (staging in {}, needs {})", location, from, to, patch, staging, helper::format_size(*temp_space)),
+            PlannedAction::Extract { package, format, destination, size: s, files, .. } => {
+                write!(f, "Extract {}", package)?;
+                if let Some(v) = format {
+                    write!(f, " ({})", v)?;
+                }
+                write!(f, " to {}, {}", destination, size(s))?;
+                match files {
+                    Some(v) => write!(f, " in {} files", v),
+                    None => Ok(())
+                }
+            },
+            PlannedAction::Delete { location }
+            => write!(f, "Delete {}", location),
+            PlannedAction::WriteFile { location }
+            => write!(f, "Write {}", location),
+            PlannedAction::NormalizePermissions { location }
+            => write!(f, "Add missing permissions to the files in {}", location),
+            PlannedAction::RepairSymlinks { location, links } => match links.is_empty() {
+                true => write!(f, "Check the symlinks in {}", location),
+                false => write!(f, "Create the symlinks in {} ({})", location, links.join(", "))
+            },
+            PlannedAction::ChangeOwner { location, uid, gid }
+            => write!(f, "Change the owner of {} to {}:{}", location, uid, gid),
+            PlannedAction::SendWebhook { event, url }
+            => write!(f, "Send \"{}\" to {}", event, url)
+        }
+    }
+}
+
+/// Everything that a workflow would do, which is built (without downloading anything except
+/// `versions.json`, or writing anything) when `LaunchFlag::DRY_RUN` is set. Created with
+/// `ActionPlan::new`, then filled in by the `plan` function of the workflow.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ActionPlan
+{
+    /// Workflow that was planned (e.g; `install`, `update`, `addons remove`)
+    pub operation: String,
+    /// Mod folder that the plan is for, or the download cache for the first plan from
+    /// `FleetWorkflow::plan`.
+    pub location: String,
+    pub current_version: Option<usize>,
+    pub target_version: Option<usize>,
+    pub actions: Vec<PlannedAction>,
+    /// Every URL that would be downloaded from. Set by `ActionPlan::finish`.
+    pub urls: Vec<String>,
+    /// Size of the downloads that are in `versions.json`, added together. Set by `ActionPlan::finish`.
+    pub download_size: usize,
+    /// Most temporary space that would be used. Set by `ActionPlan::finish`.
+    pub temp_space: usize,
+    /// Files and directories that would be deleted. Set by `ActionPlan::finish`.
+    pub deleted: Vec<String>,
+    /// Files that already exist, and would be replaced.
+    pub overwritten: Vec<String>,
+    /// Things that can't be known until the workflow runs, and why something would be skipped.
+    pub notes: Vec<String>
+}
+impl ActionPlan
+{
+    pub fn new(operation: impl Display, ctx: &mut RunnerContext, target_version: Option<usize>) -> Self
+    {
+        let mut result = Self::for_location(operation, ctx.get_mod_location());
+        result.current_version = ctx.current_version;
+        result.target_version = target_version;
+        result
+    }
+    /// Create a plan for `location`, which doesn't have to be an install.
+    pub fn for_location(operation: impl Display, location: String) -> Self
+    {
+        Self {
+            operation: operation.to_string(),
+            location,
+            current_version: None,
+            target_version: None,
+            actions: Vec::new(),
+            urls: Vec::new(),
+            download_size: 0,
+            temp_space: 0,
+            deleted: Vec::new(),
+            overwritten: Vec::new(),
+            notes: Vec::new()
+        }
+    }
+
+    pub fn add(&mut self, action: PlannedAction)
+    {
+        self.actions.push(action);
+    }
+    pub fn note(&mut self, note: impl Into<String>)
+    {
+        let note = note.into();
+        if !self.notes.contains(&note) {
+            self.notes.push(note);
+        }
+    }
+
+    /// Add the files in `files` (relative to `out_dir`) that already exist to `overwritten`.
+    pub fn add_overwritten(&mut self, out_dir: &str, files: &[String])
+    {
+        for file in files.iter() {
+            let location = std::path::Path::new(out_dir).join(file);
+            if std::fs::symlink_metadata(&location).is_ok() {
+                self.overwritten.push(location.to_string_lossy().to_string());
+            }
+        }
+    }
+
+    /// Add what extracting `summary` from a package would do, or a note with the amount of files
+    /// that could be overwritten when the files in the package aren't known yet.
+    pub fn add_extract(&mut self, package: String, format: Option<PackageFormat>, out_dir: &str, summary: Option<ArchiveSummary>, max_size: Option<usize>)
+    {
+        let temp_space = match format {
+            Some(PackageFormat::Tar) | Some(PackageFormat::Zip) | Some(PackageFormat::Directory) => 0,
+            _ => summary.as_ref().map(|v| v.total_size as usize).or(max_size).unwrap_or(0)
+        };
+        self.add(PlannedAction::Extract {
+            package,
+            format,
+            destination: out_dir.to_string(),
+            size: summary.as_ref().map(|v| v.total_size as usize).or(max_size),
+            files: summary.as_ref().map(|v| v.files.len()),
+            temp_space
+        });
+        match summary {
+            Some(v) => {
+                if !v.skipped.is_empty() {
+                    self.note(format!("{} files in the package wouldn't be extracted, since beans-rs keeps its state in them: {}", v.skipped.len(), v.skipped.join(", ")));
+                }
+                self.add_overwritten(out_dir, &v.files)
+            },
+            None => {
+                let existing = count_files(&self.location);
+                if existing > 0 {
+                    self.note(format!("The files in the package aren't known until it's downloaded or decompressed. Any of the {} files in {} could be overwritten", existing, self.location));
+                }
+            }
+        }
+    }
+
+    /// Add `RunnerContext::normalize_permissions` and `RunnerContext::repair_symlinks`, when they
+    /// would do anything on this platform.
+    pub fn add_mod_folder_fixes(&mut self, mod_location: String)
+    {
+        let av = crate::appvar::parse();
+        if cfg!(target_os = "linux") && av.mod_info.permissions.enabled {
+            self.add(PlannedAction::NormalizePermissions {
+                location: mod_location.clone()
+            });
+        }
+        if cfg!(target_os = "linux") && !av.mod_info.symlinks.is_empty() {
+            let links = RunnerContext::check_symlinks(mod_location.clone()).into_iter()
+                .filter(|v| v.state != crate::SymlinkState::Ok)
+                .map(|v| v.link)
+                .collect();
+            self.add(PlannedAction::RepairSymlinks {
+                location: mod_location,
+                links
+            });
+        }
+    }
+
+    /// Add `event` for every webhook that wants it.
+    pub fn add_webhooks(&mut self, ctx: &RunnerContext, event: WebhookEvent)
+    {
+        for webhook in WebhookConfig::get(ctx.sourcemod_path.clone()).iter() {
+            if webhook.wants(event) {
+                self.add(PlannedAction::SendWebhook {
+                    event,
+                    url: redact_url(&webhook.url)
+                });
+            }
+        }
+    }
+
+    /// Use the file at `location` instead of downloading `url`, since it will already have been
+    /// downloaded by then (see `FleetWorkflow::plan`).
+    pub fn use_cached(&mut self, url: &str, location: String)
+    {
+        let mut replaced: Vec<String> = Vec::new();
+        for action in self.actions.iter_mut() {
+            if let PlannedAction::Download { url: u, destination, .. } = action {
+                if u == url {
+                    replaced.push(destination.clone());
+                    *action = PlannedAction::UseCachedFile {
+                        location: location.clone()
+                    };
+                }
+            }
+        }
+        // cached files are never deleted by workflows.
+        self.actions.retain(|v| !matches!(v, PlannedAction::Delete { location } if replaced.contains(location)));
+        for action in self.actions.iter_mut() {
+            match action {
+                PlannedAction::Extract { package: v, .. } | PlannedAction::Patch { patch: v, .. } if replaced.contains(v) => {
+                    *v = location.clone();
+                },
+                _ => {}
+            }
+        }
+    }
+
+    /// Set `urls`, `download_size`, `temp_space` and `deleted` from `actions`.
+    pub fn finish(&mut self)
+    {
+        self.urls.clear();
+        self.deleted.clear();
+        self.download_size = 0;
+        self.temp_space = 0;
+        // temporary files that haven't been deleted yet, since the space they use adds up.
+        let mut temporary: Vec<(String, usize)> = Vec::new();
+        for action in self.actions.iter() {
+            match action {
+                PlannedAction::Download { url, size, destination, temporary: is_temporary } => {
+                    self.urls.push(url.clone());
+                    self.download_size += size.unwrap_or(0);
+                    if *is_temporary {
+                        temporary.push((destination.clone(), size.unwrap_or(0)));
+                    }
+                },
+                PlannedAction::Verify { signature_url, heal_url, .. } => {
+                    self.urls.push(signature_url.clone());
+                    self.urls.push(heal_url.clone());
+                },
+                PlannedAction::Patch { temp_space, .. } | PlannedAction::Extract { temp_space, .. } => {
+                    let used: usize = temporary.iter().map(|(_, v)| v).sum();
+                    self.temp_space = self.temp_space.max(used + temp_space);
+                },
+                PlannedAction::Delete { location } => {
+                    temporary.retain(|(v, _)| v != location);
+                    self.deleted.push(location.clone());
+                },
+                _ => {}
+            }
+            let used: usize = temporary.iter().map(|(_, v)| v).sum();
+            self.temp_space = self.temp_space.max(used);
+        }
+        self.urls.dedup();
+        self.deleted.dedup();
+    }
+
+    /// Print this plan with `ActionPlan::print_all`.
+    pub fn print(self) -> Result<(), BeansError>
+    {
+        Self::print_all(vec![self], false)
+    }
+    /// Call `finish` on every plan, then print them. Written as JSON when
+    /// `LaunchFlag::JSON_OUTPUT` is set (as an array when `as_array` is `true`, otherwise as an
+    /// object for every plan).
+    pub fn print_all(mut plans: Vec<Self>, as_array: bool) -> Result<(), BeansError>
+    {
+        for plan in plans.iter_mut() {
+            plan.finish();
+        }
+        if flags::json_output() {
+            match as_array {
+                true => println!("{}", serde_json::to_string(&plans)?),
+                false => for plan in plans.iter() {
+                    println!("{}", serde_json::to_string(plan)?);
+                }
+            }
+            return Ok(());
+        }
+        for plan in plans.iter() {
+            println!("{}", plan);
+        }
+        Ok(())
+    }
+}
+impl Display for ActionPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let version = |v: Option<usize>| v.map(|v| format!("v{}", v)).unwrap_or("not installed".to_string());
+        writeln!(f, "{:=>60}", "=")?;
+        write!(f, "Dry run: {} {}", self.operation, self.location)?;
+        match self.target_version {
+            Some(t) if self.current_version != Some(t) => writeln!(f, " ({} -> v{})", version(self.current_version), t)?,
+            _ => writeln!(f, " ({})", version(self.current_version))?
+        }
+        writeln!(f, "{:=>60}", "=")?;
+        if self.actions.is_empty() {
+            writeln!(f, "Nothing would be done.")?;
+        }
+        for (index, action) in self.actions.iter().enumerate() {
+            writeln!(f, "{:>3}. {}", index + 1, action)?;
+        }
+        if !self.urls.is_empty() {
+            writeln!(f, "URLs:")?;
+            for url in self.urls.iter() {
+                writeln!(f, "  {}", url)?;
+            }
+        }
+        let unknown_sizes = self.actions.iter()
+            .filter(|v| matches!(v, PlannedAction::Download { size: None, .. }))
+            .count();
+        match unknown_sizes {
+            0 => writeln!(f, "Download size: {}", helper::format_size(self.download_size))?,
+            v => writeln!(f, "Download size: {} (and {} download(s) with an unknown size)", helper::format_size(self.download_size), v)?
+        }
+        writeln!(f, "Temporary space: {}", helper::format_size(self.temp_space))?;
+        for (name, files) in [("Deleted", &self.deleted), ("Overwritten", &self.overwritten)] {
+            if files.is_empty() {
+                continue;
+            }
+            writeln!(f, "{} ({}):", name, files.len())?;
+            for file in files.iter().take(PLAN_MAX_LISTED_FILES) {
+                writeln!(f, "  {}", file)?;
+            }
+            if files.len() > PLAN_MAX_LISTED_FILES {
+                writeln!(f, "  ...and {} more (use --json to see all of them)", files.len() - PLAN_MAX_LISTED_FILES)?;
+            }
+        }
+        for note in self.notes.iter() {
+            writeln!(f, "Note: {}", note)?;
+        }
+        write!(f, "{:=>60}", "=")
+    }
+}
+
+/// Return `BeansError::InvalidArgument` when `LaunchFlag::DRY_RUN` is set, for workflows that
+/// can't be planned.
+pub fn ensure_not_dry_run(operation: &str) -> Result<(), BeansError>
+{
+    if !flags::dry_run() {
+        return Ok(());
+    }
+    Err(BeansError::InvalidArgument {
+        name: "dry-run".to_string(),
+        value: operation.to_string(),
+        reason: format!("{} can't be used with --dry-run", operation)
+    })
+}
+
+/// Amount of files (not directories) in `location`.
+fn count_files(location: &str) -> usize
+{
+    let mut count: usize = 0;
+    let mut pending: Vec<std::path::PathBuf> = vec![std::path::PathBuf::from(location)];
+    while let Some(directory) = pending.pop() {
+        let entries = match std::fs::read_dir(&directory) {
+            Ok(v) => v,
+            Err(_) => continue
+        };
+        for entry in entries.flatten() {
+            match entry.file_type() {
+                Ok(v) if v.is_dir() => pending.push(entry.path()),
+                Ok(_) => count += 1,
+                Err(_) => {}
+            }
+        }
+    }
+    count
+}
+
+/// Remove everything after the host from `url`, since webhook URLs usually have a token in them.
+fn redact_url(url: &str) -> String
+{
+    match reqwest::Url::parse(url) {
+        Ok(v) => format!("{}://{}/...", v.scheme(), v.host_str().unwrap_or_default()),
+        Err(_) => "(invalid URL)".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::test_dir::TestDir;
+
+    fn download(url: &str, size: Option<usize>, destination: &str) -> PlannedAction
+    {
+        PlannedAction::Download {
+            url: url.to_string(),
+            size,
+            destination: destination.to_string(),
+            temporary: true
+        }
+    }
+    fn extract(package: &str, temp_space: usize) -> PlannedAction
+    {
+        PlannedAction::Extract {
+            package: package.to_string(),
+            format: None,
+            destination: "/srv/sourcemods".to_string(),
+            size: None,
+            files: None,
+            temp_space
+        }
+    }
+    fn delete(location: &str) -> PlannedAction
+    {
+        PlannedAction::Delete {
+            location: location.to_string()
+        }
+    }
+
+    #[test]
+    fn finish_adds_up_downloads_and_space() {
+        let mut plan = ActionPlan::for_location("install", "/srv/sourcemods/open_fortress".to_string());
+        plan.add(download("https://example.com/a.tar.zst", Some(100), "/tmp/a.tar.zst"));
+        plan.add(extract("/tmp/a.tar.zst", 300));
+        plan.add(delete("/tmp/a.tar.zst"));
+        plan.add(download("https://example.com/b.pwr", Some(50), "/tmp/b.pwr"));
+        plan.add(PlannedAction::Patch {
+            from: 1,
+            to: 2,
+            patch: "/tmp/b.pwr".to_string(),
+            location: "/srv/sourcemods/open_fortress".to_string(),
+            staging: "/srv/sourcemods/butler-staging".to_string(),
+            temp_space: 20
+        });
+        plan.add(PlannedAction::Verify {
+            location: "/srv/sourcemods/open_fortress".to_string(),
+            signature_url: "https://example.com/sig".to_string(),
+            heal_url: "https://example.com/heal".to_string()
+        });
+        plan.finish();
+        assert_eq!(plan.urls, vec!["https://example.com/a.tar.zst", "https://example.com/b.pwr", "https://example.com/sig", "https://example.com/heal"]);
+        assert_eq!(plan.download_size, 150);
+        // the package is still there while it's extracted, and is deleted before the patch.
+        assert_eq!(plan.temp_space, 400);
+        assert_eq!(plan.deleted, vec!["/tmp/a.tar.zst"]);
+
+        // finish can be called again without counting anything twice.
+        plan.finish();
+        assert_eq!(plan.download_size, 150);
+        assert_eq!(plan.urls.len(), 4);
+    }
+
+    #[test]
+    fn use_cached_replaces_download() {
+        let mut plan = ActionPlan::for_location("update", "/srv/sourcemods/open_fortress".to_string());
+        plan.add(download("https://example.com/a.tar.zst", Some(100), "/tmp/a.tar.zst"));
+        plan.add(extract("/tmp/a.tar.zst", 300));
+        plan.add(delete("/tmp/a.tar.zst"));
+        plan.use_cached("https://example.com/a.tar.zst", "/var/cache/beans-rs/a.tar.zst".to_string());
+        plan.finish();
+        assert!(matches!(&plan.actions[0], PlannedAction::UseCachedFile { location } if location == "/var/cache/beans-rs/a.tar.zst"));
+        assert!(matches!(&plan.actions[1], PlannedAction::Extract { package, .. } if package == "/var/cache/beans-rs/a.tar.zst"));
+        assert_eq!(plan.actions.len(), 2);
+        assert!(plan.urls.is_empty() && plan.deleted.is_empty());
+        assert_eq!(plan.download_size, 0);
+    }
+
+    #[test]
+    fn overwritten_and_notes() {
+        let dir = TestDir::new("plan-overwritten");
+        std::fs::create_dir_all(dir.join("open_fortress/cfg")).unwrap();
+        std::fs::write(dir.join("open_fortress/cfg/autoexec.cfg"), "exec").unwrap();
+        let mut plan = ActionPlan::for_location("install", dir.join("open_fortress").to_string_lossy().to_string());
+        plan.add_overwritten(&dir.text(), &["open_fortress/cfg/autoexec.cfg".to_string(), "open_fortress/gameinfo.txt".to_string()]);
+        assert_eq!(plan.overwritten, vec![dir.join("open_fortress/cfg/autoexec.cfg").to_string_lossy().to_string()]);
+
+        // the files in the package aren't known, so every file in the mod folder could be overwritten.
+        plan.add_extract("https://example.com/a.tar.zst".to_string(), None, &dir.text(), None, Some(10));
+        plan.add_extract("https://example.com/a.tar.zst".to_string(), None, &dir.text(), None, Some(10));
+        assert_eq!(plan.notes.len(), 1);
+        assert!(plan.notes[0].contains("Any of the 1 files"));
+    }
+
+    #[test]
+    fn webhook_urls_are_redacted() {
+        assert_eq!(redact_url("https://discord.com/api/webhooks/123/secret"), "https://discord.com/...");
+        assert_eq!(redact_url("not a url"), "(invalid URL)");
+    }
+
+    #[test]
+    fn display() {
+        let mut plan = ActionPlan::for_location("verify", "/srv/sourcemods/open_fortress".to_string());
+        plan.finish();
+        let text = plan.to_string();
+        assert!(text.contains("Dry run: verify /srv/sourcemods/open_fortress (not installed)"));
+        assert!(text.contains("Nothing would be done."));
+
+        plan.current_version = Some(1);
+        plan.target_version = Some(2);
+        plan.add(download("https://example.com/a.tar.zst", None, "/tmp/a.tar.zst"));
+        plan.finish();
+        let text = plan.to_string();
+        assert!(text.contains("(v1 -> v2)"));
+        assert!(text.contains("  1. Download https://example.com/a.tar.zst (unknown size) to /tmp/a.tar.zst, deleted afterwards"));
+        assert!(text.contains("(and 1 download(s) with an unknown size)"));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,457 @@
+use log::{debug, info};
+use crate::{BeansError, helper, RunnerContext};
+use crate::vdf::{self, VdfMap, VdfValue};
+use crate::workflows::{join_args, LaunchTarget, LaunchWorkflow};
+
+/// Locations (relative to the mod folder) of icons that can be used for shortcuts, in order of
+/// preference.
+pub const ICON_CANDIDATES: &[&str] = &[
+    "resource/game.png",
+    "resource/game.svg",
+    "resource/game.ico",
+    "resource/icon.png",
+    "resource/icon.ico"
+];
+/// Icon name that is used for desktop entries when the mod doesn't have an icon.
+pub const DESKTOP_ENTRY_FALLBACK_ICON: &str = "steam";
+
+#[derive(Debug, Clone)]
+pub struct ShortcutWorkflow {
+    pub context: RunnerContext
+}
+impl ShortcutWorkflow {
+    /// Create a desktop entry when `desktop_entry` is `true`, and add a non-Steam shortcut for
+    /// every Steam user when `steam_shortcut` is `true`.
+    pub fn create(ctx: &mut RunnerContext, desktop_entry: bool, steam_shortcut: bool) -> Result<(), BeansError>
+    {
+        if !desktop_entry && !steam_shortcut {
+            return Ok(());
+        }
+        crate::workflows::ensure_not_dry_run("shortcut")?;
+        let mod_location = ctx.get_mod_location();
+        if !helper::is_directory(mod_location.clone()) {
+            return Err(BeansError::TargetSourcemodNotInstalled {
+                missing_file: mod_location,
+                backtrace: std::backtrace::Backtrace::capture()
+            });
+        }
+        if desktop_entry {
+            if let Some(location) = Self::create_desktop_entry(ctx)? {
+                println!("Created desktop entry at {}", location);
+            }
+        }
+        if steam_shortcut {
+            for location in Self::create_steam_shortcut(ctx)? {
+                println!("Added Steam shortcut to {}", location);
+            }
+            println!("Restart Steam for the shortcut to show up in your library.");
+        }
+        Ok(())
+    }
+
+    /// Find the icon for the mod in its folder. Returns `None` when none of the files in
+    /// `ICON_CANDIDATES` exist.
+    pub fn find_icon(ctx: &mut RunnerContext) -> Option<String>
+    {
+        let mod_location = ctx.get_mod_location();
+        ICON_CANDIDATES.iter()
+            .map(|v| helper::join_path(mod_location.clone(), v.to_string()))
+            .find(|v| helper::file_exists(v.clone()))
+    }
+
+    /// Write a freedesktop `.desktop` file for the mod to the applications folder of the current
+    /// user. Returns the location of the file that was written.
+    #[cfg(not(target_os = "windows"))]
+    pub fn create_desktop_entry(ctx: &mut RunnerContext) -> Result<Option<String>, BeansError>
+    {
+        let av = crate::appvar::parse();
+        let directory = get_applications_directory()?;
+        if !helper::file_exists(directory.clone()) {
+            if let Err(e) = std::fs::create_dir_all(&directory) {
+                return Err(BeansError::FileWriteFailure {
+                    location: directory,
+                    error: e
+                });
+            }
+        }
+        let location = helper::join_path(directory, format!("beans-{}.desktop", av.mod_info.sourcemod_name));
+
+        let mut lines: Vec<String> = vec![
+            String::from("[Desktop Entry]"),
+            String::from("Type=Application"),
+            format!("Name={}", av.mod_info.name_stylized),
+            format!("Comment=Play {}", av.mod_info.name_stylized)
+        ];
+        match LaunchWorkflow::get_target(ctx, Vec::new()) {
+            LaunchTarget::Executable { program, working_directory, args } => {
+                let mut exec = vec![program];
+                exec.extend(args);
+                lines.push(format!("Exec={}", desktop_exec(&exec)));
+                lines.push(format!("Path={}", working_directory));
+            },
+            LaunchTarget::SteamUrl(url) => {
+                lines.push(format!("Exec={}", desktop_exec(&[String::from("xdg-open"), url])));
+            }
+        }
+        let icon = Self::find_icon(ctx).unwrap_or(DESKTOP_ENTRY_FALLBACK_ICON.to_string());
+        lines.push(format!("Icon={}", icon));
+        lines.push(String::from("Terminal=false"));
+        lines.push(String::from("Categories=Game;"));
+        let content = lines.join("\n") + "\n";
+        debug!("[ShortcutWorkflow::create_desktop_entry] writing to {}\n{}", location, content);
+
+        if let Err(e) = std::fs::write(&location, content) {
+            return Err(BeansError::FileWriteFailure {
+                location,
+                error: e
+            });
+        }
+        Ok(Some(location))
+    }
+    /// Desktop entries are only a thing on Linux, so nothing is done on Windows.
+    #[cfg(target_os = "windows")]
+    pub fn create_desktop_entry(_ctx: &mut RunnerContext) -> Result<Option<String>, BeansError>
+    {
+        log::warn!("[ShortcutWorkflow::create_desktop_entry] Desktop entries are only supported on Linux, ignoring.");
+        Ok(None)
+    }
+
+    /// Add (or update) a non-Steam shortcut for the mod in `shortcuts.vdf` for every Steam user.
+    /// Returns the location of every `shortcuts.vdf` file that was written to.
+    ///
+    /// Requires Source SDK Base 2013 Multiplayer to be installed, since the shortcut runs its
+    /// executable directly.
+    pub fn create_steam_shortcut(ctx: &mut RunnerContext) -> Result<Vec<String>, BeansError>
+    {
+        let (program, working_directory, args) = match LaunchWorkflow::get_target(ctx, Vec::new()) {
+            LaunchTarget::Executable { program, working_directory, args } => (program, working_directory, args),
+            LaunchTarget::SteamUrl(_) => {
+                return Err(BeansError::SourceSdkNotFound);
+            }
+        };
+        let av = crate::appvar::parse();
+        let app_name = av.mod_info.name_stylized.clone();
+        let exe = format!("\"{}\"", program);
+        let icon = Self::find_icon(ctx).unwrap_or_default();
+
+        let steam_location = helper::get_steam_location(ctx.sourcemod_path.clone())?;
+        let userdata = helper::join_path(steam_location, String::from("userdata"));
+        let locations = find_shortcuts_files(userdata.clone());
+        if locations.is_empty() {
+            return Err(BeansError::SteamUserdataNotFound {
+                location: userdata
+            });
+        }
+
+        let mut entry: VdfMap = Vec::new();
+        vdf::set(&mut entry, "appid", VdfValue::Int32(shortcut_appid(&exe, &app_name)));
+        vdf::set(&mut entry, "AppName", VdfValue::String(app_name.clone()));
+        vdf::set(&mut entry, "Exe", VdfValue::String(exe));
+        vdf::set(&mut entry, "StartDir", VdfValue::String(format!("\"{}\"", working_directory)));
+        vdf::set(&mut entry, "icon", VdfValue::String(icon));
+        vdf::set(&mut entry, "LaunchOptions", VdfValue::String(join_args(&args)));
+
+        let mut written: Vec<String> = Vec::new();
+        for location in locations {
+            let mut root = read_shortcuts_file(location.clone())?;
+            add_shortcut(&mut root, entry.clone());
+            write_shortcuts_file(location.clone(), &root)?;
+            info!("[ShortcutWorkflow::create_steam_shortcut] Wrote shortcut for {} to {}", app_name, location);
+            written.push(location);
+        }
+        Ok(written)
+    }
+}
+
+/// Get the location of `shortcuts.vdf` for every Steam user in the `userdata` folder provided.
+/// Users that don't have a `config` folder are ignored.
+fn find_shortcuts_files(userdata_location: String) -> Vec<String>
+{
+    let mut result: Vec<String> = Vec::new();
+    let dir = match std::fs::read_dir(&userdata_location) {
+        Ok(v) => v,
+        Err(e) => {
+            debug!("[ShortcutWorkflow::find_shortcuts_files] Failed to read {} ({:})", userdata_location, e);
+            return result;
+        }
+    };
+    for item in dir.flatten() {
+        let name = item.file_name().to_string_lossy().to_string();
+        // `0` is used by Steam when nobody is logged in.
+        if name == "0" || !name.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        let config = helper::join_path(userdata_location.clone(), format!("{}/config", name));
+        if helper::is_directory(config.clone()) {
+            result.push(helper::join_path(config, String::from("shortcuts.vdf")));
+        }
+    }
+    result.sort();
+    result
+}
+
+/// Read and parse `shortcuts.vdf` at the location provided. When the file doesn't exist (or is
+/// empty), a root map with an empty `shortcuts` map is returned.
+fn read_shortcuts_file(location: String) -> Result<VdfMap, BeansError>
+{
+    if !helper::file_exists(location.clone()) {
+        return Ok(vec![(String::from("shortcuts"), VdfValue::Map(Vec::new()))]);
+    }
+    let data = match std::fs::read(&location) {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(BeansError::FileOpenFailure {
+                location,
+                error: e
+            });
+        }
+    };
+    if data.is_empty() {
+        return Ok(vec![(String::from("shortcuts"), VdfValue::Map(Vec::new()))]);
+    }
+    vdf::parse(&data).map_err(|e| BeansError::SteamShortcutsParseFailure {
+        location,
+        offset: e.offset,
+        reason: e.reason
+    })
+}
+
+/// Write `root` to `location`. The existing file is copied to `shortcuts.vdf.bak` first.
+fn write_shortcuts_file(location: String, root: &VdfMap) -> Result<(), BeansError>
+{
+    if helper::file_exists(location.clone()) {
+        let backup = format!("{}.bak", location);
+        if let Err(e) = std::fs::copy(&location, &backup) {
+            return Err(BeansError::FileWriteFailure {
+                location: backup,
+                error: e
+            });
+        }
+    }
+    if let Err(e) = std::fs::write(&location, vdf::write(root)) {
+        return Err(BeansError::FileWriteFailure {
+            location,
+            error: e
+        });
+    }
+    Ok(())
+}
+
+/// Add `entry` to the `shortcuts` map in `root`. When there is already a shortcut with the same
+/// `AppName`, the values in `entry` are set on it instead, so any other values that Steam added
+/// (like tags or the last play time) are kept.
+pub fn add_shortcut(root: &mut VdfMap, entry: VdfMap)
+{
+    if vdf::get(root, "shortcuts").and_then(|v| v.as_map()).is_none() {
+        vdf::set(root, "shortcuts", VdfValue::Map(Vec::new()));
+    }
+    let shortcuts = vdf::get_mut(root, "shortcuts")
+        .and_then(|v| v.as_map_mut())
+        .expect("shortcuts was just set to a map");
+
+    let app_name = vdf::get(&entry, "AppName").and_then(|v| v.as_str()).map(|v| v.to_string());
+    let existing = shortcuts.iter_mut()
+        .filter_map(|(_, v)| v.as_map_mut())
+        .find(|v| vdf::get(v, "AppName").and_then(|x| x.as_str()).map(|x| x.to_string()) == app_name);
+    if let Some(existing) = existing {
+        debug!("[ShortcutWorkflow::add_shortcut] Updating existing shortcut {:?}", app_name);
+        for (key, value) in entry {
+            vdf::set(existing, &key, value);
+        }
+        return;
+    }
+
+    let mut item = entry;
+    for (key, value) in default_shortcut_values() {
+        if vdf::get(&item, &key).is_none() {
+            item.push((key, value));
+        }
+    }
+    // shortcuts are stored as a map where the keys are indexes, starting at 0.
+    let index = shortcuts.iter()
+        .filter_map(|(k, _)| k.parse::<usize>().ok())
+        .max()
+        .map(|v| v + 1)
+        .unwrap_or(0);
+    shortcuts.push((index.to_string(), VdfValue::Map(item)));
+}
+
+/// Values that Steam sets on every shortcut that it creates.
+fn default_shortcut_values() -> VdfMap
+{
+    vec![
+        (String::from("ShortcutPath"), VdfValue::String(String::new())),
+        (String::from("IsHidden"), VdfValue::Int32(0)),
+        (String::from("AllowDesktopConfig"), VdfValue::Int32(1)),
+        (String::from("AllowOverlay"), VdfValue::Int32(1)),
+        (String::from("OpenVR"), VdfValue::Int32(0)),
+        (String::from("Devkit"), VdfValue::Int32(0)),
+        (String::from("DevkitGameID"), VdfValue::String(String::new())),
+        (String::from("DevkitOverrideAppID"), VdfValue::Int32(0)),
+        (String::from("LastPlayTime"), VdfValue::Int32(0)),
+        (String::from("FlatpakAppID"), VdfValue::String(String::new())),
+        (String::from("tags"), VdfValue::Map(Vec::new()))
+    ]
+}
+
+/// Generate the app ID for a shortcut the same way that Steam does, which is the CRC32 of the
+/// executable and name with the high bit set.
+fn shortcut_appid(exe: &str, app_name: &str) -> i32
+{
+    let mut crc: u32 = 0xFFFFFFFF;
+    for b in exe.bytes().chain(app_name.bytes()) {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB88320,
+                _ => crc >> 1
+            };
+        }
+    }
+    ((!crc) | 0x80000000) as i32
+}
+
+/// Get the `applications` directory for the current user, which is
+/// `$XDG_DATA_HOME/applications`, or `~/.local/share/applications` when that isn't set.
+#[cfg(not(target_os = "windows"))]
+fn get_applications_directory() -> Result<String, BeansError>
+{
+    if let Ok(v) = std::env::var("XDG_DATA_HOME") {
+        if !v.is_empty() {
+            return Ok(helper::join_path(v, String::from("applications")));
+        }
+    }
+    match simple_home_dir::home_dir().and_then(|v| v.to_str().map(|x| x.to_string())) {
+        Some(home) => Ok(helper::join_path(home, String::from(".local/share/applications"))),
+        None => Err(BeansError::HomeDirectoryNotFound)
+    }
+}
+
+/// Format `args` for the `Exec` key in a desktop entry. Every argument is quoted, and characters
+/// are escaped as described in the Desktop Entry Specification.
+#[cfg(not(target_os = "windows"))]
+fn desktop_exec(args: &[String]) -> String
+{
+    args.iter()
+        .map(|arg| {
+            let mut quoted = String::from("\"");
+            for c in arg.chars() {
+                match c {
+                    '"' | '`' | '$' => {
+                        quoted.push('\\');
+                        quoted.push(c);
+                    },
+                    // backslashes have to be escaped twice, since the value of the key is also
+                    // unescaped before it's split into arguments.
+                    '\\' => quoted.push_str("\\\\\\\\"),
+                    '%' => quoted.push_str("%%"),
+                    _ => quoted.push(c)
+                }
+            }
+            quoted.push('"');
+            quoted
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_SHORTCUTS: &[u8] = include_bytes!("../../tests/data/shortcuts.vdf");
+
+    fn entry(app_name: &str, exe: &str) -> VdfMap
+    {
+        let mut entry: VdfMap = Vec::new();
+        vdf::set(&mut entry, "appid", VdfValue::Int32(shortcut_appid(exe, app_name)));
+        vdf::set(&mut entry, "AppName", VdfValue::String(app_name.to_string()));
+        vdf::set(&mut entry, "Exe", VdfValue::String(exe.to_string()));
+        vdf::set(&mut entry, "LaunchOptions", VdfValue::String(String::from("-game open_fortress")));
+        entry
+    }
+    fn shortcuts(root: &VdfMap) -> &VdfMap
+    {
+        vdf::get(root, "shortcuts").and_then(|v| v.as_map()).unwrap()
+    }
+
+    #[test]
+    fn appid_is_crc32_with_high_bit()
+    {
+        // CRC32 of "\"/games/hl2.sh\"Open Fortress" is 0x6cad842a
+        assert_eq!(shortcut_appid("\"/games/hl2.sh\"", "Open Fortress") as u32, 0xecad842a);
+        // CRC32 of "\"/opt/steam/hl2.sh\"Open Fortress" is 0xb81f2395, which already has the
+        // high bit set
+        assert_eq!(shortcut_appid("\"/opt/steam/hl2.sh\"", "Open Fortress") as u32, 0xb81f2395);
+    }
+
+    #[test]
+    fn add_shortcut_appends_with_defaults()
+    {
+        let mut root = vdf::parse(SAMPLE_SHORTCUTS).unwrap();
+        add_shortcut(&mut root, entry("Open Fortress", "\"/opt/steam/hl2.sh\""));
+        let items = shortcuts(&root);
+        assert_eq!(items.len(), 3);
+        let (key, value) = &items[2];
+        assert_eq!(key, "2");
+        let value = value.as_map().unwrap();
+        assert_eq!(vdf::get(value, "AllowOverlay"), Some(&VdfValue::Int32(1)));
+        assert!(vdf::get(value, "tags").and_then(|v| v.as_map()).is_some());
+
+        // the shortcuts that were already there are untouched
+        let original = vdf::parse(SAMPLE_SHORTCUTS).unwrap();
+        assert_eq!(items[..2], shortcuts(&original)[..]);
+    }
+
+    #[test]
+    fn add_shortcut_updates_by_app_name()
+    {
+        let mut root = vdf::parse(SAMPLE_SHORTCUTS).unwrap();
+        add_shortcut(&mut root, entry("Open Fortress", "\"/opt/steam/hl2.sh\""));
+        // Steam adds values of its own, which have to be kept.
+        {
+            let items = vdf::get_mut(&mut root, "shortcuts").and_then(|v| v.as_map_mut()).unwrap();
+            let item = items[2].1.as_map_mut().unwrap();
+            vdf::set(item, "LastPlayTime", VdfValue::Int32(1714503921));
+        }
+        let data = vdf::write(&root);
+        let mut root = vdf::parse(&data).unwrap();
+
+        add_shortcut(&mut root, entry("Open Fortress", "\"/srv/steam/hl2.sh\""));
+        let items = shortcuts(&root);
+        assert_eq!(items.len(), 3);
+        let item = items[2].1.as_map().unwrap();
+        assert_eq!(vdf::get(item, "Exe").and_then(|v| v.as_str()), Some("\"/srv/steam/hl2.sh\""));
+        assert_eq!(vdf::get(item, "appid"), Some(&VdfValue::Int32(shortcut_appid("\"/srv/steam/hl2.sh\"", "Open Fortress"))));
+        assert_eq!(vdf::get(item, "LastPlayTime"), Some(&VdfValue::Int32(1714503921)));
+        assert_eq!(item.iter().filter(|(k, _)| k.eq_ignore_ascii_case("AppName")).count(), 1);
+    }
+
+    #[test]
+    fn add_shortcut_to_empty_file()
+    {
+        let mut root: VdfMap = Vec::new();
+        add_shortcut(&mut root, entry("Open Fortress", "\"/opt/steam/hl2.sh\""));
+        let items = shortcuts(&root);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].0, "0");
+        // what is written can be read again
+        assert_eq!(vdf::parse(&vdf::write(&root)).unwrap(), root);
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn desktop_exec_escaping()
+    {
+        let args = vec![
+            String::from("/home/user/.steam/Source SDK Base 2013/hl2.sh"),
+            String::from("-game"),
+            String::from("say \"hi\" `id` $HOME"),
+            String::from("C:\\mods"),
+            String::from("100%")
+        ];
+        assert_eq!(
+            desktop_exec(&args),
+            "\"/home/user/.steam/Source SDK Base 2013/hl2.sh\" \"-game\" \"say \\\"hi\\\" \\`id\\` \\$HOME\" \"C:\\\\\\\\mods\" \"100%%\"");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,124 @@
+use crate::{BeansError, flags, RunnerContext, SymlinkReport, SymlinkState};
+use crate::a2s::{ServerStatus, WaitForEmptyConfig};
+use crate::changelog::ChangelogEntry;
+use crate::version::RemoteNotice;
+
+/// Summary of an install, which is shown by the `status` subcommand.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct StatusReport
+{
+    pub mod_name: String,
+    pub mod_location: String,
+    pub installed: bool,
+    pub current_version: Option<usize>,
+    pub channel: String,
+    pub latest_version: usize,
+    pub update_available: bool,
+    /// When `false` and `update_available` is `true`, the game has to be re-installed to update.
+    pub patch_available: bool,
+    /// Release notes for every version between `current_version` and `latest_version`.
+    pub changelog: Vec<ChangelogEntry>,
+    /// Notice from `versions.json`, when it applies to `current_version`.
+    pub notice: Option<RemoteNotice>,
+    pub beans_version: String,
+    pub min_beans_version: Option<String>,
+    /// `false` when `beans_version` is older than `min_beans_version`.
+    pub beans_version_supported: bool,
+    /// Player count of the dedicated server, when `wait_for_empty` is set in `.adastral`.
+    pub server: Option<ServerStatus>,
+    /// Symlinks from `AppVarMod.symlinks` that are missing, or don't point to their target.
+    pub broken_symlinks: Vec<SymlinkReport>
+}
+
+#[derive(Debug, Clone)]
+pub struct StatusWorkflow {
+    pub context: RunnerContext
+}
+impl StatusWorkflow {
+    /// Print the status of the install. Written as JSON when `LaunchFlag::JSON_OUTPUT` is set.
+    pub async fn wizard(ctx: &mut RunnerContext) -> Result<(), BeansError>
+    {
+        let report = Self::get(ctx).await;
+        if flags::json_output() {
+            println!("{}", serde_json::to_string(&report)?);
+        } else {
+            report.print();
+        }
+        Ok(())
+    }
+
+    /// Create a `StatusReport` for the install in `ctx`.
+    pub async fn get(ctx: &mut RunnerContext) -> StatusReport
+    {
+        let server = match WaitForEmptyConfig::get(ctx.sourcemod_path.clone()) {
+            Some(v) => Some(crate::a2s::get_server_status(&v, ctx.get_mod_location()).await),
+            None => None
+        };
+        let (latest_version, _) = ctx.latest_remote_version();
+        let update_available = ctx.current_version.is_some_and(|v| v < latest_version);
+        let changelog = match ctx.current_version {
+            Some(cv) if update_available => crate::changelog::between(&ctx.remote_version_list, cv, latest_version, &ctx.channel),
+            _ => Vec::new()
+        };
+        StatusReport {
+            mod_name: ctx.appvar.mod_info.name_stylized.clone(),
+            mod_location: ctx.get_mod_location(),
+            installed: ctx.current_version.is_some(),
+            current_version: ctx.current_version,
+            channel: ctx.channel.clone(),
+            latest_version,
+            update_available,
+            patch_available: update_available && ctx.has_patch_available().is_some(),
+            changelog,
+            notice: ctx.remote_version_list.notice.clone()
+                .filter(|v| v.applies_to(ctx.current_version)),
+            beans_version: crate::VERSION.to_string(),
+            min_beans_version: ctx.remote_version_list.min_beans_version.clone(),
+            beans_version_supported: ctx.remote_version_list.check_min_beans_version().is_ok(),
+            server,
+            broken_symlinks: match ctx.current_version {
+                Some(_) => RunnerContext::check_symlinks(ctx.get_mod_location()).into_iter()
+                    .filter(|v| v.state != SymlinkState::Ok)
+                    .collect(),
+                None => Vec::new()
+            }
+        }
+    }
+}
+impl StatusReport {
+    pub fn print(&self)
+    {
+        println!("{} ({})", self.mod_name, self.mod_location);
+        match self.current_version {
+            Some(v) => println!("Installed version: v{}", v),
+            None => println!("Installed version: not installed")
+        }
+        println!("Channel:           {}", self.channel);
+        println!("Latest version:    v{}", self.latest_version);
+        match (&self.min_beans_version, self.beans_version_supported) {
+            (Some(min), false) => println!("beans-rs version:  v{} (v{} or later is required, please update beans-rs)", self.beans_version, min),
+            _ => println!("beans-rs version:  v{}", self.beans_version)
+        }
+        if let Some(server) = &self.server {
+            match (&server.info, server.players) {
+                (Some(info), Some(players)) => println!("Server:            {}/{} players on {} ({})",
+                    players, info.max_players, info.map, server.address),
+                _ => println!("Server:            not responding ({})", server.address)
+            }
+            if let Some(last) = &server.last_wait {
+                println!("Last update:       continued at {}, {}", last.time, last.decision);
+            }
+        }
+        for symlink in self.broken_symlinks.iter() {
+            println!("Broken symlink:    {} (run \"beans-rs verify\" to repair it)", symlink);
+        }
+        if self.update_available {
+            match self.patch_available {
+                true => println!("An update is available! Run \"beans-rs update\" to install it."),
+                false => println!("An update is available, but there is no patch for the installed version. Run \"beans-rs install\" to re-install.")
+            }
+            println!();
+            crate::changelog::print(&self.changelog);
+        }
+    }
+}
\ No newline at end of file
@@ -1,5 +1,22 @@
-use log::{debug, info};
-use crate::{BeansError, butler, helper, RunnerContext};
+use log::{debug, info, warn};
+use crate::{BeansError, butler, flags, helper, RunnerContext};
+use crate::a2s::{LastWaitForEmpty, WaitForEmptyConfig, WaitForEmptyDecision};
+use crate::changelog::ChangelogEntry;
+use crate::hooks::{HookOperation, HookRunner};
+use crate::ownership::OwnershipRepair;
+use crate::rcon::RconConfig;
+use crate::webhook::{WebhookEvent, WebhookPayload};
+use crate::workflows::{ActionPlan, PlannedAction};
+
+/// Written to stdout once the game has been updated, when `LaunchFlag::JSON_OUTPUT` is set.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UpdateReport
+{
+    pub old_version: usize,
+    pub new_version: usize,
+    /// Release notes for every version between `old_version` and `new_version`.
+    pub changelog: Vec<ChangelogEntry>
+}
 
 pub struct UpdateWorkflow
 {
@@ -8,8 +25,142 @@ pub struct UpdateWorkflow
 impl UpdateWorkflow
 {
     pub async fn wizard(ctx: &mut RunnerContext) -> Result<(), BeansError>
+    {
+        let (target_version, _) = ctx.latest_remote_version();
+        if flags::dry_run() {
+            return Self::plan(ctx)?.print();
+        }
+        let repair = OwnershipRepair::new(ctx)?;
+        HookRunner::new(ctx, HookOperation::Update, Some(target_version))
+            .run(repair.run(Self::update(ctx))).await
+    }
+
+    /// Plan what `wizard` would do, without downloading the patch or writing anything.
+    pub fn plan(ctx: &mut RunnerContext) -> Result<ActionPlan, BeansError>
     {
         let av = crate::appvar::parse();
+        ctx.remote_version_list.check_min_beans_version()?;
+        let (target_version, _) = ctx.latest_remote_version();
+        let mut plan = ActionPlan::new(HookOperation::Update, ctx, Some(target_version));
+        let runner = HookRunner::new(ctx, HookOperation::Update, Some(target_version));
+        let repair = OwnershipRepair::new(ctx)?;
+        runner.plan(&mut plan, |plan| {
+            let current_version_id = match ctx.current_version {
+                Some(v) => v,
+                None => {
+                    plan.note("The game isn't installed, so it can't be updated");
+                    return Ok(());
+                }
+            };
+            let remote_version = ctx.current_remote_version()?;
+            let patch = match ctx.has_patch_available() {
+                Some(v) => v,
+                None => {
+                    plan.note("No patch is available for the version that is currently installed");
+                    return Ok(());
+                }
+            };
+            let (signature_url, heal_url) = match (remote_version.signature_url, remote_version.heal_url) {
+                (Some(s), Some(h)) => (s, h),
+                _ => {
+                    plan.note(format!("The signature or heal URL for v{} is missing, so it can't be updated", current_version_id));
+                    return Ok(());
+                }
+            };
+
+            let mod_dir_location = ctx.get_mod_location();
+            let staging_dir_location = ctx.get_staging_location();
+            // see `RunnerContext::prepare_symlink`
+            if cfg!(target_os = "linux") {
+                for symlink in ctx.appvar.mod_info.symlinks.iter() {
+                    let ln_location = helper::join_path(mod_dir_location.clone(), symlink.link.clone());
+                    if helper::file_exists(ln_location.clone()) && !helper::is_symlink(ln_location.clone()) {
+                        plan.add(PlannedAction::Delete {
+                            location: ln_location
+                        });
+                    }
+                }
+            }
+            plan.add_webhooks(ctx, WebhookEvent::UpdateAvailable);
+            let available = helper::get_free_space(ctx.sourcemod_path.clone())?;
+            if (patch.tempreq as u64) >= available {
+                plan.note(format!("Not enough free space in {}, since patching needs {}", ctx.sourcemod_path, helper::format_size(patch.tempreq)));
+            }
+
+            if let Some(config) = WaitForEmptyConfig::get(ctx.sourcemod_path.clone()) {
+                if config.stop_when_unreachable.unwrap_or(false) {
+                    plan.note(format!("The update is stopped when {} doesn't respond", config.address));
+                }
+                plan.add(PlannedAction::WaitForEmptyServer {
+                    address: config.address,
+                    timeout: config.timeout.unwrap_or(crate::a2s::DEFAULT_WAIT_TIMEOUT)
+                });
+                plan.add(PlannedAction::WriteFile {
+                    location: helper::join_path(mod_dir_location.clone(), crate::a2s::LAST_WAIT_FILENAME.to_string())
+                });
+            }
+            if let Some(config) = RconConfig::get(ctx.sourcemod_path.clone()) {
+                plan.add(PlannedAction::StopServer {
+                    address: config.address
+                });
+            }
+            if helper::file_exists(ctx.gameinfo_location()) {
+                plan.add(PlannedAction::BackupGameinfo {
+                    location: ctx.gameinfo_location()
+                });
+            }
+            plan.add(PlannedAction::Verify {
+                location: mod_dir_location.clone(),
+                signature_url: format!("{}{}", &av.remote_info.base_url, signature_url),
+                heal_url: format!("{}{}", &av.remote_info.base_url, heal_url)
+            });
+            if helper::file_exists(staging_dir_location.clone()) {
+                plan.add(PlannedAction::Delete {
+                    location: staging_dir_location.clone()
+                });
+            }
+            let patch_location = match ctx.get_cached_file(&patch.file) {
+                Some(cached) => {
+                    plan.add(PlannedAction::UseCachedFile {
+                        location: cached.clone()
+                    });
+                    cached
+                },
+                None => {
+                    // see `butler::patch_dl`
+                    let location = helper::join_path(helper::tmp_dir_location(), format!("*_{}", patch.file));
+                    plan.add(PlannedAction::Download {
+                        url: format!("{}{}", &av.remote_info.base_url, patch.file),
+                        size: None,
+                        destination: location.clone(),
+                        temporary: false
+                    });
+                    location
+                }
+            };
+            plan.add(PlannedAction::Patch {
+                from: current_version_id,
+                to: target_version,
+                patch: patch_location,
+                location: mod_dir_location.clone(),
+                staging: staging_dir_location,
+                temp_space: patch.tempreq
+            });
+            plan.note("The files that the patch changes aren't known until it's downloaded");
+            plan.add_mod_folder_fixes(mod_dir_location.clone());
+            plan.add(PlannedAction::WriteFile {
+                location: helper::join_path(mod_dir_location, ".adastral".to_string())
+            });
+            plan.add_webhooks(ctx, WebhookEvent::UpdateApplied);
+            repair.plan(plan);
+            Ok(())
+        })?;
+        Ok(plan)
+    }
+    async fn update(ctx: &mut RunnerContext) -> Result<(), BeansError>
+    {
+        let av = crate::appvar::parse();
+        ctx.remote_version_list.check_min_beans_version()?;
 
         let current_version_id = match ctx.current_version {
             Some(v) => v,
@@ -22,6 +173,7 @@ impl UpdateWorkflow
         let remote_version = ctx.current_remote_version()?;
 
         ctx.prepare_symlink()?;
+        let (target_version, _) = ctx.latest_remote_version();
         let patch = match ctx.has_patch_available() {
             Some(v) => v,
             None => {
@@ -30,10 +182,24 @@ impl UpdateWorkflow
             }
         };
 
+        let changelog = crate::changelog::between(&ctx.remote_version_list, current_version_id, target_version, &ctx.channel);
+        if !flags::json_output() {
+            crate::changelog::print(&changelog);
+        }
+        let payload = WebhookPayload::new(WebhookEvent::UpdateAvailable,
+            ctx.get_mod_location(), Some(current_version_id), Some(target_version));
+        crate::webhook::notify(ctx, payload).await;
+
         ctx.gameinfo_perms()?;
 
-        if helper::has_free_space(ctx.sourcemod_path.clone(), patch.clone().tempreq)? == false {
+        let available = helper::get_free_space(ctx.sourcemod_path.clone())?;
+        if (patch.tempreq as u64) >= available {
             println!("[UpdateWorkflow::wizard] Not enough free space! Requires {}", helper::format_size(patch.tempreq));
+            return Err(BeansError::NotEnoughFreeSpace {
+                location: ctx.sourcemod_path.clone(),
+                required: patch.tempreq,
+                available
+            });
         }
         debug!("remote_version: {:#?}", remote_version);
         if remote_version.signature_url.is_none() {
@@ -50,6 +216,9 @@ impl UpdateWorkflow
         let mod_dir_location = ctx.get_mod_location();
         let staging_dir_location = ctx.get_staging_location();
 
+        Self::wait_for_empty_server(ctx).await?;
+        Self::stop_server(ctx).await?;
+        crate::process::ensure_game_not_running(mod_dir_location.clone()).await?;
         helper::backup_gameinfo(ctx)?;
 
         ctx.gameinfo_perms()?;
@@ -63,18 +232,86 @@ impl UpdateWorkflow
         }
         ctx.gameinfo_perms()?;
         info!("[UpdateWorkflow] Patching game");
-        if let Err(e) = butler::patch_dl(
-            format!("{}{}", &av.remote_info.base_url, patch.file),
-            staging_dir_location,
-            patch.file,
-            mod_dir_location).await {
+        let result = match ctx.get_cached_file(&patch.file) {
+            Some(cached) => {
+                info!("[UpdateWorkflow] Using cached patch {}", cached);
+                if helper::file_exists(staging_dir_location.clone()) {
+                    std::fs::remove_dir_all(&staging_dir_location)?;
+                }
+                butler::patch(cached, staging_dir_location, mod_dir_location)
+            },
+            None => butler::patch_dl(
+                format!("{}{}", &av.remote_info.base_url, patch.file),
+                staging_dir_location,
+                patch.file,
+                mod_dir_location).await
+        };
+        if let Err(e) = result {
             sentry::capture_error(&e);
             return Err(e);
         }
 
         ctx.gameinfo_perms()?;
+        RunnerContext::normalize_permissions(ctx.get_mod_location())?;
+        RunnerContext::repair_symlinks(ctx.get_mod_location())?;
+        ctx.current_version = Some(target_version);
+        ctx.write_version_file()?;
+        let payload = WebhookPayload::new(WebhookEvent::UpdateApplied,
+            ctx.get_mod_location(), Some(current_version_id), Some(target_version));
+        crate::webhook::notify(ctx, payload).await;
+
+        if flags::json_output() {
+            let report = UpdateReport {
+                old_version: current_version_id,
+                new_version: target_version,
+                changelog
+            };
+            println!("{}", serde_json::to_string(&report)?);
+        } else {
+            println!("Game has been updated!");
+        }
+        Ok(())
+    }
+
+    /// Wait for the players to leave the dedicated server when `wait_for_empty` has been set up in
+    /// `.adastral`. The update continues once the server is empty or the timeout has passed.
+    /// When the server doesn't respond, the update continues unless `stop_when_unreachable` is
+    /// set, then `BeansError::ServerUnreachable` is returned.
+    async fn wait_for_empty_server(ctx: &mut RunnerContext) -> Result<(), BeansError>
+    {
+        let config = match WaitForEmptyConfig::get(ctx.sourcemod_path.clone()) {
+            Some(v) => v,
+            None => return Ok(())
+        };
+        info!("[UpdateWorkflow::wait_for_empty_server] Checking if {} is empty", config.address);
+        let decision = crate::a2s::wait_for_empty(&config).await;
+        let unreachable = decision == WaitForEmptyDecision::Unreachable;
+        let stop = unreachable && config.stop_when_unreachable.unwrap_or(false);
+        if !stop && unreachable {
+            warn!("[UpdateWorkflow::wait_for_empty_server] Continuing with the update, {}. Set stop_when_unreachable in wait_for_empty to stop the update instead", decision);
+        } else if !stop {
+            info!("[UpdateWorkflow::wait_for_empty_server] Continuing with the update, {}", decision);
+        }
+        if let Err(e) = LastWaitForEmpty::new(decision).write(ctx.get_mod_location()) {
+            warn!("[UpdateWorkflow::wait_for_empty_server] Failed to save the decision {:}", e);
+        }
+        if stop {
+            return Err(BeansError::ServerUnreachable {
+                address: config.address
+            });
+        }
+        Ok(())
+    }
 
-        println!("Game has been updated!");
+    /// Stop the dedicated server with RCON when it has been set up in `.adastral`. Nothing is
+    /// done when the server isn't running.
+    async fn stop_server(ctx: &mut RunnerContext) -> Result<(), BeansError>
+    {
+        let config = match RconConfig::get(ctx.sourcemod_path.clone()) {
+            Some(v) => v,
+            None => return Ok(())
+        };
+        crate::rcon::shutdown_server_if_running(&config, ctx.get_mod_location()).await?;
         Ok(())
     }
 }
\ No newline at end of file
@@ -1,13 +1,83 @@
-use crate::{BeansError, butler, helper, RunnerContext};
+use log::debug;
+use crate::{BeansError, butler, flags, helper, RunnerContext};
+use crate::hooks::{HookOperation, HookRunner};
+use crate::ownership::OwnershipRepair;
 use crate::version::RemoteVersion;
+use crate::webhook::{WebhookEvent, WebhookPayload};
+use crate::workflows::{ActionPlan, PlannedAction};
 
 pub struct VerifyWorkflow {
     pub ctx: RunnerContext
 }
 impl VerifyWorkflow {
     pub async fn wizard(ctx: &mut RunnerContext) -> Result<(), BeansError>
+    {
+        let current_version = ctx.current_version;
+        if flags::dry_run() {
+            return Self::plan(ctx)?.print();
+        }
+        let repair = OwnershipRepair::new(ctx)?;
+        HookRunner::new(ctx, HookOperation::Verify, current_version)
+            .run(repair.run(Self::verify(ctx))).await
+    }
+
+    /// Plan what `wizard` would do, without running butler or writing anything.
+    pub fn plan(ctx: &mut RunnerContext) -> Result<ActionPlan, BeansError>
     {
         let av = crate::appvar::parse();
+        ctx.remote_version_list.check_min_beans_version()?;
+        let current_version = ctx.current_version;
+        let mut plan = ActionPlan::new(HookOperation::Verify, ctx, current_version);
+        let runner = HookRunner::new(ctx, HookOperation::Verify, current_version);
+        let repair = OwnershipRepair::new(ctx)?;
+        runner.plan(&mut plan, |plan| {
+            let current_version_id = match ctx.current_version {
+                Some(v) => v,
+                None => {
+                    plan.note("The game isn't installed, so it can't be verified");
+                    return Ok(());
+                }
+            };
+            let remote: RemoteVersion = ctx.current_remote_version()?;
+            let (signature_url, heal_url) = match (remote.signature_url, remote.heal_url) {
+                (Some(s), Some(h)) => (s, h),
+                _ => {
+                    plan.note(format!("The signature or heal URL for v{} is missing, so it can't be verified", current_version_id));
+                    return Ok(());
+                }
+            };
+
+            let mod_dir_location = ctx.get_mod_location();
+            if helper::file_exists(ctx.gameinfo_location()) {
+                plan.add(PlannedAction::BackupGameinfo {
+                    location: ctx.gameinfo_location()
+                });
+            }
+            plan.add(PlannedAction::Verify {
+                location: mod_dir_location.clone(),
+                signature_url: format!("{}{}", &av.remote_info.base_url, signature_url),
+                heal_url: format!("{}{}", &av.remote_info.base_url, heal_url)
+            });
+            plan.note("The files that are repaired aren't known until butler has verified the game");
+            plan.add_mod_folder_fixes(mod_dir_location.clone());
+            plan.add(PlannedAction::WriteFile {
+                location: helper::join_path(mod_dir_location, ".adastral".to_string())
+            });
+            let repaired_webhooks = crate::webhook::WebhookConfig::get(ctx.sourcemod_path.clone()).iter()
+                .filter(|v| v.wants(WebhookEvent::VerifyRepaired))
+                .count();
+            if repaired_webhooks > 0 {
+                plan.note(format!("{} webhook(s) are sent when any files are repaired", repaired_webhooks));
+            }
+            repair.plan(plan);
+            Ok(())
+        })?;
+        Ok(plan)
+    }
+    async fn verify(ctx: &mut RunnerContext) -> Result<(), BeansError>
+    {
+        let av = crate::appvar::parse();
+        ctx.remote_version_list.check_min_beans_version()?;
 
         let current_version_id = match ctx.current_version {
             Some(v) => v,
@@ -29,14 +99,29 @@ impl VerifyWorkflow {
             return Ok(());
         }
 
-        helper::backup_gameinfo(ctx)?;
         let mod_dir_location = ctx.get_mod_location();
-        butler::verify(
+        crate::process::ensure_game_not_running(mod_dir_location.clone()).await?;
+        helper::backup_gameinfo(ctx)?;
+        let output = butler::verify(
             format!("{}{}", &av.remote_info.base_url, remote.signature_url.unwrap()),
             mod_dir_location.clone(),
             format!("{}{}", &av.remote_info.base_url, remote.heal_url.unwrap()))?;
         println!("[VerifyWorkflow::wizard] The verification process has completed, and any corruption has been repaired.");
         ctx.gameinfo_perms()?;
+        RunnerContext::normalize_permissions(mod_dir_location.clone())?;
+        RunnerContext::repair_symlinks(mod_dir_location.clone())?;
+        // butler may have replaced .adastral while healing, so make sure the channel is kept.
+        ctx.write_version_file()?;
+        match output.verify_result() {
+            Some(result) if result.repaired() => {
+                let mut payload = WebhookPayload::new(WebhookEvent::VerifyRepaired,
+                    mod_dir_location, Some(current_version_id), Some(current_version_id));
+                payload.details = result.details();
+                crate::webhook::notify(ctx, payload).await;
+            },
+            Some(_) => {},
+            None => debug!("[VerifyWorkflow::verify] butler didn't send a result, so it isn't known if anything was repaired")
+        }
         Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,233 @@
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use log::{error, info};
+use rand::Rng;
+use tokio::sync::Notify;
+use crate::{BeansError, RunnerContext};
+use crate::webhook::{WebhookEvent, WebhookPayload};
+use crate::workflows::UpdateWorkflow;
+
+/// Default value for `--interval`, in seconds.
+pub const DEFAULT_WATCH_INTERVAL: u64 = 3600;
+/// Default value for `--jitter`, in seconds.
+pub const DEFAULT_WATCH_JITTER: u64 = 300;
+/// How long to wait after the first failed cycle. Doubled for every failure after that.
+pub const WATCH_RETRY_DELAY: Duration = Duration::from_secs(60);
+/// Longest amount of time to wait between cycles when they keep failing.
+pub const WATCH_MAX_BACKOFF: Duration = Duration::from_secs(6 * 60 * 60);
+
+#[derive(Debug, Clone)]
+pub struct WatchOptions
+{
+    /// How long to wait between checking for updates.
+    pub interval: Duration,
+    /// Random amount of time (up to this value) that is added to `interval`, so a group of
+    /// servers don't all update at the same time.
+    pub jitter: Duration
+}
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(DEFAULT_WATCH_INTERVAL),
+            jitter: Duration::from_secs(DEFAULT_WATCH_JITTER)
+        }
+    }
+}
+
+/// What happened during a successful watch cycle.
+#[derive(Debug, Clone)]
+pub enum WatchCycleOutcome
+{
+    NotInstalled,
+    UpToDate {
+        version: usize
+    },
+    Updated {
+        from: usize,
+        to: usize
+    },
+    /// `UpdateWorkflow` finished without updating to `latest`. Counted as a failure, since it
+    /// will keep happening until `versions.json` (or the install) is fixed.
+    NotUpdated {
+        from: usize,
+        latest: usize,
+        reason: String
+    }
+}
+impl WatchCycleOutcome {
+    /// `true` when the cycle should be treated as a failed one.
+    pub fn is_failure(&self) -> bool
+    {
+        matches!(self, WatchCycleOutcome::NotUpdated { .. })
+    }
+}
+impl Display for WatchCycleOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WatchCycleOutcome::NotInstalled
+            => write!(f, "the game isn't installed, nothing to update"),
+            WatchCycleOutcome::UpToDate { version }
+            => write!(f, "v{} is the latest version", version),
+            WatchCycleOutcome::Updated { from, to }
+            => write!(f, "updated from v{} to v{}", from, to),
+            WatchCycleOutcome::NotUpdated { from, latest, reason }
+            => write!(f, "couldn't update from v{} to v{}, {}", from, latest, reason)
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WatchWorkflow {
+    pub context: RunnerContext
+}
+impl WatchWorkflow {
+    /// Check for updates every `options.interval` and install them with `UpdateWorkflow`, until
+    /// SIGTERM or Ctrl+C is received. A cycle that is in progress is always finished before
+    /// stopping.
+    ///
+    /// Failed cycles are logged, and the next cycle is delayed with an exponential backoff
+    /// (starting at `WATCH_RETRY_DELAY`, up to `WATCH_MAX_BACKOFF`).
+    pub async fn watch(ctx: &mut RunnerContext, options: WatchOptions) -> Result<(), BeansError>
+    {
+        let stopping = Arc::new(AtomicBool::new(false));
+        let shutdown = Arc::new(Notify::new());
+        {
+            let stopping = stopping.clone();
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                wait_for_shutdown_signal().await;
+                info!("[WatchWorkflow::watch] Received shutdown signal, stopping once the current cycle is done");
+                stopping.store(true, Ordering::SeqCst);
+                shutdown.notify_one();
+            });
+        }
+
+        info!("[WatchWorkflow::watch] Checking {} for updates every {}s (with up to {}s of jitter)",
+            ctx.get_mod_location(), options.interval.as_secs(), options.jitter.as_secs());
+        let mut cycle: usize = 0;
+        let mut failures: u32 = 0;
+        loop {
+            cycle += 1;
+            match Self::cycle(ctx).await {
+                Ok(outcome) if outcome.is_failure() => {
+                    failures += 1;
+                    error!("[WatchWorkflow::watch] Cycle {} failed ({} in a row), {}", cycle, failures, outcome);
+                    let mut payload = WebhookPayload::new(WebhookEvent::Failure, ctx.get_mod_location(), ctx.current_version, None);
+                    payload.error = Some(outcome.to_string());
+                    crate::webhook::notify(ctx, payload).await;
+                },
+                Ok(outcome) => {
+                    failures = 0;
+                    info!("[WatchWorkflow::watch] Cycle {} done, {}", cycle, outcome);
+                },
+                Err(e) => {
+                    failures += 1;
+                    sentry::capture_error(&e);
+                    error!("[WatchWorkflow::watch] Cycle {} failed ({} in a row), {:}", cycle, failures, e);
+                }
+            }
+            if stopping.load(Ordering::SeqCst) {
+                break;
+            }
+
+            let delay = Self::next_delay(&options, failures);
+            info!("[WatchWorkflow::watch] Next check in {}s", delay.as_secs());
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {},
+                _ = shutdown.notified() => break
+            }
+        }
+        info!("[WatchWorkflow::watch] Stopped after {} cycle(s)", cycle);
+        Ok(())
+    }
+
+    /// Fetch `versions.json` again, and update the game when there is a newer version.
+    pub async fn cycle(ctx: &mut RunnerContext) -> Result<WatchCycleOutcome, BeansError>
+    {
+        // failures while updating are sent by `HookRunner`, so only this one is sent here.
+        if let Err(e) = ctx.set_remote_version_list().await {
+            let payload = WebhookPayload::new(WebhookEvent::Failure, ctx.get_mod_location(), ctx.current_version, None)
+                .with_error(&e);
+            crate::webhook::notify(ctx, payload).await;
+            return Err(e);
+        }
+        ctx.current_version = crate::version::get_current_version(Some(ctx.sourcemod_path.clone()));
+        ctx.channel = RunnerContext::read_channel(ctx.sourcemod_path.clone());
+
+        let current_version = match ctx.current_version {
+            Some(v) => v,
+            None => return Ok(WatchCycleOutcome::NotInstalled)
+        };
+        let (latest_version, _) = ctx.latest_remote_version();
+        if latest_version <= current_version {
+            return Ok(WatchCycleOutcome::UpToDate {
+                version: current_version
+            });
+        }
+
+        info!("[WatchWorkflow::cycle] Updating from v{} to v{}", current_version, latest_version);
+        UpdateWorkflow::wizard(ctx).await?;
+        if ctx.current_version == Some(latest_version) {
+            return Ok(WatchCycleOutcome::Updated {
+                from: current_version,
+                to: latest_version
+            });
+        }
+        // `UpdateWorkflow` doesn't fail for these, it only prints why nothing was done.
+        let reason = match (ctx.has_patch_available(), ctx.current_remote_version()) {
+            (None, _) => format!("there is no patch for v{} (re-install with \"beans-rs install\")", current_version),
+            (Some(_), Ok(v)) if v.signature_url.is_none() || v.heal_url.is_none()
+            => format!("versions.json is missing the signature or heal URL for v{}", current_version),
+            _ => "the update didn't finish, check the log for details".to_string()
+        };
+        Ok(WatchCycleOutcome::NotUpdated {
+            from: current_version,
+            latest: latest_version,
+            reason
+        })
+    }
+
+    /// How long to wait before the next cycle. `failures` is the amount of cycles that have
+    /// failed in a row.
+    pub fn next_delay(options: &WatchOptions, failures: u32) -> Duration
+    {
+        let base = match failures {
+            0 => options.interval,
+            n => WATCH_RETRY_DELAY
+                .saturating_mul(2u32.saturating_pow(n - 1))
+                .min(WATCH_MAX_BACKOFF)
+        };
+        let jitter = match options.jitter.as_secs() {
+            0 => 0,
+            j => rand::thread_rng().gen_range(0..=j)
+        };
+        base + Duration::from_secs(jitter)
+    }
+}
+
+/// Wait until SIGTERM or SIGINT is received.
+#[cfg(not(target_os = "windows"))]
+async fn wait_for_shutdown_signal()
+{
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut sigterm = match signal(SignalKind::terminate()) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("[watch::wait_for_shutdown_signal] Failed to listen for SIGTERM, {:}", e);
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+    tokio::select! {
+        _ = sigterm.recv() => {},
+        _ = tokio::signal::ctrl_c() => {}
+    }
+}
+/// Wait until Ctrl+C is pressed.
+#[cfg(target_os = "windows")]
+async fn wait_for_shutdown_signal()
+{
+    let _ = tokio::signal::ctrl_c().await;
+}
\ No newline at end of file